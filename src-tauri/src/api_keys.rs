@@ -0,0 +1,370 @@
+// api_keys.rs - API-ключи для машинных клиентов
+
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use crate::auth::{User, UserRole};
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+use crate::permissions::Permission;
+
+/// Префикс ключа, по которому его легко узнать в конфигурации и логах
+const KEY_PREFIX: &str = "svk";
+
+/// Длина идентификатора ключа в байтах (64 бита)
+const ID_LEN: usize = 8;
+
+/// Длина случайной части ключа в байтах (160 бит)
+const SECRET_LEN: usize = 20;
+
+/// Отметка последнего использования сохраняется на диск не чаще этого интервала
+const LAST_USED_PERSIST_SECS: i64 = 60;
+
+/// Роль, под которой работает сессия API-ключа
+pub const API_KEY_ROLE: &str = "api_key";
+
+/// Хранимая запись ключа (сам ключ не хранится, только его хеш)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: BTreeSet<Permission>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Состояние ключа
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyStatus {
+    Active,
+    Expired,
+    Revoked,
+}
+
+impl ApiKey {
+    pub fn status_at(&self, now: DateTime<Utc>) -> ApiKeyStatus {
+        if self.revoked_at.is_some() {
+            ApiKeyStatus::Revoked
+        } else if self.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            ApiKeyStatus::Expired
+        } else {
+            ApiKeyStatus::Active
+        }
+    }
+}
+
+/// Сведения о ключе для администратора (без хеша)
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeySummary {
+    pub id: String,
+    pub name: String,
+    pub scopes: BTreeSet<Permission>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub status: ApiKeyStatus,
+}
+
+impl ApiKeySummary {
+    fn new(key: &ApiKey, now: DateTime<Utc>) -> Self {
+        Self {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            scopes: key.scopes.clone(),
+            created_by: key.created_by.clone(),
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+            revoked_at: key.revoked_at,
+            status: key.status_at(now),
+        }
+    }
+}
+
+/// Только что выпущенный ключ: значение показывается один раз
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    pub summary: ApiKeySummary,
+}
+
+/// Субъект, вошедший по API-ключу
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKeyPrincipal {
+    pub id: String,
+    pub name: String,
+    pub scopes: BTreeSet<Permission>,
+}
+
+impl ApiKeyPrincipal {
+    /// Служебный пользователь сессии; права определяются областями ключа
+    pub fn user(&self) -> User {
+        let mut user = User::new(
+            &format!("api-key:{}", self.id),
+            String::new(),
+            UserRole::Custom(API_KEY_ROLE.to_string()),
+        );
+        user.display_name = Some(self.name.clone());
+        user
+    }
+}
+
+/// Хранилище API-ключей (JSON-файл или память)
+pub struct ApiKeyStore {
+    path: Option<PathBuf>,
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeyStore {
+    /// Хранилище в памяти (до открытия файла и для тестов)
+    pub fn in_memory() -> Self {
+        Self { path: None, keys: Vec::new() }
+    }
+
+    /// Открытие файлового хранилища
+    pub fn open(path: &Path) -> Result<Self> {
+        let keys = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path: Some(path.to_path_buf()), keys })
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(&self.keys)?)?;
+        }
+        Ok(())
+    }
+
+    /// Выпуск нового ключа
+    pub fn create(
+        &mut self,
+        name: &str,
+        scopes: BTreeSet<Permission>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: &str,
+    ) -> Result<CreatedApiKey> {
+        let now = Utc::now();
+        if name.trim().is_empty() {
+            return Err(SurveillanceError::config_error("api_key_name_empty"));
+        }
+        if scopes.is_empty() {
+            return Err(SurveillanceError::config_error("api_key_scopes_empty"));
+        }
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(SurveillanceError::config_error("api_key_expiry_past"));
+        }
+
+        // По идентификатору ключ проверяется и отзывается, поэтому он уникален в хранилище
+        let id = loop {
+            let mut id_bytes = [0u8; ID_LEN];
+            rand::thread_rng().fill_bytes(&mut id_bytes);
+            let id = hex_encode(&id_bytes);
+            if !self.keys.iter().any(|record| record.id == id) {
+                break id;
+            }
+        };
+        let mut secret = [0u8; SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        let key = format!(
+            "{}_{}_{}",
+            KEY_PREFIX,
+            id,
+            data_encoding::BASE32_NOPAD.encode(&secret).to_lowercase()
+        );
+
+        let record = ApiKey {
+            id,
+            name: name.trim().to_string(),
+            key_hash: hash_key(&key),
+            scopes,
+            created_by: created_by.to_string(),
+            created_at: now,
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+        };
+        let summary = ApiKeySummary::new(&record, now);
+        self.keys.push(record);
+        self.save()?;
+
+        Ok(CreatedApiKey { key, summary })
+    }
+
+    /// Проверка предъявленного ключа
+    pub fn authenticate(&mut self, key: &str) -> Result<ApiKeyPrincipal> {
+        self.authenticate_at(key, Utc::now())
+    }
+
+    fn authenticate_at(&mut self, key: &str, now: DateTime<Utc>) -> Result<ApiKeyPrincipal> {
+        let id = parse_key_id(key).ok_or(SurveillanceError::InvalidCredentials)?;
+        let index = self.keys
+            .iter()
+            .position(|record| record.id == id && record.key_hash == hash_key(key))
+            .ok_or(SurveillanceError::InvalidCredentials)?;
+
+        // Проверяется именно совпавшая запись, а не первая с тем же идентификатором
+        self.check_record_at(index, now)?;
+        let record = &self.keys[index];
+        Ok(ApiKeyPrincipal {
+            id: record.id.clone(),
+            name: record.name.clone(),
+            scopes: record.scopes.clone(),
+        })
+    }
+
+    /// Проверка, что ключ сессии не отозван и не истёк; отмечает использование
+    pub fn check_active(&mut self, id: &str) -> Result<()> {
+        self.check_active_at(id, Utc::now())
+    }
+
+    fn check_active_at(&mut self, id: &str, now: DateTime<Utc>) -> Result<()> {
+        let index = self.keys
+            .iter()
+            .position(|record| record.id == id)
+            .ok_or(SurveillanceError::InvalidCredentials)?;
+        self.check_record_at(index, now)
+    }
+
+    /// Статус записи и отметка её использования
+    fn check_record_at(&mut self, index: usize, now: DateTime<Utc>) -> Result<()> {
+        let record = &mut self.keys[index];
+        match record.status_at(now) {
+            ApiKeyStatus::Revoked => return Err(SurveillanceError::auth_error("api_key_revoked")),
+            ApiKeyStatus::Expired => return Err(SurveillanceError::auth_error("api_key_expired")),
+            ApiKeyStatus::Active => {}
+        }
+
+        let persist = record
+            .last_used_at
+            .is_none_or(|used| now - used >= Duration::seconds(LAST_USED_PERSIST_SECS));
+        record.last_used_at = Some(now);
+        if persist {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Отзыв ключа (запись сохраняется для истории)
+    pub fn revoke(&mut self, id: &str) -> Result<()> {
+        let record = self.keys
+            .iter_mut()
+            .find(|record| record.id == id)
+            .ok_or_else(|| SurveillanceError::config_error(Detail::new("api_key_not_found").with("id", id)))?;
+
+        if record.revoked_at.is_none() {
+            record.revoked_at = Some(Utc::now());
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Список ключей, новые в конце
+    pub fn list(&self) -> Vec<ApiKeySummary> {
+        let now = Utc::now();
+        self.keys.iter().map(|key| ApiKeySummary::new(key, now)).collect()
+    }
+}
+
+/// Идентификатор из ключа вида `svk_<id>_<секрет>`
+fn parse_key_id(key: &str) -> Option<&str> {
+    let mut parts = key.trim().splitn(3, '_');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(KEY_PREFIX), Some(id), Some(secret)) if !id.is_empty() && !secret.is_empty() => Some(id),
+        _ => None,
+    }
+}
+
+/// Ключи случайные и длинные, поэтому достаточно SHA-256 без соли
+fn hash_key(key: &str) -> String {
+    hex_encode(&Sha256::digest(key.trim().as_bytes()))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(permissions: &[Permission]) -> BTreeSet<Permission> {
+        permissions.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_create_and_authenticate() {
+        let path = std::env::temp_dir().join(format!("api-keys-{}.json", uuid::Uuid::new_v4()));
+        let mut store = ApiKeyStore::open(&path).unwrap();
+        let created = store.create("rtsp-server", scopes(&[Permission::ViewLive]), None, "admin").unwrap();
+        assert!(created.key.starts_with("svk_"));
+        assert_eq!(created.summary.id.len(), ID_LEN * 2);
+
+        // На диске только хеш
+        let stored = std::fs::read_to_string(&path).unwrap();
+        assert!(!stored.contains(&created.key));
+
+        let mut store = ApiKeyStore::open(&path).unwrap();
+        let principal = store.authenticate(&created.key).unwrap();
+        assert_eq!(principal.name, "rtsp-server");
+        assert_eq!(principal.scopes, scopes(&[Permission::ViewLive]));
+        assert!(store.list()[0].last_used_at.is_some());
+
+        let forged = format!("{}x", created.key);
+        assert!(matches!(store.authenticate(&forged), Err(SurveillanceError::InvalidCredentials)));
+        assert!(matches!(store.authenticate("garbage"), Err(SurveillanceError::InvalidCredentials)));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_expiry_and_revocation() {
+        let mut store = ApiKeyStore::in_memory();
+        assert!(store.create("empty", BTreeSet::new(), None, "admin").is_err());
+
+        let now = Utc::now();
+        let created = store
+            .create("monitoring", scopes(&[Permission::ViewAuditLog]), Some(now + Duration::hours(1)), "admin")
+            .unwrap();
+        assert!(store.authenticate_at(&created.key, now).is_ok());
+        assert!(store.authenticate_at(&created.key, now + Duration::hours(2)).is_err());
+
+        store.revoke(&created.summary.id).unwrap();
+        assert!(store.authenticate(&created.key).is_err());
+        assert_eq!(store.list()[0].status, ApiKeyStatus::Revoked);
+
+        // Хранилища прежних версий могли содержать совпавшие идентификаторы:
+        // статус берётся у записи, совпавшей по хешу
+        let legacy_key = format!("{}_{}_legacysecret", KEY_PREFIX, created.summary.id);
+        store.keys.push(ApiKey {
+            id: created.summary.id.clone(),
+            name: "legacy".to_string(),
+            key_hash: hash_key(&legacy_key),
+            scopes: scopes(&[Permission::ViewLive]),
+            created_by: "admin".to_string(),
+            created_at: now,
+            expires_at: None,
+            last_used_at: None,
+            revoked_at: None,
+        });
+        let principal = store.authenticate(&legacy_key).unwrap();
+        assert_eq!(principal.name, "legacy");
+        assert!(store.keys[1].last_used_at.is_some());
+    }
+}
@@ -0,0 +1,341 @@
+// api_server.rs - Локальный HTTP API для машинных клиентов по API-ключам
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use crate::api_keys::ApiKeyPrincipal;
+use crate::error::{CommandError, SurveillanceError, Result};
+use crate::i18n::{Detail, Locale};
+use crate::permissions::Permission;
+
+/// Предел размера заголовков запроса
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// Время на получение запроса целиком
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Настройки HTTP API
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ApiServerSettings {
+    pub enabled: bool,        // Выключен, пока администратор его не включит
+    pub bind_address: String, // Адрес прослушивания (по умолчанию только локальный)
+}
+
+impl Default for ApiServerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1:8787".to_string(),
+        }
+    }
+}
+
+/// Запрос, прошедший проверку ключа и области
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub principal: ApiKeyPrincipal,
+    pub query: BTreeMap<String, String>,
+}
+
+/// Ответ HTTP API
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    fn error(status: u16, error: &SurveillanceError) -> Self {
+        let body = serde_json::to_value(CommandError::new(error, Locale::default())).unwrap_or(Value::Null);
+        Self { status, body }
+    }
+}
+
+pub type ApiAuthenticator = Box<dyn Fn(&str) -> Result<ApiKeyPrincipal> + Send + Sync>;
+pub type ApiHandler = Box<dyn Fn(&ApiRequest) -> Result<Value> + Send + Sync>;
+
+struct ApiRoute {
+    path: &'static str,
+    permission: Permission,
+    handler: ApiHandler,
+}
+
+/// Маршруты HTTP API.
+///
+/// Ключ проверяется на каждом запросе и не открывает сессию приложения:
+/// вошедший в интерфейс пользователь и клиенты API не влияют друг на друга.
+pub struct ApiRouter {
+    authenticate: ApiAuthenticator,
+    routes: Vec<ApiRoute>,
+}
+
+impl ApiRouter {
+    pub fn new(authenticate: ApiAuthenticator) -> Self {
+        Self { authenticate, routes: Vec::new() }
+    }
+
+    /// Регистрация маршрута `GET path`, доступного ключам с областью `permission`
+    pub fn route(mut self, path: &'static str, permission: Permission, handler: ApiHandler) -> Self {
+        self.routes.push(ApiRoute { path, permission, handler });
+        self
+    }
+
+    /// Обработка запроса: ключ из `Authorization: Bearer`, затем область и обработчик
+    pub fn handle(&self, method: &str, target: &str, authorization: Option<&str>) -> ApiResponse {
+        let (path, query) = split_target(target);
+        let Some(route) = self.routes.iter().find(|route| route.path == path) else {
+            return ApiResponse::error(404, &SurveillanceError::config_error("api_unknown_endpoint"));
+        };
+        if method != "GET" {
+            return ApiResponse::error(405, &SurveillanceError::config_error("api_method_not_allowed"));
+        }
+
+        let Some(key) = authorization.and_then(bearer_token) else {
+            return ApiResponse::error(401, &SurveillanceError::InvalidCredentials);
+        };
+        let principal = match (self.authenticate)(key) {
+            Ok(principal) => principal,
+            Err(e) => return ApiResponse::error(401, &e),
+        };
+        if !principal.scopes.contains(&route.permission) {
+            log::warn!("API-ключу {} отказано в праве {:?} ({})", principal.id, route.permission, path);
+            return ApiResponse::error(403, &SurveillanceError::PermissionDenied);
+        }
+
+        match (route.handler)(&ApiRequest { principal, query }) {
+            Ok(body) => ApiResponse { status: 200, body },
+            Err(e @ SurveillanceError::PermissionDenied) => ApiResponse::error(403, &e),
+            Err(e @ SurveillanceError::ConfigError { .. }) => ApiResponse::error(400, &e),
+            Err(e) => {
+                log::error!("Ошибка обработки {} через API: {}", path, e);
+                ApiResponse::error(500, &e)
+            }
+        }
+    }
+}
+
+/// HTTP API: запуск, перезапуск при смене адреса и остановка по настройкам
+pub struct ApiServer {
+    router: Arc<ApiRouter>,
+    running: Mutex<Option<(String, JoinHandle<()>)>>,
+}
+
+impl ApiServer {
+    pub fn new(router: ApiRouter) -> Self {
+        Self { router: Arc::new(router), running: Mutex::new(None) }
+    }
+
+    /// Применение настроек; возвращает фактический адрес, если сервер работает
+    pub async fn apply_settings(&self, settings: &ApiServerSettings) -> Result<Option<String>> {
+        {
+            let mut running = self.running.lock().map_err(|e| SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", e.to_string())))?;
+            match running.as_ref() {
+                Some((address, _)) if settings.enabled && *address == settings.bind_address => {
+                    return Ok(Some(address.clone()));
+                }
+                Some(_) => {
+                    if let Some((address, task)) = running.take() {
+                        task.abort();
+                        log::info!("HTTP API на {} остановлен", address);
+                    }
+                }
+                None => {}
+            }
+        }
+        if !settings.enabled {
+            return Ok(None);
+        }
+
+        let listener = TcpListener::bind(&settings.bind_address)
+            .await
+            .map_err(|e| SurveillanceError::network_error(Detail::new("api_bind_failed").with("address", settings.bind_address.as_str()).with("error", e.to_string())))?;
+        let address = listener.local_addr()?.to_string();
+        log::info!("HTTP API слушает {}", address);
+
+        let task = tokio::spawn(serve(listener, self.router.clone()));
+        let mut running = self.running.lock().map_err(|e| SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", e.to_string())))?;
+        if let Some((_, previous)) = running.replace((settings.bind_address.clone(), task)) {
+            previous.abort();
+        }
+        Ok(Some(address))
+    }
+
+    /// Остановка сервера
+    pub fn stop(&self) {
+        if let Ok(mut running) = self.running.lock() {
+            if let Some((_, task)) = running.take() {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Приём соединений; каждое обслуживает один запрос
+async fn serve(listener: TcpListener, router: Arc<ApiRouter>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::warn!("HTTP API: ошибка приёма соединения: {}", e);
+                continue;
+            }
+        };
+        let router = router.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &router).await {
+                log::debug!("HTTP API: соединение {} закрыто: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, router: &ApiRouter) -> Result<()> {
+    let head = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| SurveillanceError::ConnectionTimeout)??;
+
+    let response = match parse_head(&head) {
+        Some((method, target, authorization)) => router.handle(method, target, authorization),
+        None => ApiResponse::error(400, &SurveillanceError::config_error("api_malformed_request")),
+    };
+
+    let body = serde_json::to_vec(&response.body)?;
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason_phrase(response.status),
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Чтение заголовков запроса (тело не нужно: все маршруты — GET)
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            buffer.truncate(end);
+            break;
+        }
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Err(SurveillanceError::network_error("api_request_too_long"));
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(SurveillanceError::network_error("api_request_truncated"));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    String::from_utf8(buffer).map_err(|_| SurveillanceError::network_error("api_malformed_request"))
+}
+
+/// Метод, адрес и заголовок `Authorization` из заголовков запроса
+fn parse_head(head: &str) -> Option<(&str, &str, Option<&str>)> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let (method, target, version) = (request_line.next()?, request_line.next()?, request_line.next()?);
+    if !version.starts_with("HTTP/1.") {
+        return None;
+    }
+    let authorization = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("Authorization"))
+        .map(|(_, value)| value.trim());
+    Some((method, target, authorization))
+}
+
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("Bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Путь и параметры запроса (без декодирования: значения — логины и числа)
+fn split_target(target: &str) -> (&str, BTreeMap<String, String>) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (name.to_string(), value.to_string())
+        })
+        .collect();
+    (path, query)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_keys::ApiKeyStore;
+    use serde_json::json;
+    use std::collections::BTreeSet;
+
+    #[tokio::test]
+    async fn test_key_checked_on_every_request() {
+        let keys = Arc::new(Mutex::new(ApiKeyStore::in_memory()));
+        let scopes: BTreeSet<Permission> = [Permission::ViewLive].into_iter().collect();
+        let created = keys.lock().unwrap().create("monitoring", scopes, None, "admin").unwrap();
+
+        let store = keys.clone();
+        let router = ApiRouter::new(Box::new(move |key| store.lock().unwrap().authenticate(key)))
+            .route("/api/v1/cameras", Permission::ViewLive, Box::new(|request| {
+                Ok(json!({ "client": request.principal.name, "limit": request.query.get("limit") }))
+            }))
+            .route("/api/v1/audit", Permission::ViewAuditLog, Box::new(|_| Ok(Value::Null)));
+        let server = ApiServer::new(router);
+        let settings = ApiServerSettings { enabled: true, bind_address: "127.0.0.1:0".to_string() };
+        let address = server.apply_settings(&settings).await.unwrap().unwrap();
+
+        let get = |path: &str, key: Option<&str>| {
+            let address = address.clone();
+            let request = match key {
+                Some(key) => format!("GET {} HTTP/1.1\r\nHost: local\r\nAuthorization: Bearer {}\r\n\r\n", path, key),
+                None => format!("GET {} HTTP/1.1\r\nHost: local\r\n\r\n", path),
+            };
+            async move {
+                let mut stream = TcpStream::connect(&address).await.unwrap();
+                stream.write_all(request.as_bytes()).await.unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).await.unwrap();
+                response
+            }
+        };
+
+        let response = get("/api/v1/cameras?limit=5", Some(&created.key)).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with(r#"{"client":"monitoring","limit":"5"}"#));
+
+        assert!(get("/api/v1/cameras", None).await.starts_with("HTTP/1.1 401"));
+        assert!(get("/api/v1/audit", Some(&created.key)).await.starts_with("HTTP/1.1 403"));
+        assert!(get("/api/v1/unknown", Some(&created.key)).await.starts_with("HTTP/1.1 404"));
+
+        // Отзыв действует на следующий же запрос
+        keys.lock().unwrap().revoke(&created.summary.id).unwrap();
+        assert!(get("/api/v1/cameras", Some(&created.key)).await.starts_with("HTTP/1.1 401"));
+
+        server.stop();
+    }
+}
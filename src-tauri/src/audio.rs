@@ -0,0 +1,250 @@
+// audio.rs - Звук камер: эксклюзивное прослушивание и громкость каждой камеры
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use crate::config::Settings;
+use crate::error::{Result, SurveillanceError};
+
+/// Настройки звука
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub exclusive_mode: bool, // Одновременно слышна только одна камера
+    pub default_volume: f32,  // Громкость камеры, для которой она не задана (0.0–1.0)
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            exclusive_mode: true,
+            default_volume: 0.7,
+        }
+    }
+}
+
+/// Состояние звука, одинаковое для всех окон.
+///
+/// Потоки камер всегда несут звук AAC; выключение и громкость применяет webview
+/// к элементу `video` камеры по событию "audio": камера слышна, если она есть
+/// в `audible`, громкость берётся из `volumes`, иначе `default_volume`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AudioState {
+    /// Камеры, звук которых слышен, в порядке включения
+    pub audible: Vec<u32>,
+    /// Громкость, заданная для камер
+    pub volumes: BTreeMap<u32, f32>,
+    pub default_volume: f32,
+    pub exclusive_mode: bool,
+}
+
+/// Получатель изменений звука (интерфейс)
+pub type AudioSink = Box<dyn Fn(&AudioState) + Send>;
+
+/// Управление звуком камер
+pub struct AudioManager {
+    settings: AudioSettings,
+    audible: Vec<u32>,
+    volumes: BTreeMap<u32, f32>,
+    sink: Option<AudioSink>,
+}
+
+impl AudioManager {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            settings: normalized(&settings.audio),
+            audible: Vec::new(),
+            volumes: BTreeMap::new(),
+            sink: None,
+        }
+    }
+
+    /// Новые настройки; при включении эксклюзивного режима остаётся последняя включённая камера
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let settings = normalized(&settings.audio);
+        if self.settings == settings {
+            return;
+        }
+        self.settings = settings;
+        if self.settings.exclusive_mode && self.audible.len() > 1 {
+            self.audible.drain(..self.audible.len() - 1);
+        }
+        self.emit();
+    }
+
+    pub fn set_sink(&mut self, sink: AudioSink) {
+        self.sink = Some(sink);
+    }
+
+    /// Включение или выключение звука камеры; возвращает, слышна ли камера теперь
+    pub fn toggle(&mut self, camera_id: u32) -> bool {
+        let audible = if self.is_audible(camera_id) {
+            self.audible.retain(|&id| id != camera_id);
+            false
+        } else {
+            // Предыдущий источник выключается автоматически
+            if self.settings.exclusive_mode {
+                self.audible.clear();
+            }
+            self.audible.push(camera_id);
+            true
+        };
+        self.emit();
+        audible
+    }
+
+    /// Выключение звука камеры (например, при остановке её потока)
+    pub fn mute(&mut self, camera_id: u32) {
+        if self.is_audible(camera_id) {
+            self.audible.retain(|&id| id != camera_id);
+            self.emit();
+        }
+    }
+
+    /// Выключение звука всех камер (выход или смена пользователя)
+    pub fn mute_all(&mut self) {
+        if !self.audible.is_empty() {
+            self.audible.clear();
+            self.emit();
+        }
+    }
+
+    pub fn set_volume(&mut self, camera_id: u32, volume: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(SurveillanceError::config_error("audio_volume_invalid"));
+        }
+        if self.volumes.insert(camera_id, volume) != Some(volume) {
+            self.emit();
+        }
+        Ok(())
+    }
+
+    pub fn volume(&self, camera_id: u32) -> f32 {
+        self.volumes.get(&camera_id).copied().unwrap_or(self.settings.default_volume)
+    }
+
+    pub fn is_audible(&self, camera_id: u32) -> bool {
+        self.audible.contains(&camera_id)
+    }
+
+    pub fn state(&self) -> AudioState {
+        AudioState {
+            audible: self.audible.clone(),
+            volumes: self.volumes.clone(),
+            default_volume: self.settings.default_volume,
+            exclusive_mode: self.settings.exclusive_mode,
+        }
+    }
+
+    fn emit(&self) {
+        if let Some(sink) = &self.sink {
+            sink(&self.state());
+        }
+    }
+}
+
+/// Громкость по умолчанию вне диапазона приводится к допустимой
+fn normalized(settings: &AudioSettings) -> AudioSettings {
+    let default_volume = if settings.default_volume.is_finite() {
+        settings.default_volume.clamp(0.0, 1.0)
+    } else {
+        AudioSettings::default().default_volume
+    };
+    AudioSettings { default_volume, ..settings.clone() }
+}
+
+/// Звук камер приложения
+pub static AUDIO: Lazy<Mutex<AudioManager>> = Lazy::new(|| {
+    Mutex::new(AudioManager::new(&Settings::default()))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    type Events = Arc<Mutex<Vec<AudioState>>>;
+
+    fn manager(exclusive_mode: bool) -> (AudioManager, Events) {
+        let mut settings = Settings::default();
+        settings.audio.exclusive_mode = exclusive_mode;
+        let mut manager = AudioManager::new(&settings);
+        let events: Events = Arc::default();
+        let sink = events.clone();
+        manager.set_sink(Box::new(move |state| sink.lock().unwrap().push(state.clone())));
+        (manager, events)
+    }
+
+    #[test]
+    fn test_exclusive_toggle() {
+        let (mut manager, events) = manager(true);
+
+        assert!(manager.toggle(1));
+        assert!(manager.toggle(2));
+        assert_eq!(manager.state().audible, vec![2]);
+        assert!(!manager.toggle(2));
+        assert!(manager.state().audible.is_empty());
+
+        let audible: Vec<Vec<u32>> = events.lock().unwrap().iter().map(|state| state.audible.clone()).collect();
+        assert_eq!(audible, vec![vec![1], vec![2], vec![]]);
+
+        // Повторное выключение не рассылает событие
+        manager.mute(2);
+        manager.mute_all();
+        assert_eq!(events.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_shared_mode_and_settings() {
+        let (mut manager, events) = manager(false);
+        manager.toggle(1);
+        manager.toggle(2);
+        manager.toggle(3);
+        assert_eq!(manager.state().audible, vec![1, 2, 3]);
+        manager.mute(2);
+        assert_eq!(manager.state().audible, vec![1, 3]);
+
+        // Включение эксклюзивного режима оставляет последнюю камеру
+        let mut settings = Settings::default();
+        settings.audio.default_volume = 1.5;
+        manager.apply_settings(&settings);
+        let state = events.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((state.audible, state.exclusive_mode, state.default_volume), (vec![3], true, 1.0));
+    }
+
+    #[test]
+    fn test_frontend_payload() {
+        let (mut manager, events) = manager(true);
+        manager.set_volume(2, 0.5).unwrap();
+        manager.toggle(2);
+
+        // Событие "audio" и ответ команд несут то же, что читает интерфейс
+        let payload = serde_json::to_value(events.lock().unwrap().last().unwrap()).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "audible": [2],
+                "volumes": { "2": 0.5 },
+                "default_volume": manager.volume(1),
+                "exclusive_mode": true,
+            })
+        );
+        assert_eq!(payload, serde_json::to_value(manager.state()).unwrap());
+    }
+
+    #[test]
+    fn test_volume_per_camera() {
+        let (mut manager, events) = manager(true);
+        assert_eq!(manager.volume(1), 0.7);
+
+        manager.set_volume(1, 0.25).unwrap();
+        manager.set_volume(1, 0.25).unwrap();
+        assert_eq!((manager.volume(1), manager.volume(2)), (0.25, 0.7));
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        assert!(manager.set_volume(1, 1.1).is_err());
+        assert!(manager.set_volume(1, f32::NAN).is_err());
+        assert_eq!(manager.volume(1), 0.25);
+    }
+}
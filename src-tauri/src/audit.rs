@@ -0,0 +1,401 @@
+// audit.rs - Журнал аудита безопасности
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use crate::error::Result;
+
+/// Хеш, с которого начинается цепочка
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Тип события аудита
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    LoginSuccess,
+    LoginFailure,
+    TwoFactorChallenge,
+    TwoFactorFailure,
+    TwoFactorEnrolled,
+    TwoFactorDisabled,
+    Logout,
+    UserCreated,
+    UserUpdated,
+    UserRemoved,
+    PasswordChanged,
+    PasswordReset,
+    ConfigChanged,
+    AccessDenied,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    ErrorJournalCleared,
+}
+
+/// Запись журнала аудита.
+///
+/// Каждая запись содержит хеш предыдущей, поэтому изменение или удаление
+/// любой строки файла обнаруживается при проверке цепочки.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    pub target: Option<String>,
+    pub details: Value,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Хеш записи: SHA-256 от предыдущего хеша и содержимого без поля `hash`
+    fn compute_hash(&self) -> String {
+        let content = json!({
+            "seq": self.seq,
+            "timestamp": self.timestamp,
+            "actor": self.actor,
+            "action": self.action,
+            "target": self.target,
+            "details": self.details,
+        });
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(content.to_string().as_bytes());
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// Фильтр для выборки записей
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub actions: Vec<AuditAction>,
+    pub target: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor.as_ref().is_none_or(|actor| entry.actor.as_ref() == Some(actor))
+            && (self.actions.is_empty() || self.actions.contains(&entry.action))
+            && self.target.as_ref().is_none_or(|target| entry.target.as_ref() == Some(target))
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp <= to)
+    }
+}
+
+/// Результат проверки целостности журнала
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AuditVerification {
+    pub entries: u64,
+    pub valid: bool,
+    /// Номер первой записи, на которой цепочка нарушена
+    pub first_invalid_seq: Option<u64>,
+    /// Номера строк файла, которые не удалось разобрать
+    pub corrupt_lines: Vec<u64>,
+}
+
+/// Журнал аудита: JSON-строки с цепочкой хешей, только дозапись
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    memory: Vec<AuditEntry>,
+    last_hash: String,
+    next_seq: u64,
+}
+
+impl AuditLog {
+    /// Журнал в памяти (до открытия файла и для тестов)
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            memory: Vec::new(),
+            last_hash: GENESIS_HASH.to_string(),
+            next_seq: 1,
+        }
+    }
+
+    /// Открытие файлового журнала с продолжением существующей цепочки
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut journal = Self {
+            path: Some(path.to_path_buf()),
+            memory: Vec::new(),
+            last_hash: GENESIS_HASH.to_string(),
+            next_seq: 1,
+        };
+
+        let (entries, corrupt_lines) = journal.read_lines()?;
+        for line in &corrupt_lines {
+            log::error!("Журнал аудита {}: строка {} не разобрана и пропущена", path.display(), line);
+        }
+        let verification = Self::verify_entries(&entries, corrupt_lines);
+        if verification.first_invalid_seq.is_some() {
+            log::error!(
+                "Журнал аудита {} повреждён начиная с записи {:?}",
+                path.display(),
+                verification.first_invalid_seq
+            );
+        }
+        if let Some(last) = entries.last() {
+            journal.last_hash = last.hash.clone();
+            journal.next_seq = last.seq + 1;
+        }
+
+        Ok(journal)
+    }
+
+    /// Добавление записи в конец журнала
+    pub fn record(
+        &mut self,
+        actor: Option<&str>,
+        action: AuditAction,
+        target: Option<&str>,
+        details: Value,
+    ) -> Result<AuditEntry> {
+        let mut entry = AuditEntry {
+            seq: self.next_seq,
+            timestamp: Utc::now(),
+            actor: actor.map(str::to_string),
+            action,
+            target: target.map(str::to_string),
+            details,
+            prev_hash: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        match &self.path {
+            Some(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+                file.sync_data()?;
+            }
+            None => self.memory.push(entry.clone()),
+        }
+
+        self.last_hash = entry.hash.clone();
+        self.next_seq += 1;
+        Ok(entry)
+    }
+
+    /// Чтение всех записей журнала
+    fn read_entries(&self) -> Result<Vec<AuditEntry>> {
+        Ok(self.read_lines()?.0)
+    }
+
+    /// Чтение записей файла; неразобранные строки пропускаются, их номера возвращаются
+    fn read_lines(&self) -> Result<(Vec<AuditEntry>, Vec<u64>)> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok((self.memory.clone(), Vec::new())),
+        };
+        if !path.exists() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let reader = BufReader::new(std::fs::File::open(path)?);
+        let mut entries = Vec::new();
+        let mut corrupt_lines = Vec::new();
+        for (number, line) in (1u64..).zip(reader.lines()) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    log::warn!("Строка {} журнала аудита не разобрана: {}", number, e);
+                    corrupt_lines.push(number);
+                }
+            }
+        }
+        Ok((entries, corrupt_lines))
+    }
+
+    /// Выборка записей по фильтру (последние записи, если задан лимит)
+    pub fn query(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+        let mut entries: Vec<AuditEntry> = self
+            .read_entries()?
+            .into_iter()
+            .filter(|entry| filter.matches(entry))
+            .collect();
+
+        if let Some(limit) = filter.limit {
+            let skip = entries.len().saturating_sub(limit);
+            entries.drain(..skip);
+        }
+        Ok(entries)
+    }
+
+    /// Выгрузка записей в формате JSON Lines вместе с хешами для расследований
+    pub fn export(&self, filter: &AuditFilter) -> Result<String> {
+        let mut output = String::new();
+        for entry in self.query(filter)? {
+            output.push_str(&serde_json::to_string(&entry)?);
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
+    /// Проверка целостности цепочки
+    pub fn verify(&self) -> Result<AuditVerification> {
+        let (entries, corrupt_lines) = self.read_lines()?;
+        Ok(Self::verify_entries(&entries, corrupt_lines))
+    }
+
+    fn verify_entries(entries: &[AuditEntry], corrupt_lines: Vec<u64>) -> AuditVerification {
+        let mut expected_prev = GENESIS_HASH.to_string();
+
+        for (expected_seq, entry) in (1u64..).zip(entries) {
+            if entry.seq != expected_seq || entry.prev_hash != expected_prev || entry.hash != entry.compute_hash() {
+                return AuditVerification {
+                    entries: entries.len() as u64,
+                    valid: false,
+                    first_invalid_seq: Some(entry.seq),
+                    corrupt_lines,
+                };
+            }
+            expected_prev = entry.hash.clone();
+        }
+
+        AuditVerification {
+            entries: entries.len() as u64,
+            valid: corrupt_lines.is_empty(),
+            first_invalid_seq: None,
+            corrupt_lines,
+        }
+    }
+}
+
+/// Разница двух JSON-значений: список изменённых путей со старым и новым значением
+pub fn json_diff(before: &Value, after: &Value) -> Value {
+    let mut changes = Vec::new();
+    collect_diff("", before, after, &mut changes);
+    Value::Array(changes)
+}
+
+fn collect_diff(path: &str, before: &Value, after: &Value, changes: &mut Vec<Value>) {
+    match (before, after) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = format!("{}/{}", path, key);
+                collect_diff(
+                    &child,
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let child = format!("{}/{}", path, index);
+                collect_diff(
+                    &child,
+                    old.get(index).unwrap_or(&Value::Null),
+                    new.get(index).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ if before != after => {
+            changes.push(json!({ "path": path, "old": before, "new": after }));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log_path() -> PathBuf {
+        std::env::temp_dir().join(format!("audit-{}.log", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_chain_survives_reopen_and_detects_tampering() {
+        let path = temp_log_path();
+        {
+            let mut log = AuditLog::open(&path).unwrap();
+            log.record(Some("admin"), AuditAction::LoginSuccess, None, Value::Null).unwrap();
+            log.record(Some("admin"), AuditAction::ConfigChanged, Some("camera:1"), json!([])).unwrap();
+        }
+
+        let mut log = AuditLog::open(&path).unwrap();
+        let entry = log.record(None, AuditAction::LoginFailure, Some("ghost"), Value::Null).unwrap();
+        assert_eq!(entry.seq, 3);
+        assert_eq!(
+            log.verify().unwrap(),
+            AuditVerification { entries: 3, valid: true, first_invalid_seq: None, corrupt_lines: Vec::new() }
+        );
+
+        // Подмена субъекта во второй записи ломает цепочку
+        let content = std::fs::read_to_string(&path).unwrap();
+        let tampered = content.replacen("\"camera:1\"", "\"camera:2\"", 1);
+        std::fs::write(&path, tampered).unwrap();
+        assert_eq!(log.verify().unwrap().first_invalid_seq, Some(2));
+
+        // Удаление строки тоже обнаруживается
+        let lines: Vec<&str> = content.lines().collect();
+        std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(log.verify().unwrap().first_invalid_seq, Some(3));
+
+        // Испорченная строка не мешает открыть файл и продолжить запись
+        std::fs::write(&path, format!("{}\n{{oops\n{}\n", lines[0], lines[1])).unwrap();
+        let mut log = AuditLog::open(&path).unwrap();
+        let entry = log.record(Some("admin"), AuditAction::LoginSuccess, None, Value::Null).unwrap();
+        assert_eq!(entry.seq, 3);
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_seq, None);
+        assert_eq!(verification.corrupt_lines, vec![2]);
+        assert_eq!(log.query(&AuditFilter::default()).unwrap().len(), 3);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_query_filter_and_export() {
+        let mut log = AuditLog::in_memory();
+        log.record(Some("admin"), AuditAction::LoginSuccess, None, Value::Null).unwrap();
+        log.record(Some("operator1"), AuditAction::LoginSuccess, None, Value::Null).unwrap();
+        log.record(Some("admin"), AuditAction::Logout, None, Value::Null).unwrap();
+
+        let filter = AuditFilter { actor: Some("admin".to_string()), ..AuditFilter::default() };
+        assert_eq!(log.query(&filter).unwrap().len(), 2);
+
+        let filter = AuditFilter { actions: vec![AuditAction::LoginSuccess], limit: Some(1), ..AuditFilter::default() };
+        let entries = log.query(&filter).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor.as_deref(), Some("operator1"));
+
+        assert_eq!(log.export(&AuditFilter::default()).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_json_diff() {
+        let before = json!({ "cameras": [{ "id": 1, "enabled": true }], "name": "a" });
+        let after = json!({ "cameras": [{ "id": 1, "enabled": false }, { "id": 2 }], "name": "a" });
+
+        let diff = json_diff(&before, &after);
+        assert_eq!(diff, json!([
+            { "path": "/cameras/0/enabled", "old": true, "new": false },
+            { "path": "/cameras/1", "old": null, "new": { "id": 2 } },
+        ]));
+    }
+}
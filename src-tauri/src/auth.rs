@@ -1,14 +1,50 @@
 // auth.rs - Система авторизации
 
 use serde::{Deserialize, Serialize};
-use bcrypt::{hash, verify, DEFAULT_COST};
 use std::collections::HashMap;
+use crate::auth_backend::{AuthBackend, BackendIdentity, LocalBackend, UserSource};
+use crate::error::{SurveillanceError, Result as SurveillanceResult};
+use crate::i18n::{Detail, Locale};
+use crate::password::{PasswordHashPolicy, PasswordPolicy, PasswordService};
+use crate::permissions::{Permission, RoleRegistry};
+use crate::totp::{self, SecretCipher, TotpEnrollment, TwoFactorPolicy, TwoFactorState};
+
+/// Время жизни незавершённого входа, ожидающего второй фактор
+const TWO_FACTOR_CHALLENGE_TTL_SECS: i64 = 300;
+
+/// Количество попыток ввода кода на один вход
+const TWO_FACTOR_MAX_ATTEMPTS: u32 = 5;
 
 /// Роли пользователей в системе
+///
+/// Права ролей задаются в `Config::roles`; помимо встроенных ролей
+/// можно объявить произвольную именованную роль.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum UserRole {
     Admin,
     Operator,
+    Custom(String),
+}
+
+impl UserRole {
+    /// Имя роли, по которому ищется набор прав
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Admin => "Admin",
+            Self::Operator => "Operator",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Пользовательская роль с именем встроенной (`Custom("Admin")`) не получает её прав
+    pub fn is_reserved_custom(&self) -> bool {
+        match self {
+            Self::Custom(name) => [Self::Admin.name(), Self::Operator.name()]
+                .iter()
+                .any(|builtin| builtin.eq_ignore_ascii_case(name)),
+            _ => false,
+        }
+    }
 }
 
 /// Структура пользователя
@@ -17,6 +53,118 @@ pub struct User {
     pub login: String,
     pub password_hash: String,
     pub role: UserRole,
+    /// Хеши предыдущих паролей (новые в начале) для запрета повторов
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub password_history: Vec<String>,
+    /// Квартиры, доступные пользователю (`None` — все квартиры)
+    #[serde(default)]
+    pub allowed_apartments: Option<Vec<String>>,
+    /// Настройки двухфакторной аутентификации (секрет зашифрован)
+    #[serde(default)]
+    pub two_factor: Option<TwoFactorState>,
+    /// Учётная запись отключена администратором
+    #[serde(default)]
+    pub disabled: bool,
+    /// Пароль сброшен администратором и должен быть сменён при входе
+    #[serde(default)]
+    pub must_change_password: bool,
+    /// Отображаемое имя
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Время последнего успешного входа
+    #[serde(default)]
+    pub last_login: Option<chrono::DateTime<chrono::Utc>>,
+    /// Источник учётной записи (локальная или из каталога)
+    #[serde(default)]
+    pub source: UserSource,
+    /// Язык интерфейса и сообщений об ошибках
+    #[serde(default)]
+    pub locale: Locale,
+}
+
+impl User {
+    /// Создание пользователя без ограничений доступа
+    pub fn new(login: &str, password_hash: String, role: UserRole) -> Self {
+        Self {
+            login: login.to_string(),
+            password_hash,
+            role,
+            password_history: Vec::new(),
+            allowed_apartments: None,
+            two_factor: None,
+            disabled: false,
+            must_change_password: false,
+            display_name: None,
+            last_login: None,
+            source: UserSource::Local,
+            locale: Locale::default(),
+        }
+    }
+
+    /// Подключена ли и подтверждена ли 2FA
+    pub fn has_two_factor(&self) -> bool {
+        self.two_factor.as_ref().map(|state| state.confirmed).unwrap_or(false)
+    }
+
+    /// Есть ли у пользователя доступ к квартире
+    pub fn can_access_apartment(&self, apartment_name: &str) -> bool {
+        match &self.allowed_apartments {
+            Some(allowed) => allowed.iter().any(|name| name == apartment_name),
+            None => true,
+        }
+    }
+}
+
+/// Публичное представление пользователя.
+///
+/// Только этот тип уходит во фронтенд: хеши паролей, история паролей и
+/// секреты 2FA остаются на стороне бэкенда.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserInfo {
+    pub login: String,
+    pub display_name: String,
+    pub role: UserRole,
+    pub permissions: Vec<Permission>,
+    pub last_login: Option<chrono::DateTime<chrono::Utc>>,
+    pub must_change_password: bool,
+    pub locale: Locale,
+}
+
+impl UserInfo {
+    pub fn new(user: &User, roles: &RoleRegistry) -> Self {
+        Self {
+            login: user.login.clone(),
+            display_name: user.display_name.clone().unwrap_or_else(|| user.login.clone()),
+            role: user.role.clone(),
+            permissions: roles.permissions_for(&user.role).into_iter().collect(),
+            last_login: user.last_login,
+            must_change_password: user.must_change_password,
+            locale: user.locale,
+        }
+    }
+}
+
+/// Сведения о пользователе для администратора (без хешей и секретов)
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSummary {
+    #[serde(flatten)]
+    pub info: UserInfo,
+    pub disabled: bool,
+    pub two_factor_enabled: bool,
+    pub allowed_apartments: Option<Vec<String>>,
+    pub source: UserSource,
+}
+
+impl UserSummary {
+    pub fn new(user: &User, roles: &RoleRegistry) -> Self {
+        Self {
+            info: UserInfo::new(user, roles),
+            disabled: user.disabled,
+            two_factor_enabled: user.has_two_factor(),
+            allowed_apartments: user.allowed_apartments.clone(),
+            source: user.source,
+        }
+    }
 }
 
 /// Запрос на авторизацию
@@ -30,13 +178,68 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     pub success: bool,
-    pub user: Option<User>,
+    pub user: Option<UserInfo>,
     pub message: String,
+    /// Ключ сообщения в каталоге `i18n`: ключ ошибки или уточнения
+    pub message_key: String,
+    /// Код ошибки (`SurveillanceError::error_code`) при неудачном входе
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    /// Заполняется, если после пароля требуется второй фактор
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_factor: Option<TwoFactorChallenge>,
+}
+
+impl LoginResponse {
+    fn failure(error: SurveillanceError) -> Self {
+        Self {
+            success: false,
+            user: None,
+            message: error.localized_message(crate::session_locale()),
+            message_key: error.message_key().to_string(),
+            code: Some(error.error_code()),
+            two_factor: None,
+        }
+    }
+
+    /// Сообщение о ходе входа из каталога на языке сессии
+    fn notice(detail: Detail) -> Self {
+        Self {
+            success: false,
+            user: None,
+            message: detail.render(crate::session_locale()),
+            message_key: detail.key,
+            code: None,
+            two_factor: None,
+        }
+    }
+}
+
+/// Запрос второго фактора после успешной проверки пароля
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    /// Пользователь обязан сначала подключить 2FA
+    pub enrollment_required: bool,
+}
+
+/// Вход, ожидающий подтверждения вторым фактором
+struct PendingLogin {
+    login: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    attempts: u32,
 }
 
 /// Менеджер авторизации
 pub struct AuthManager {
     users: HashMap<String, User>,
+    passwords: PasswordService,
+    password_policy: PasswordPolicy,
+    two_factor_policy: TwoFactorPolicy,
+    secret_cipher: SecretCipher,
+    pending_logins: HashMap<String, PendingLogin>,
+    roles: RoleRegistry,
+    directory: Option<Box<dyn AuthBackend>>,
 }
 
 impl AuthManager {
@@ -44,6 +247,13 @@ impl AuthManager {
     pub fn new() -> Self {
         let mut manager = Self {
             users: HashMap::new(),
+            passwords: PasswordService::default(),
+            password_policy: PasswordPolicy::default(),
+            two_factor_policy: TwoFactorPolicy::default(),
+            secret_cipher: SecretCipher::ephemeral(),
+            pending_logins: HashMap::new(),
+            roles: RoleRegistry::default(),
+            directory: None,
         };
         
         // Добавляем тестовых пользователей
@@ -51,75 +261,395 @@ impl AuthManager {
         manager
     }
 
-    /// Добавление пользователей по умолчанию
-    fn add_default_users(&mut self) {
-        // Администратор
-        if let Ok(admin_hash) = hash("admin123", DEFAULT_COST) {
-            let admin = User {
-                login: "admin".to_string(),
-                password_hash: admin_hash,
-                role: UserRole::Admin,
-            };
-            self.users.insert("admin".to_string(), admin);
+    /// Создание менеджера с заданной политикой хеширования
+    pub fn with_hash_policy(policy: PasswordHashPolicy) -> SurveillanceResult<Self> {
+        let mut manager = Self {
+            users: HashMap::new(),
+            passwords: PasswordService::new(policy)?,
+            password_policy: PasswordPolicy::default(),
+            two_factor_policy: TwoFactorPolicy::default(),
+            secret_cipher: SecretCipher::ephemeral(),
+            pending_logins: HashMap::new(),
+            roles: RoleRegistry::default(),
+            directory: None,
+        };
+
+        manager.add_default_users();
+        Ok(manager)
+    }
+
+    /// Смена политики хеширования (существующие хеши обновятся при входе)
+    pub fn set_hash_policy(&mut self, policy: PasswordHashPolicy) -> SurveillanceResult<()> {
+        if self.passwords.policy() != &policy {
+            self.passwords = PasswordService::new(policy)?;
         }
+        Ok(())
+    }
+
+    /// Смена политики сложности паролей
+    pub fn set_password_policy(&mut self, policy: PasswordPolicy) {
+        self.password_policy = policy;
+    }
+
+    /// Смена политики двухфакторной аутентификации
+    pub fn set_two_factor_policy(&mut self, policy: TwoFactorPolicy) -> SurveillanceResult<()> {
+        policy.validate()?;
+        self.two_factor_policy = policy;
+        Ok(())
+    }
+
+    /// Установка реестра ролей для расчёта прав в ответах
+    pub fn set_role_registry(&mut self, roles: RoleRegistry) {
+        self.roles = roles;
+    }
+
+    /// Подключение каталога (LDAP) для пользователей, которых нет в локальном хранилище
+    pub fn set_directory_backend(&mut self, backend: Option<Box<dyn AuthBackend>>) {
+        self.directory = backend;
+    }
+
+    /// Установка ключа шифрования секретов 2FA
+    pub fn set_secret_cipher(&mut self, cipher: SecretCipher) {
+        self.secret_cipher = cipher;
+    }
 
-        // Оператор
-        if let Ok(operator_hash) = hash("operator123", DEFAULT_COST) {
-            let operator = User {
-                login: "operator1".to_string(),
-                password_hash: operator_hash,
-                role: UserRole::Operator,
+    /// Добавление пользователей по умолчанию.
+    ///
+    /// Их пароли проверяются политикой, как у любого нового пользователя;
+    /// не прошедший проверку пароль разрешает только вход со сменой пароля.
+    fn add_default_users(&mut self) {
+        for (login, password, role) in [("admin", "admin123", UserRole::Admin), ("operator1", "operator123", UserRole::Operator)] {
+            let Ok(password_hash) = self.passwords.hash(password) else {
+                continue;
             };
-            self.users.insert("operator1".to_string(), operator);
+            let mut user = User::new(login, password_hash, role);
+            user.must_change_password = self.password_policy.check(login, password, &[], &self.passwords).is_err();
+            self.users.insert(login.to_string(), user);
         }
     }
 
     /// Добавление нового пользователя
-    pub fn add_user(&mut self, login: String, password: String, role: UserRole) -> Result<(), String> {
+    pub fn add_user(&mut self, login: String, password: String, role: UserRole) -> SurveillanceResult<()> {
         if self.users.contains_key(&login) {
-            return Err("Пользователь уже существует".to_string());
+            return Err(SurveillanceError::auth_error("user_exists"));
+        }
+        if role.is_reserved_custom() {
+            return Err(SurveillanceError::auth_error("role_name_reserved"));
         }
 
-        let password_hash = hash(&password, DEFAULT_COST)
-            .map_err(|_| "Ошибка хеширования пароля")?;
+        self.password_policy.check(&login, &password, &[], &self.passwords)?;
 
-        let user = User {
-            login: login.clone(),
-            password_hash,
-            role,
-        };
+        let password_hash = self.passwords.hash(&password)?;
+
+        let user = User::new(&login, password_hash, role);
 
         self.users.insert(login, user);
         Ok(())
     }
 
     /// Проверка учётных данных
-    pub fn authenticate(&self, request: &LoginRequest) -> LoginResponse {
-        match self.users.get(&request.login) {
-            Some(user) => {
-                match verify(&request.password, &user.password_hash) {
-                    Ok(true) => LoginResponse {
-                        success: true,
-                        user: Some(user.clone()),
-                        message: "Авторизация успешна".to_string(),
-                    },
-                    Ok(false) => LoginResponse {
-                        success: false,
-                        user: None,
-                        message: "Неверный пароль".to_string(),
-                    },
-                    Err(_) => LoginResponse {
-                        success: false,
-                        user: None,
-                        message: "Ошибка проверки пароля".to_string(),
-                    },
+    ///
+    /// Локальные пользователи проверяются по хешу пароля, остальные — через
+    /// каталог, если он настроен. При успешном локальном входе хеш, созданный
+    /// более слабыми параметрами, прозрачно перехешируется по текущей политике.
+    pub fn authenticate(&mut self, request: &LoginRequest) -> LoginResponse {
+        let identity = match self.check_password(&request.login, &request.password) {
+            Ok(identity) => identity,
+            // Неизвестный логин и неверный пароль неразличимы для вызывающего:
+            // причина видна только в журнале
+            Err(e @ (SurveillanceError::UserNotFound | SurveillanceError::InvalidCredentials)) => {
+                log::warn!("Вход {} отклонён: {}", request.login, e);
+                return LoginResponse::failure(SurveillanceError::InvalidCredentials);
+            }
+            Err(e @ SurveillanceError::PermissionDenied) => return LoginResponse::failure(e),
+            Err(e) => {
+                log::error!("Ошибка проверки пароля {}: {}", request.login, e);
+                return LoginResponse::failure(SurveillanceError::auth_error("password_check_failed"));
+            }
+        };
+
+        match identity.source {
+            UserSource::Local => self.upgrade_hash_if_needed(&request.login, &request.password),
+            UserSource::Directory => self.sync_directory_user(identity),
+        }
+
+        // Статус учётной записи раскрывается только при верном пароле
+        if self.users.get(&request.login).is_some_and(|user| user.disabled) {
+            return LoginResponse::failure(SurveillanceError::AccountLocked);
+        }
+        self.complete_password_step(&request.login)
+    }
+
+    /// Проверка пароля локально, а для неизвестных локально логинов — в каталоге
+    fn check_password(&self, login: &str, password: &str) -> SurveillanceResult<BackendIdentity> {
+        let local = LocalBackend::new(&self.users, &self.passwords);
+        match (local.authenticate(login, password), &self.directory) {
+            (Err(SurveillanceError::UserNotFound), Some(directory)) => {
+                directory.authenticate(login, password).inspect_err(|e| {
+                    log::warn!("Каталог {} отклонил вход {}: {}", directory.name(), login, e);
+                })
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Создание или обновление локальной записи пользователя каталога.
+    ///
+    /// Роль и имя берутся из каталога при каждом входе; блокировка, 2FA и
+    /// доступные квартиры хранятся локально.
+    fn sync_directory_user(&mut self, identity: BackendIdentity) {
+        let user = self.users
+            .entry(identity.login.clone())
+            .or_insert_with(|| {
+                log::info!("Создана учётная запись пользователя каталога {}", identity.login);
+                User::new(&identity.login, String::new(), identity.role.clone())
+            });
+        user.source = UserSource::Directory;
+        user.role = identity.role;
+        user.display_name = identity.display_name;
+    }
+
+    /// Пароль пользователя каталога меняется только в каталоге
+    fn ensure_local_password(&self, login: &str) -> SurveillanceResult<()> {
+        match self.users.get(login) {
+            Some(user) if user.source == UserSource::Directory => {
+                Err(SurveillanceError::auth_error("password_managed_by_directory"))
+            }
+            Some(_) => Ok(()),
+            None => Err(SurveillanceError::UserNotFound),
+        }
+    }
+
+    /// Завершение входа после пароля: сразу или через второй фактор
+    fn complete_password_step(&mut self, login: &str) -> LoginResponse {
+        let user = match self.users.get(login) {
+            Some(user) => user,
+            None => return LoginResponse::failure(SurveillanceError::UserNotFound),
+        };
+
+        let required = self.two_factor_policy.is_required_for(user.role.name());
+        if !user.has_two_factor() && !required {
+            return self.login_success(login);
+        }
+
+        let enrollment_required = !user.has_two_factor();
+        let challenge_token = uuid::Uuid::new_v4().to_string();
+        self.pending_logins.retain(|_, pending| pending.expires_at > chrono::Utc::now());
+        self.pending_logins.insert(challenge_token.clone(), PendingLogin {
+            login: login.to_string(),
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(TWO_FACTOR_CHALLENGE_TTL_SECS),
+            attempts: 0,
+        });
+
+        let detail = if enrollment_required {
+            Detail::new("two_factor_enrollment_required")
+        } else {
+            Detail::new("two_factor_code_required")
+        };
+        LoginResponse {
+            two_factor: Some(TwoFactorChallenge { challenge_token, enrollment_required }),
+            ..LoginResponse::notice(detail)
+        }
+    }
+
+    /// Логин незавершённого входа по токену
+    fn pending_login(&self, challenge_token: &str) -> SurveillanceResult<String> {
+        self.pending_logins
+            .get(challenge_token)
+            .filter(|pending| pending.expires_at > chrono::Utc::now())
+            .map(|pending| pending.login.clone())
+            .ok_or(SurveillanceError::SessionExpired)
+    }
+
+    /// Начало подключения 2FA: генерирует секрет, URI для QR-кода и резервные коды
+    pub fn begin_totp_enrollment(&mut self, login: &str) -> SurveillanceResult<TotpEnrollment> {
+        let user = self.users.get(login).ok_or(SurveillanceError::UserNotFound)?;
+        if user.has_two_factor() {
+            return Err(SurveillanceError::auth_error("two_factor_already_enabled"));
+        }
+
+        let secret = totp::generate_secret();
+        let (recovery_codes, recovery_code_hashes) = totp::generate_recovery_codes();
+        let state = TwoFactorState {
+            encrypted_secret: self.secret_cipher.encrypt(&secret)?,
+            confirmed: false,
+            recovery_code_hashes,
+            last_used_step: None,
+        };
+
+        if let Some(user) = self.users.get_mut(login) {
+            user.two_factor = Some(state);
+        }
+
+        Ok(TotpEnrollment {
+            secret: totp::encode_secret(&secret),
+            provisioning_uri: totp::provisioning_uri(&secret, login, &self.two_factor_policy),
+            recovery_codes,
+        })
+    }
+
+    /// Подключение 2FA в рамках незавершённого входа (обязательная 2FA)
+    pub fn begin_totp_enrollment_for_challenge(&mut self, challenge_token: &str) -> SurveillanceResult<TotpEnrollment> {
+        let login = self.pending_login(challenge_token)?;
+        self.begin_totp_enrollment(&login)
+    }
+
+    /// Подтверждение подключения 2FA первым кодом из приложения
+    pub fn confirm_totp_enrollment(&mut self, login: &str, code: &str) -> SurveillanceResult<()> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.confirm_totp_enrollment_at(login, code, now)
+    }
+
+    fn confirm_totp_enrollment_at(&mut self, login: &str, code: &str, now: u64) -> SurveillanceResult<()> {
+        let user = self.users.get(login).ok_or(SurveillanceError::UserNotFound)?;
+        let state = user.two_factor.as_ref()
+            .filter(|state| !state.confirmed)
+            .ok_or_else(|| SurveillanceError::auth_error("two_factor_enrollment_missing"))?;
+
+        let secret = self.secret_cipher.decrypt(&state.encrypted_secret)?;
+        let step = totp::verify_code(&secret, code, now, &self.two_factor_policy, None)
+            .ok_or(SurveillanceError::InvalidCredentials)?;
+
+        if let Some(state) = self.users.get_mut(login).and_then(|user| user.two_factor.as_mut()) {
+            state.confirmed = true;
+            state.last_used_step = Some(step);
+        }
+        log::info!("Пользователь {} подключил двухфакторную аутентификацию", login);
+        Ok(())
+    }
+
+    /// Второй шаг входа: проверка кода TOTP или резервного кода
+    pub fn verify_two_factor(&mut self, challenge_token: &str, code: &str) -> LoginResponse {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.verify_two_factor_at(challenge_token, code, now)
+    }
+
+    fn verify_two_factor_at(&mut self, challenge_token: &str, code: &str, now: u64) -> LoginResponse {
+        let login = match self.pending_login(challenge_token) {
+            Ok(login) => login,
+            Err(e) => {
+                self.pending_logins.remove(challenge_token);
+                return LoginResponse::failure(e);
+            }
+        };
+
+        if let Some(pending) = self.pending_logins.get_mut(challenge_token) {
+            pending.attempts += 1;
+            if pending.attempts > TWO_FACTOR_MAX_ATTEMPTS {
+                self.pending_logins.remove(challenge_token);
+                return LoginResponse::failure(SurveillanceError::auth_error("too_many_attempts"));
+            }
+        }
+
+        let verified = match self.users.get(&login).and_then(|user| user.two_factor.clone()) {
+            // Первый код одновременно подтверждает подключение
+            Some(state) if !state.confirmed => self.confirm_totp_enrollment_at(&login, code, now).is_ok(),
+            Some(state) => self.check_second_factor(&login, &state, code, now),
+            None => {
+                return LoginResponse::failure(SurveillanceError::auth_error("two_factor_not_enabled"))
+            }
+        };
+
+        if !verified {
+            return LoginResponse::failure(SurveillanceError::auth_error("invalid_verification_code"));
+        }
+
+        self.pending_logins.remove(challenge_token);
+        self.login_success(&login)
+    }
+
+    /// Успешное завершение входа: фиксирует время входа и формирует ответ
+    fn login_success(&mut self, login: &str) -> LoginResponse {
+        let user = match self.users.get_mut(login) {
+            Some(user) => user,
+            None => return LoginResponse::failure(SurveillanceError::UserNotFound),
+        };
+        user.last_login = Some(chrono::Utc::now());
+
+        LoginResponse {
+            success: true,
+            user: Some(UserInfo::new(user, &self.roles)),
+            ..LoginResponse::notice(Detail::new("login_success"))
+        }
+    }
+
+    /// Проверка кода TOTP, а при неудаче — одноразового резервного кода
+    fn check_second_factor(&mut self, login: &str, state: &TwoFactorState, code: &str, now: u64) -> bool {
+        let secret = match self.secret_cipher.decrypt(&state.encrypted_secret) {
+            Ok(secret) => secret,
+            Err(e) => {
+                log::error!("Не удалось расшифровать секрет 2FA пользователя {}: {}", login, e);
+                return false;
+            }
+        };
+
+        let Some(state) = self.users.get_mut(login).and_then(|user| user.two_factor.as_mut()) else {
+            return false;
+        };
+
+        if let Some(step) = totp::verify_code(&secret, code, now, &self.two_factor_policy, state.last_used_step) {
+            state.last_used_step = Some(step);
+            return true;
+        }
+
+        let code_hash = totp::hash_recovery_code(code);
+        if let Some(index) = state.recovery_code_hashes.iter().position(|hash| *hash == code_hash) {
+            state.recovery_code_hashes.remove(index);
+            log::warn!("Пользователь {} вошёл по резервному коду, осталось {}", login, state.recovery_code_hashes.len());
+            return true;
+        }
+
+        false
+    }
+
+    /// Отключение 2FA (запрещено для ролей, где она обязательна).
+    ///
+    /// Требует повторного ввода пароля и, если подключение подтверждено,
+    /// текущего кода TOTP или резервного кода.
+    pub fn disable_totp(&mut self, login: &str, password: &str, code: &str) -> SurveillanceResult<()> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.disable_totp_at(login, password, code, now)
+    }
+
+    fn disable_totp_at(&mut self, login: &str, password: &str, code: &str, now: u64) -> SurveillanceResult<()> {
+        let user = self.users.get(login).ok_or(SurveillanceError::UserNotFound)?;
+        if self.two_factor_policy.is_required_for(user.role.name()) {
+            return Err(SurveillanceError::PermissionDenied);
+        }
+        let state = user.two_factor.clone();
+
+        self.check_password(login, password)?;
+        if let Some(state) = state.filter(|state| state.confirmed) {
+            if !self.check_second_factor(login, &state, code, now) {
+                return Err(SurveillanceError::auth_error("invalid_verification_code"));
+            }
+        }
+
+        if let Some(user) = self.users.get_mut(login) {
+            user.two_factor = None;
+        }
+        Ok(())
+    }
+
+    /// Перехеширование пароля, если сохранённый хеш слабее текущей политики
+    fn upgrade_hash_if_needed(&mut self, login: &str, password: &str) {
+        let needs_rehash = self.users
+            .get(login)
+            .map(|user| self.passwords.needs_rehash(&user.password_hash))
+            .unwrap_or(false);
+
+        if !needs_rehash {
+            return;
+        }
+
+        match self.passwords.hash(password) {
+            Ok(new_hash) => {
+                if let Some(user) = self.users.get_mut(login) {
+                    user.password_hash = new_hash;
+                    log::info!("Хеш пароля пользователя {} обновлён по текущей политике", login);
                 }
             }
-            None => LoginResponse {
-                success: false,
-                user: None,
-                message: "Пользователь не найден".to_string(),
-            },
+            Err(e) => log::warn!("Не удалось перехешировать пароль {}: {}", login, e),
         }
     }
 
@@ -136,44 +666,138 @@ impl AuthManager {
             .unwrap_or(false)
     }
 
+    /// Ограничение пользователя списком квартир (`None` снимает ограничение)
+    pub fn set_allowed_apartments(&mut self, login: &str, apartments: Option<Vec<String>>) -> SurveillanceResult<()> {
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        user.allowed_apartments = apartments;
+        Ok(())
+    }
+
+    /// Установка отображаемого имени
+    pub fn set_display_name(&mut self, login: &str, display_name: Option<String>) -> SurveillanceResult<()> {
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        user.display_name = display_name;
+        Ok(())
+    }
+
+    /// Выбор языка пользователя
+    pub fn set_locale(&mut self, login: &str, locale: Locale) -> SurveillanceResult<()> {
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        user.locale = locale;
+        Ok(())
+    }
+
     /// Получение списка всех пользователей (только для админа)
     pub fn get_all_users(&self) -> Vec<&User> {
         self.users.values().collect()
     }
 
+    /// Список пользователей без хешей паролей, упорядоченный по логину
+    pub fn list_users(&self) -> Vec<UserSummary> {
+        let mut users: Vec<UserSummary> = self.users
+            .values()
+            .map(|user| UserSummary::new(user, &self.roles))
+            .collect();
+        users.sort_by(|a, b| a.info.login.cmp(&b.info.login));
+        users
+    }
+
+    /// Количество активных администраторов, кроме указанного пользователя
+    fn other_active_admins(&self, login: &str) -> usize {
+        self.users
+            .values()
+            .filter(|user| user.login != login && user.role == UserRole::Admin && !user.disabled)
+            .count()
+    }
+
+    /// Включение/отключение учётной записи
+    pub fn set_user_disabled(&mut self, login: &str, disabled: bool) -> SurveillanceResult<()> {
+        let is_admin = self.is_admin(login);
+        if disabled && is_admin && self.other_active_admins(login) == 0 {
+            return Err(SurveillanceError::auth_error("cannot_disable_last_admin"));
+        }
+
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        user.disabled = disabled;
+        Ok(())
+    }
+
+    /// Смена роли пользователя (у пользователей каталога роль задаётся группами LDAP)
+    pub fn set_user_role(&mut self, login: &str, role: UserRole) -> SurveillanceResult<()> {
+        if role.is_reserved_custom() {
+            return Err(SurveillanceError::auth_error("role_name_reserved"));
+        }
+        let is_admin = self.is_admin(login);
+        if is_admin && role != UserRole::Admin && self.other_active_admins(login) == 0 {
+            return Err(SurveillanceError::auth_error("cannot_demote_last_admin"));
+        }
+
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        if user.source == UserSource::Directory {
+            return Err(SurveillanceError::auth_error("role_managed_by_directory"));
+        }
+        user.role = role;
+        Ok(())
+    }
+
+    /// Сброс пароля администратором.
+    ///
+    /// Возвращает временный пароль; пользователь обязан сменить его при
+    /// следующем входе.
+    pub fn reset_password(&mut self, login: &str) -> SurveillanceResult<String> {
+        self.ensure_local_password(login)?;
+
+        let temporary = self.password_policy.generate_temporary(login);
+        let new_hash = self.passwords.hash(&temporary)?;
+        let history_size = self.password_policy.history_size;
+
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        let old_hash = std::mem::replace(&mut user.password_hash, new_hash);
+        user.password_history.insert(0, old_hash);
+        user.password_history.truncate(history_size);
+        user.must_change_password = true;
+        Ok(temporary)
+    }
+
     /// Удаление пользователя (только для админа)
-    pub fn remove_user(&mut self, login: &str) -> Result<(), String> {
+    pub fn remove_user(&mut self, login: &str) -> SurveillanceResult<()> {
         if login == "admin" {
-            return Err("Нельзя удалить основного администратора".to_string());
+            return Err(SurveillanceError::auth_error("cannot_remove_primary_admin"));
         }
 
         match self.users.remove(login) {
             Some(_) => Ok(()),
-            None => Err("Пользователь не найден".to_string()),
+            None => Err(SurveillanceError::UserNotFound),
         }
     }
 
     /// Изменение пароля пользователя
-    pub fn change_password(&mut self, login: &str, old_password: &str, new_password: &str) -> Result<(), String> {
+    pub fn change_password(&mut self, login: &str, old_password: &str, new_password: &str) -> SurveillanceResult<()> {
+        self.ensure_local_password(login)?;
         let user = self.users.get(login)
-            .ok_or("Пользователь не найден")?;
+            .ok_or(SurveillanceError::UserNotFound)?;
 
         // Проверяем старый пароль
-        if !verify(old_password, &user.password_hash).unwrap_or(false) {
-            return Err("Неверный текущий пароль".to_string());
+        if !self.passwords.verify(old_password, &user.password_hash).unwrap_or(false) {
+            return Err(SurveillanceError::InvalidCredentials);
         }
 
+        // Текущий пароль тоже входит в историю повторов
+        let mut previous_hashes = vec![user.password_hash.clone()];
+        previous_hashes.extend(user.password_history.iter().cloned());
+        self.password_policy.check(login, new_password, &previous_hashes, &self.passwords)?;
+
         // Хешируем новый пароль
-        let new_hash = hash(new_password, DEFAULT_COST)
-            .map_err(|_| "Ошибка хеширования нового пароля")?;
+        let new_hash = self.passwords.hash(new_password)?;
 
-        // Обновляем пароль
-        if let Some(user) = self.users.get_mut(login) {
-            user.password_hash = new_hash;
-            Ok(())
-        } else {
-            Err("Пользователь не найден".to_string())
-        }
+        // Обновляем пароль, сохраняя предыдущий хеш в истории
+        let history_size = self.password_policy.history_size;
+        let user = self.users.get_mut(login).ok_or(SurveillanceError::UserNotFound)?;
+        let old_hash = std::mem::replace(&mut user.password_hash, new_hash);
+        user.password_history.insert(0, old_hash);
+        user.password_history.truncate(history_size);
+        user.must_change_password = false;
+        Ok(())
     }
 }
 
@@ -189,12 +813,12 @@ mod tests {
 
     #[test]
     fn test_authentication() {
-        let auth_manager = AuthManager::new();
+        let mut auth_manager = AuthManager::new();
         
         // Тест успешной авторизации
         let request = LoginRequest {
-            login: "admin".to_string(),
-            password: "admin123".to_string(),
+            login: "operator1".to_string(),
+            password: "operator123".to_string(),
         };
         
         let response = auth_manager.authenticate(&request);
@@ -207,9 +831,21 @@ mod tests {
             password: "wrong_password".to_string(),
         };
         
-        let response = auth_manager.authenticate(&request);
-        assert!(!response.success);
-        assert!(response.user.is_none());
+        let wrong_password = auth_manager.authenticate(&request);
+        assert!(!wrong_password.success);
+        assert!(wrong_password.user.is_none());
+        assert_eq!(wrong_password.code, Some(1009));
+
+        // Неизвестный пользователь неотличим от неверного пароля; просроченный второй шаг
+        let request = LoginRequest {
+            login: "ghost".to_string(),
+            password: "whatever".to_string(),
+        };
+        let unknown_user = auth_manager.authenticate(&request);
+        assert_eq!(unknown_user.code, wrong_password.code);
+        assert_eq!(unknown_user.message, wrong_password.message);
+        assert_eq!(auth_manager.verify_two_factor("no-such-challenge", "123456").code, Some(1014));
+        assert!(auth_manager.remove_user("ghost").is_err_and(|e| matches!(e, SurveillanceError::UserNotFound)));
     }
 
     #[test]
@@ -219,4 +855,194 @@ mod tests {
         assert!(auth_manager.is_admin("admin"));
         assert!(!auth_manager.is_admin("operator1"));
     }
+
+    #[test]
+    fn test_rehash_on_login() {
+        let mut auth_manager = AuthManager::new();
+        let legacy_hash = bcrypt::hash("legacy123", 4).unwrap();
+        auth_manager.users.insert(
+            "legacy".to_string(),
+            User::new("legacy", legacy_hash.clone(), UserRole::Operator),
+        );
+
+        let response = auth_manager.authenticate(&LoginRequest {
+            login: "legacy".to_string(),
+            password: "legacy123".to_string(),
+        });
+        assert!(response.success);
+
+        let upgraded = &auth_manager.get_user("legacy").unwrap().password_hash;
+        assert!(upgraded.starts_with("$argon2id$"));
+        assert_ne!(upgraded, &legacy_hash);
+    }
+
+    #[test]
+    fn test_password_policy_enforced() {
+        let mut auth_manager = AuthManager::new();
+
+        let result = auth_manager.add_user("ivan".to_string(), String::new(), UserRole::Operator);
+        assert!(matches!(result, Err(SurveillanceError::PasswordPolicy { .. })));
+
+        auth_manager
+            .add_user("ivan".to_string(), "Kx7pLm29qZ".to_string(), UserRole::Operator)
+            .unwrap();
+        auth_manager.change_password("ivan", "Kx7pLm29qZ", "Tr4vel2Moon").unwrap();
+
+        // Повтор недавнего пароля запрещён
+        let result = auth_manager.change_password("ivan", "Tr4vel2Moon", "Kx7pLm29qZ");
+        assert!(matches!(result, Err(SurveillanceError::PasswordPolicy { .. })));
+        assert_eq!(auth_manager.get_user("ivan").unwrap().password_history.len(), 1);
+
+        // Пароли пользователей по умолчанию не проходят политику и требуют смены
+        assert!(auth_manager.get_user("admin").unwrap().must_change_password);
+        assert!(auth_manager.get_user("operator1").unwrap().must_change_password);
+    }
+
+    #[test]
+    fn test_admin_two_factor_login() {
+        let mut auth_manager = AuthManager::new();
+        let request = LoginRequest {
+            login: "admin".to_string(),
+            password: "admin123".to_string(),
+        };
+
+        // Администратор обязан подключить 2FA до входа
+        let response = auth_manager.authenticate(&request);
+        assert!(!response.success);
+        let challenge = response.two_factor.unwrap();
+        assert!(challenge.enrollment_required);
+
+        let enrollment = auth_manager.begin_totp_enrollment_for_challenge(&challenge.challenge_token).unwrap();
+        assert!(enrollment.provisioning_uri.starts_with("otpauth://totp/"));
+        let secret = data_encoding::BASE32_NOPAD.decode(enrollment.secret.as_bytes()).unwrap();
+        assert!(!auth_manager.get_user("admin").unwrap().two_factor.as_ref().unwrap()
+            .encrypted_secret.contains(&enrollment.secret));
+
+        let now = 1_700_000_000;
+        let code = format!("{:06}", totp::hotp(&secret, totp::time_step(now, 30), 6));
+        assert!(!auth_manager.verify_two_factor_at(&challenge.challenge_token, "000000", now).success);
+        assert!(auth_manager.verify_two_factor_at(&challenge.challenge_token, &code, now).success);
+
+        // Повторный вход: тот же код не принимается, резервный код — один раз
+        let challenge = auth_manager.authenticate(&request).two_factor.unwrap();
+        assert!(!challenge.enrollment_required);
+        assert!(!auth_manager.verify_two_factor_at(&challenge.challenge_token, &code, now).success);
+        let recovery = &enrollment.recovery_codes[0];
+        assert!(auth_manager.verify_two_factor_at(&challenge.challenge_token, recovery, now).success);
+
+        let challenge = auth_manager.authenticate(&request).two_factor.unwrap();
+        assert!(!auth_manager.verify_two_factor_at(&challenge.challenge_token, recovery, now).success);
+        assert!(auth_manager.disable_totp("admin", "admin123", recovery).is_err());
+
+        // Некорректная длина кода отклоняется, отключение требует пароль и код
+        let mut policy = TwoFactorPolicy { digits: 10, ..TwoFactorPolicy::default() };
+        assert!(auth_manager.set_two_factor_policy(policy.clone()).is_err());
+        policy.digits = 6;
+        policy.required_roles.clear();
+        auth_manager.set_two_factor_policy(policy).unwrap();
+
+        let later = now + 30;
+        let code = format!("{:06}", totp::hotp(&secret, totp::time_step(later, 30), 6));
+        assert!(auth_manager.disable_totp_at("admin", "wrong", &code, later).is_err());
+        assert!(auth_manager.disable_totp_at("admin", "admin123", "000000", later).is_err());
+        auth_manager.disable_totp_at("admin", "admin123", &code, later).unwrap();
+        assert!(auth_manager.get_user("admin").unwrap().two_factor.is_none());
+    }
+
+    #[test]
+    fn test_user_management() {
+        let mut auth_manager = AuthManager::new();
+        auth_manager
+            .add_user("ivan".to_string(), "Kx7pLm29qZ".to_string(), UserRole::Operator)
+            .unwrap();
+
+        let users = auth_manager.list_users();
+        assert_eq!(users.iter().map(|u| u.info.login.as_str()).collect::<Vec<_>>(), vec!["admin", "ivan", "operator1"]);
+
+        // Последнего администратора нельзя отключить или понизить
+        assert!(auth_manager.set_user_disabled("admin", true).is_err());
+        assert!(auth_manager.set_user_role("admin", UserRole::Operator).is_err());
+
+        auth_manager.set_user_disabled("ivan", true).unwrap();
+        let request = LoginRequest { login: "ivan".to_string(), password: "Kx7pLm29qZ".to_string() };
+        assert!(!auth_manager.authenticate(&request).success);
+        auth_manager.set_user_disabled("ivan", false).unwrap();
+
+        // После сброса пароль обязательно меняется при входе
+        let temporary = auth_manager.reset_password("ivan").unwrap();
+        let request = LoginRequest { login: "ivan".to_string(), password: temporary.clone() };
+        let response = auth_manager.authenticate(&request);
+        assert!(response.success);
+        assert!(response.user.unwrap().must_change_password);
+
+        auth_manager.change_password("ivan", &temporary, "Tr4vel2Moon").unwrap();
+        assert!(!auth_manager.get_user("ivan").unwrap().must_change_password);
+    }
+
+    #[test]
+    fn test_directory_login() {
+        let mut auth_manager = AuthManager::new();
+        auth_manager.set_directory_backend(Some(Box::new(crate::ldap::tests::stand_in_backend())));
+
+        let response = auth_manager.authenticate(&LoginRequest {
+            login: "oleg".to_string(),
+            password: "Oleg-pass-1".to_string(),
+        });
+        let info = response.user.unwrap();
+        assert_eq!(info.role, UserRole::Operator);
+        assert_eq!(info.display_name, "Олег Петров");
+        assert_eq!(auth_manager.get_user("oleg").unwrap().source, UserSource::Directory);
+
+        // Локальная блокировка действует и для пользователей каталога
+        auth_manager.set_user_disabled("oleg", true).unwrap();
+        let response = auth_manager.authenticate(&LoginRequest {
+            login: "oleg".to_string(),
+            password: "Oleg-pass-1".to_string(),
+        });
+        assert_eq!(response.code, Some(SurveillanceError::AccountLocked.error_code()));
+
+        // Администратор каталога проходит через обязательную 2FA
+        let response = auth_manager.authenticate(&LoginRequest {
+            login: "anna".to_string(),
+            password: "Anna-pass-1".to_string(),
+        });
+        assert!(response.two_factor.unwrap().enrollment_required);
+
+        assert!(auth_manager.reset_password("anna").is_err());
+        // Роль пользователя каталога определяется только группами
+        assert!(auth_manager.set_user_role("oleg", UserRole::Admin).is_err());
+        assert!(!auth_manager.authenticate(&LoginRequest {
+            login: "anna".to_string(),
+            password: String::new(),
+        }).success);
+    }
+
+    /// Проверка, что в JSON нет ни одного поля или значения с секретами
+    fn assert_no_secrets(json: &str) {
+        for forbidden in ["password_hash", "password_history", "two_factor\"", "encrypted_secret",
+                          "recovery_code_hashes", "$argon2", "$2b$"] {
+            assert!(!json.contains(forbidden), "в ответе найдено {}: {}", forbidden, json);
+        }
+    }
+
+    #[test]
+    fn test_no_hash_leaves_backend() {
+        let mut auth_manager = AuthManager::new();
+        let response = auth_manager.authenticate(&LoginRequest {
+            login: "operator1".to_string(),
+            password: "operator123".to_string(),
+        });
+        let info = response.user.as_ref().unwrap();
+        assert_eq!(info.display_name, "operator1");
+        assert!(info.last_login.is_some());
+        assert!(info.permissions.contains(&Permission::ViewLive));
+        assert_no_secrets(&serde_json::to_string(&response).unwrap());
+
+        // Администратор с подключённой 2FA
+        auth_manager.begin_totp_enrollment("admin").unwrap();
+        assert_no_secrets(&serde_json::to_string(&auth_manager.list_users()).unwrap());
+
+        let user = auth_manager.get_user("admin").unwrap();
+        assert_no_secrets(&serde_json::to_string(&UserInfo::new(user, &RoleRegistry::default())).unwrap());
+    }
 }
@@ -0,0 +1,103 @@
+// auth_backend.rs - Источники проверки учётных данных
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::auth::{User, UserRole};
+use crate::error::{SurveillanceError, Result};
+use crate::password::PasswordService;
+
+/// Откуда берётся учётная запись пользователя
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSource {
+    /// Пароль хранится в локальном хранилище
+    #[default]
+    Local,
+    /// Пароль и роль определяются каталогом (LDAP / Active Directory)
+    Directory,
+}
+
+/// Личность, подтверждённая источником учётных данных
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendIdentity {
+    pub login: String,
+    pub role: UserRole,
+    pub display_name: Option<String>,
+    pub source: UserSource,
+}
+
+/// Источник проверки логина и пароля.
+///
+/// Источник только подтверждает личность и роль; блокировка, 2FA и
+/// ограничения по квартирам применяются `AuthManager` одинаково для всех.
+pub trait AuthBackend: Send {
+    /// Имя источника для журналов
+    fn name(&self) -> &'static str;
+
+    /// Проверка пароля; `UserNotFound` означает, что источник не знает логин
+    fn authenticate(&self, login: &str, password: &str) -> Result<BackendIdentity>;
+}
+
+/// Локальное хранилище пользователей с хешами паролей
+pub struct LocalBackend<'a> {
+    users: &'a HashMap<String, User>,
+    passwords: &'a PasswordService,
+}
+
+impl<'a> LocalBackend<'a> {
+    pub fn new(users: &'a HashMap<String, User>, passwords: &'a PasswordService) -> Self {
+        Self { users, passwords }
+    }
+}
+
+impl AuthBackend for LocalBackend<'_> {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn authenticate(&self, login: &str, password: &str) -> Result<BackendIdentity> {
+        let user = self.users
+            .get(login)
+            .filter(|user| user.source == UserSource::Local)
+            .ok_or(SurveillanceError::UserNotFound)?;
+
+        match self.passwords.verify(password, &user.password_hash) {
+            Ok(true) => Ok(BackendIdentity {
+                login: user.login.clone(),
+                role: user.role.clone(),
+                display_name: user.display_name.clone(),
+                source: UserSource::Local,
+            }),
+            Ok(false) => Err(SurveillanceError::InvalidCredentials),
+            Err(_) => Err(SurveillanceError::auth_error("password_check_failed")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_backend() {
+        let passwords = PasswordService::default();
+        let mut users = HashMap::new();
+        let mut user = User::new("ivan", passwords.hash("Secret-pass1").unwrap(), UserRole::Operator);
+        user.display_name = Some("Иван".to_string());
+        users.insert(user.login.clone(), user);
+
+        let mut shadow = User::new("petr", String::new(), UserRole::Operator);
+        shadow.source = UserSource::Directory;
+        users.insert(shadow.login.clone(), shadow);
+
+        let backend = LocalBackend::new(&users, &passwords);
+        let identity = backend.authenticate("ivan", "Secret-pass1").unwrap();
+        assert_eq!(identity.display_name.as_deref(), Some("Иван"));
+        assert_eq!(identity.source, UserSource::Local);
+
+        assert!(matches!(backend.authenticate("ivan", "wrong"), Err(SurveillanceError::InvalidCredentials)));
+        assert!(matches!(backend.authenticate("nobody", "x"), Err(SurveillanceError::UserNotFound)));
+        // Пользователи каталога локально не проверяются
+        assert!(matches!(backend.authenticate("petr", ""), Err(SurveillanceError::UserNotFound)));
+    }
+}
@@ -0,0 +1,405 @@
+// camera_status.rs - Состояние камер: автомат состояний, показатели потока и события переходов
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use crate::config::Camera;
+use crate::error::{CommandError, SurveillanceError};
+use crate::reconnect::{Clock, SystemClock};
+
+/// Пороги деградации потока
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CameraHealthSettings {
+    pub min_fps: f32,            // Ниже этой частоты кадров поток считается деградировавшим
+    pub stall_timeout_secs: u32, // Сколько можно не получать кадры, прежде чем поток считается деградировавшим
+}
+
+impl Default for CameraHealthSettings {
+    fn default() -> Self {
+        Self {
+            min_fps: 5.0,
+            stall_timeout_secs: 5,
+        }
+    }
+}
+
+/// Состояние камеры
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraState {
+    /// Камера отключена в конфигурации
+    Disabled,
+    Connecting,
+    Streaming,
+    /// Кадры идут редко или перестали приходить
+    Degraded,
+    /// Поток не передаётся: камера недоступна или просмотр остановлен
+    Offline,
+    /// Камера отклонила учётные данные из ссылки RTSP
+    AuthFailed,
+}
+
+/// Что сообщает контроль потоков о камере
+#[derive(Debug, Clone)]
+pub enum CameraObservation {
+    Connecting,
+    Frame { fps: f32, bitrate_kbps: Option<f32> },
+    Failed { error: SurveillanceError },
+    Stopped,
+}
+
+/// Состояние и показатели камеры
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CameraStatus {
+    pub camera_id: u32,
+    pub state: CameraState,
+    pub state_since: DateTime<Utc>,
+    pub last_frame_at: Option<DateTime<Utc>>,
+    pub fps: Option<f32>,
+    pub bitrate_kbps: Option<f32>,
+    pub error_count: u64,
+    /// Последняя ошибка с кодом и параметрами каталога
+    pub last_error: Option<CommandError>,
+    pub streaming_since: Option<DateTime<Utc>>,
+    /// Время непрерывной передачи кадров (заполняется при чтении)
+    pub uptime_secs: u64,
+}
+
+impl CameraStatus {
+    /// Камера отключена, отклонила доступ или не подключилась при последней попытке
+    pub fn is_unavailable(&self) -> bool {
+        match self.state {
+            CameraState::Disabled | CameraState::AuthFailed => true,
+            CameraState::Offline => self.last_error.is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// Переход между состояниями для интерфейса
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraStatusEvent {
+    pub camera_id: u32,
+    pub from: CameraState,
+    pub to: CameraState,
+    pub status: CameraStatus,
+}
+
+/// Число камер в каждом состоянии
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CameraSummary {
+    pub total: usize,
+    pub disabled: usize,
+    pub connecting: usize,
+    pub streaming: usize,
+    pub degraded: usize,
+    pub offline: usize,
+    pub auth_failed: usize,
+}
+
+impl CameraSummary {
+    pub fn from_statuses<'a>(statuses: impl IntoIterator<Item = &'a CameraStatus>) -> Self {
+        statuses.into_iter().fold(Self::default(), |mut summary, status| {
+            summary.total += 1;
+            match status.state {
+                CameraState::Disabled => summary.disabled += 1,
+                CameraState::Connecting => summary.connecting += 1,
+                CameraState::Streaming => summary.streaming += 1,
+                CameraState::Degraded => summary.degraded += 1,
+                CameraState::Offline => summary.offline += 1,
+                CameraState::AuthFailed => summary.auth_failed += 1,
+            }
+            summary
+        })
+    }
+}
+
+/// Получатель событий (в приложении — отправка события Tauri)
+pub type CameraStatusSink = Box<dyn Fn(&CameraStatusEvent) + Send>;
+
+/// Состояние всех камер
+pub struct CameraStatusBoard {
+    settings: CameraHealthSettings,
+    clock: Arc<dyn Clock>,
+    cameras: BTreeMap<u32, CameraStatus>,
+    sink: Option<CameraStatusSink>,
+}
+
+impl CameraStatusBoard {
+    pub fn new(settings: CameraHealthSettings) -> Self {
+        Self::with_clock(settings, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(settings: CameraHealthSettings, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            settings,
+            clock,
+            cameras: BTreeMap::new(),
+            sink: None,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: CameraHealthSettings) {
+        self.settings = settings;
+    }
+
+    pub fn set_sink(&mut self, sink: CameraStatusSink) {
+        self.sink = Some(sink);
+    }
+
+    fn new_status(&self, camera_id: u32, state: CameraState) -> CameraStatus {
+        CameraStatus {
+            camera_id,
+            state,
+            state_since: self.clock.now(),
+            last_frame_at: None,
+            fps: None,
+            bitrate_kbps: None,
+            error_count: 0,
+            last_error: None,
+            streaming_since: None,
+            uptime_secs: 0,
+        }
+    }
+
+    /// Камеры из конфигурации: новые добавляются, удалённые забываются,
+    /// отключение и включение — такие же переходы, как остальные
+    pub fn sync_cameras(&mut self, cameras: &[Camera]) {
+        self.cameras.retain(|id, _| cameras.iter().any(|camera| camera.id == *id));
+        for camera in cameras {
+            let target = if camera.enabled { CameraState::Offline } else { CameraState::Disabled };
+            match self.cameras.get(&camera.id).map(|status| status.state) {
+                None => {
+                    let status = self.new_status(camera.id, target);
+                    self.cameras.insert(camera.id, status);
+                }
+                Some(CameraState::Disabled) if camera.enabled => self.transition(camera.id, target),
+                Some(state) if !camera.enabled && state != CameraState::Disabled => self.transition(camera.id, target),
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Событие от контроля потоков; отключённые камеры не меняют состояние
+    pub fn observe(&mut self, camera_id: u32, observation: CameraObservation) {
+        let now = self.clock.now();
+        let min_fps = self.settings.min_fps;
+        if !self.cameras.contains_key(&camera_id) {
+            let status = self.new_status(camera_id, CameraState::Offline);
+            self.cameras.insert(camera_id, status);
+        }
+        let Some(status) = self.cameras.get_mut(&camera_id) else {
+            return;
+        };
+        let current = status.state;
+        if current == CameraState::Disabled {
+            return;
+        }
+
+        let target = match observation {
+            // Повторная попытка не скрывает отказ в доступе до успешного подключения
+            CameraObservation::Connecting if current == CameraState::AuthFailed => current,
+            CameraObservation::Connecting => match current {
+                CameraState::Streaming | CameraState::Degraded => current,
+                _ => CameraState::Connecting,
+            },
+            CameraObservation::Frame { fps, bitrate_kbps } => {
+                status.last_frame_at = Some(now);
+                status.fps = Some(fps);
+                status.bitrate_kbps = bitrate_kbps;
+                status.last_error = None;
+                status.streaming_since.get_or_insert(now);
+                // FFmpeg сообщает 0 кадров в секунду, пока не накопит статистику
+                if fps > 0.0 && fps < min_fps {
+                    CameraState::Degraded
+                } else {
+                    CameraState::Streaming
+                }
+            }
+            CameraObservation::Failed { error } => {
+                status.error_count += 1;
+                status.last_error = Some(CommandError::new(&error, crate::session_locale()));
+                match error {
+                    SurveillanceError::CameraAuthFailed => CameraState::AuthFailed,
+                    _ => CameraState::Offline,
+                }
+            }
+            CameraObservation::Stopped => CameraState::Offline,
+        };
+        self.transition(camera_id, target);
+    }
+
+    /// Проверка задержки кадров; вызывается периодически
+    pub fn refresh(&mut self) {
+        let now = self.clock.now();
+        let stall_timeout = chrono::Duration::seconds(self.settings.stall_timeout_secs.into());
+        let stalled: Vec<u32> = self
+            .cameras
+            .values()
+            .filter(|status| status.state == CameraState::Streaming)
+            .filter(|status| status.last_frame_at.is_some_and(|at| now - at > stall_timeout))
+            .map(|status| status.camera_id)
+            .collect();
+        for camera_id in stalled {
+            self.transition(camera_id, CameraState::Degraded);
+        }
+    }
+
+    fn transition(&mut self, camera_id: u32, to: CameraState) {
+        let now = self.clock.now();
+        let Some(status) = self.cameras.get_mut(&camera_id) else {
+            return;
+        };
+        let from = status.state;
+        if from == to {
+            return;
+        }
+
+        status.state = to;
+        status.state_since = now;
+        if !matches!(to, CameraState::Streaming | CameraState::Degraded) {
+            status.streaming_since = None;
+            status.fps = None;
+            status.bitrate_kbps = None;
+        }
+        log::debug!("Камера {}: {:?} -> {:?}", camera_id, from, to);
+
+        if self.sink.is_some() {
+            let status = self.snapshot(camera_id);
+            if let (Some(sink), Some(status)) = (&self.sink, status) {
+                sink(&CameraStatusEvent { camera_id, from, to, status });
+            }
+        }
+    }
+
+    fn snapshot(&self, camera_id: u32) -> Option<CameraStatus> {
+        let now = self.clock.now();
+        self.cameras.get(&camera_id).map(|status| CameraStatus {
+            uptime_secs: status
+                .streaming_since
+                .map(|since| (now - since).num_seconds().max(0) as u64)
+                .unwrap_or(0),
+            ..status.clone()
+        })
+    }
+
+    pub fn get(&self, camera_id: u32) -> Option<CameraStatus> {
+        self.snapshot(camera_id)
+    }
+
+    /// Состояние всех камер по возрастанию номера
+    pub fn list(&self) -> Vec<CameraStatus> {
+        self.cameras.keys().filter_map(|id| self.snapshot(*id)).collect()
+    }
+
+    pub fn summary(&self) -> CameraSummary {
+        CameraSummary::from_statuses(self.cameras.values())
+    }
+}
+
+/// Общее состояние камер для контроля потоков и команд
+pub static CAMERA_STATUS: Lazy<Mutex<CameraStatusBoard>> = Lazy::new(|| {
+    Mutex::new(CameraStatusBoard::new(CameraHealthSettings::default()))
+});
+
+/// Событие фоновой задачи; недоступность состояния только логируется
+pub fn report(camera_id: u32, observation: CameraObservation) {
+    match CAMERA_STATUS.lock() {
+        Ok(mut board) => board.observe(camera_id, observation),
+        Err(e) => log::error!("Состояние камер недоступно: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconnect::ManualClock;
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    fn camera(id: u32, enabled: bool) -> Camera {
+        Camera {
+            id,
+            camera_name: format!("Камера {}", id),
+            apartment_name: "Тест".to_string(),
+            rtsp_link: format!("rtsp://192.168.1.{}/stream", id),
+            enabled,
+        }
+    }
+
+    type Transitions = Arc<Mutex<Vec<(CameraState, CameraState)>>>;
+
+    fn board() -> (Arc<ManualClock>, CameraStatusBoard, Transitions) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()));
+        let mut board = CameraStatusBoard::with_clock(CameraHealthSettings::default(), clock.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        board.set_sink(Box::new(move |event| log.lock().unwrap().push((event.from, event.to))));
+        (clock, board, events)
+    }
+
+    #[test]
+    fn test_state_machine_and_metrics() {
+        use CameraState::*;
+        let (clock, mut board, events) = board();
+        board.sync_cameras(&[camera(1, true), camera(2, false)]);
+        assert_eq!(board.get(2).unwrap().state, Disabled);
+
+        board.observe(1, CameraObservation::Connecting);
+        board.observe(1, CameraObservation::Frame { fps: 0.0, bitrate_kbps: None });
+        clock.advance(Duration::from_secs(2));
+        board.observe(1, CameraObservation::Frame { fps: 25.0, bitrate_kbps: Some(850.0) });
+        let status = board.get(1).unwrap();
+        assert_eq!((status.state, status.fps, status.uptime_secs), (Streaming, Some(25.0), 2));
+
+        // Кадры перестали приходить
+        clock.advance(Duration::from_secs(6));
+        board.refresh();
+        assert_eq!(board.get(1).unwrap().state, Degraded);
+        board.observe(1, CameraObservation::Frame { fps: 2.0, bitrate_kbps: Some(100.0) });
+        assert_eq!(board.get(1).unwrap().state, Degraded);
+
+        // Отказ в доступе не скрывается новой попыткой
+        board.observe(1, CameraObservation::Failed { error: SurveillanceError::CameraAuthFailed });
+        board.observe(1, CameraObservation::Connecting);
+        let status = board.get(1).unwrap();
+        assert_eq!((status.state, status.error_count, status.uptime_secs), (AuthFailed, 1, 0));
+
+        board.observe(1, CameraObservation::Failed { error: SurveillanceError::ConnectionTimeout });
+        assert_eq!(board.get(1).unwrap().state, Offline);
+
+        // Отключённая камера не реагирует на события потока
+        board.observe(2, CameraObservation::Connecting);
+        board.sync_cameras(&[camera(1, false), camera(2, true)]);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                (Offline, Connecting),
+                (Connecting, Streaming),
+                (Streaming, Degraded),
+                (Degraded, AuthFailed),
+                (AuthFailed, Offline),
+                (Offline, Disabled),
+                (Disabled, Offline),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let (_, mut board, _) = board();
+        board.sync_cameras(&[camera(1, true), camera(2, true), camera(3, false)]);
+        board.observe(1, CameraObservation::Frame { fps: 25.0, bitrate_kbps: None });
+        board.observe(2, CameraObservation::Failed { error: SurveillanceError::CameraAuthFailed });
+
+        let summary = board.summary();
+        assert_eq!((summary.total, summary.streaming, summary.auth_failed, summary.disabled), (3, 1, 1, 1));
+
+        board.sync_cameras(&[camera(1, true)]);
+        assert_eq!(board.list().len(), 1);
+    }
+}
@@ -2,7 +2,21 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use crate::api_server::ApiServerSettings;
+use crate::auth::{User, UserSummary};
+use crate::camera_status::CameraHealthSettings;
 use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+use crate::audio::AudioSettings;
+use crate::error_journal::ErrorJournalSettings;
+use crate::notifications::NotificationSettings;
+use crate::ldap::LdapConfig;
+use crate::password::{PasswordHashPolicy, PasswordPolicy};
+use crate::permissions::{Permission, RoleDefinition, RoleRegistry};
+use crate::reconnect::ReconnectSettings;
+use crate::rotation::RotationSettings;
+use crate::stream::hls::HlsSettings;
+use crate::totp::TwoFactorPolicy;
 
 /// Структура камеры
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,8 +44,38 @@ pub struct Settings {
     pub retry_interval: u32,         // Интервал повтора подключения в секундах
     pub max_retry_attempts: u32,     // Максимальное количество попыток переподключения
     pub low_quality_resolution: String,  // Разрешение для сетки (480p)
-    pub high_quality_resolution: String, // Разрешение для полного экрана (1080p)
+    pub high_quality_resolution: String, // Полный экран копирует поток камеры; поле оставлено для совместимости конфигураций
     pub grid_size: u32,              // Размер сетки (например, 16 для 4x4)
+    #[serde(default = "default_ffmpeg_path")]
+    pub ffmpeg_path: String,         // Исполняемый файл FFmpeg
+    #[serde(default)]
+    pub password_hashing: PasswordHashPolicy, // Политика хеширования паролей
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,      // Требования к сложности паролей
+    #[serde(default)]
+    pub two_factor: TwoFactorPolicy,          // Политика двухфакторной аутентификации
+    #[serde(default)]
+    pub ldap: Option<LdapConfig>,             // Каталог LDAP / Active Directory (если используется)
+    #[serde(default)]
+    pub error_journal: ErrorJournalSettings,  // Хранение и агрегация журнала ошибок
+    #[serde(default)]
+    pub notifications: NotificationSettings,  // Порог важности и хранение уведомлений
+    #[serde(default)]
+    pub reconnect: ReconnectSettings,         // Рост задержки и медленные повторы переподключения
+    #[serde(default)]
+    pub camera_health: CameraHealthSettings,  // Пороги деградации потока камеры
+    #[serde(default)]
+    pub rotation: RotationSettings,           // Группировка страниц сетки и пропуск недоступных камер
+    #[serde(default)]
+    pub audio: AudioSettings,                 // Эксклюзивный звук и громкость по умолчанию
+    #[serde(default)]
+    pub hls: HlsSettings,                     // Сегменты встроенного упаковщика HLS
+    #[serde(default)]
+    pub api_server: ApiServerSettings,        // HTTP API для клиентов с API-ключами
+}
+
+fn default_ffmpeg_path() -> String {
+    "ffmpeg".to_string()
 }
 
 impl Default for Settings {
@@ -44,6 +88,19 @@ impl Default for Settings {
             low_quality_resolution: "640x480".to_string(),
             high_quality_resolution: "1920x1080".to_string(),
             grid_size: 16,
+            ffmpeg_path: default_ffmpeg_path(),
+            password_hashing: PasswordHashPolicy::default(),
+            password_policy: PasswordPolicy::default(),
+            two_factor: TwoFactorPolicy::default(),
+            ldap: None,
+            error_journal: ErrorJournalSettings::default(),
+            notifications: NotificationSettings::default(),
+            reconnect: ReconnectSettings::default(),
+            camera_health: CameraHealthSettings::default(),
+            rotation: RotationSettings::default(),
+            audio: AudioSettings::default(),
+            hls: HlsSettings::default(),
+            api_server: ApiServerSettings::default(),
         }
     }
 }
@@ -55,6 +112,19 @@ pub struct Config {
     pub apartments: Vec<Apartment>,
     pub cameras: Vec<Camera>,
     pub settings: Settings,
+    #[serde(default = "RoleDefinition::defaults")]
+    pub roles: Vec<RoleDefinition>,
+}
+
+/// Конфигурация для интерфейса и журнала аудита: учётные записи представлены
+/// сводками без хешей паролей и секретов 2FA
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigView {
+    pub users: Vec<UserSummary>,
+    pub apartments: Vec<Apartment>,
+    pub cameras: Vec<Camera>,
+    pub settings: Settings,
+    pub roles: Vec<RoleDefinition>,
 }
 
 impl Default for Config {
@@ -64,6 +134,7 @@ impl Default for Config {
             apartments: Self::default_apartments(),
             cameras: Self::default_cameras(),
             settings: Settings::default(),
+            roles: RoleDefinition::defaults(),
         }
     }
 }
@@ -81,9 +152,15 @@ impl Config {
             apartments: Self::default_apartments(),
             cameras: Self::default_cameras(),
             settings: Settings::default(),
+            roles: RoleDefinition::defaults(),
         }
     }
 
+    /// Реестр ролей с правами из конфигурации
+    pub fn role_registry(&self) -> RoleRegistry {
+        RoleRegistry::new(&self.roles)
+    }
+
     /// Квартиры по умолчанию для тестирования
     fn default_apartments() -> Vec<Apartment> {
         vec![
@@ -170,11 +247,83 @@ impl Config {
         grouped
     }
 
+    /// Квартиры, доступные пользователю
+    pub fn apartments_for_user(&self, user: &User) -> Vec<&Apartment> {
+        self.apartments
+            .iter()
+            .filter(|apartment| user.can_access_apartment(&apartment.apartment_name))
+            .collect()
+    }
+
+    /// Камеры, доступные пользователю
+    pub fn cameras_for_user(&self, user: &User) -> Vec<&Camera> {
+        self.cameras
+            .iter()
+            .filter(|camera| user.can_access_apartment(&camera.apartment_name))
+            .collect()
+    }
+
+    /// Представление конфигурации без секретов учётных записей и пароля каталога
+    pub fn view(&self) -> ConfigView {
+        let roles = self.role_registry();
+        let mut settings = self.settings.clone();
+        if let Some(ldap) = settings.ldap.as_mut() {
+            ldap.bind_password = None;
+        }
+        ConfigView {
+            users: self.users.iter().map(|user| UserSummary::new(user, &roles)).collect(),
+            apartments: self.apartments.clone(),
+            cameras: self.cameras.clone(),
+            settings,
+            roles: self.roles.clone(),
+        }
+    }
+
+    /// Представление, содержащее только доступные пользователю квартиры и камеры.
+    ///
+    /// Учётные записи видны только с правом управления пользователями, а каталог
+    /// LDAP и политики безопасности — только с правом изменения настроек.
+    pub fn filtered_for_user(&self, user: &User) -> ConfigView {
+        let roles = self.role_registry();
+        let mut config = self.view();
+        config.apartments.retain(|apartment| user.can_access_apartment(&apartment.apartment_name));
+        config.cameras.retain(|camera| user.can_access_apartment(&camera.apartment_name));
+
+        if !roles.has_permission(&user.role, Permission::ManageUsers) {
+            config.users.clear();
+            config.roles.clear();
+        }
+        if !roles.has_permission(&user.role, Permission::ManageSettings) {
+            config.settings.ldap = None;
+            config.settings.password_hashing = PasswordHashPolicy::default();
+            config.settings.password_policy = PasswordPolicy::default();
+            config.settings.two_factor = TwoFactorPolicy::default();
+        }
+        config
+    }
+
+    /// Получение камеры с проверкой доступа пользователя к её квартире.
+    ///
+    /// Любой запрос RTSP-ссылки или потока должен проходить через этот метод.
+    pub fn camera_for_user(&self, camera_id: u32, user: &User) -> Result<&Camera> {
+        let camera = self.cameras
+            .iter()
+            .find(|cam| cam.id == camera_id)
+            .ok_or_else(|| SurveillanceError::config_error("camera_not_found"))?;
+
+        if user.can_access_apartment(&camera.apartment_name) {
+            Ok(camera)
+        } else {
+            log::warn!("Пользователю {} запрещён доступ к камере {}", user.login, camera_id);
+            Err(SurveillanceError::PermissionDenied)
+        }
+    }
+
     /// Добавление новой квартиры
     pub fn add_apartment(&mut self, apartment_name: String, apartment_number: String) -> Result<u32> {
         // Проверяем, не существует ли уже такая квартира
         if self.apartments.iter().any(|apt| apt.apartment_name == apartment_name) {
-            return Err(SurveillanceError::config_error("Квартира с таким названием уже существует"));
+            return Err(SurveillanceError::config_error("apartment_exists"));
         }
 
         let id = self.apartments.iter().map(|apt| apt.id).max().unwrap_or(0) + 1;
@@ -193,7 +342,7 @@ impl Config {
     pub fn add_camera(&mut self, camera_name: String, apartment_name: String, rtsp_link: String) -> Result<u32> {
         // Проверяем, существует ли квартира
         if !self.apartments.iter().any(|apt| apt.apartment_name == apartment_name) {
-            return Err(SurveillanceError::config_error("Квартира не найдена"));
+            return Err(SurveillanceError::config_error("apartment_not_found"));
         }
 
         let id = self.cameras.iter().map(|cam| cam.id).max().unwrap_or(0) + 1;
@@ -216,7 +365,7 @@ impl Config {
         self.cameras.retain(|camera| camera.id != camera_id);
         
         if self.cameras.len() == initial_len {
-            Err(SurveillanceError::config_error("Камера не найдена"))
+            Err(SurveillanceError::config_error("camera_not_found"))
         } else {
             Ok(())
         }
@@ -226,7 +375,7 @@ impl Config {
     pub fn update_camera(&mut self, camera_id: u32, camera_name: Option<String>, apartment_name: Option<String>, rtsp_link: Option<String>) -> Result<()> {
         let camera = self.cameras.iter_mut()
             .find(|cam| cam.id == camera_id)
-            .ok_or_else(|| SurveillanceError::config_error("Камера не найдена"))?;
+            .ok_or_else(|| SurveillanceError::config_error("camera_not_found"))?;
 
         if let Some(name) = camera_name {
             camera.camera_name = name;
@@ -235,7 +384,7 @@ impl Config {
         if let Some(apartment) = apartment_name {
             // Проверяем, существует ли квартира
             if !self.apartments.iter().any(|apt| apt.apartment_name == apartment) {
-                return Err(SurveillanceError::config_error("Квартира не найдена"));
+                return Err(SurveillanceError::config_error("apartment_not_found"));
             }
             camera.apartment_name = apartment;
         }
@@ -251,7 +400,7 @@ impl Config {
     pub fn toggle_camera(&mut self, camera_id: u32) -> Result<bool> {
         let camera = self.cameras.iter_mut()
             .find(|cam| cam.id == camera_id)
-            .ok_or_else(|| SurveillanceError::config_error("Камера не найдена"))?;
+            .ok_or_else(|| SurveillanceError::config_error("camera_not_found"))?;
 
         camera.enabled = !camera.enabled;
         Ok(camera.enabled)
@@ -266,12 +415,12 @@ impl Config {
     pub fn validate(&self) -> Result<()> {
         // Проверяем, что есть хотя бы одна квартира
         if self.apartments.is_empty() {
-            return Err(SurveillanceError::config_error("Должна быть хотя бы одна квартира"));
+            return Err(SurveillanceError::config_error("apartments_required"));
         }
 
         // Проверяем, что есть хотя бы одна камера
         if self.cameras.is_empty() {
-            return Err(SurveillanceError::config_error("Должна быть хотя бы одна камера"));
+            return Err(SurveillanceError::config_error("cameras_required"));
         }
 
         // Проверяем, что все камеры ссылаются на существующие квартиры
@@ -279,32 +428,42 @@ impl Config {
         
         for camera in &self.cameras {
             if !apartment_names.contains(&camera.apartment_name) {
-                return Err(SurveillanceError::config_error(&format!("Камера '{}' ссылается на несуществующую квартиру '{}'", camera.camera_name, camera.apartment_name)));
+                return Err(SurveillanceError::config_error(
+                    Detail::new("camera_unknown_apartment")
+                        .with("camera", camera.camera_name.as_str())
+                        .with("apartment", camera.apartment_name.as_str()),
+                ));
             }
         }
 
+        if let Some(user) = self.users.iter().find(|user| user.role.is_reserved_custom()) {
+            return Err(SurveillanceError::config_error(
+                Detail::new("user_role_reserved").with("login", user.login.as_str()),
+            ));
+        }
+
         // Проверяем настройки
         if self.settings.rotation_interval == 0 {
-            return Err(SurveillanceError::config_error("Интервал ротации должен быть больше 0"));
+            return Err(SurveillanceError::config_error("rotation_interval_invalid"));
         }
 
         if self.settings.connection_timeout == 0 {
-            return Err(SurveillanceError::config_error("Таймаут соединения должен быть больше 0"));
+            return Err(SurveillanceError::config_error("connection_timeout_invalid"));
         }
 
+        self.settings.two_factor.validate()?;
+
         Ok(())
     }
 
     /// Сериализация в JSON
     pub fn to_json(&self) -> Result<String> {
-        serde_json::to_string_pretty(self)
-            .map_err(|e| SurveillanceError::json_error(&e.to_string()))
+        Ok(serde_json::to_string_pretty(self)?)
     }
 
     /// Десериализация из JSON
     pub fn from_json(json: &str) -> Result<Self> {
-        serde_json::from_str(json)
-            .map_err(|e| SurveillanceError::json_error(&e.to_string()))
+        Ok(serde_json::from_str(json)?)
     }
 }
 
@@ -346,16 +505,14 @@ impl ConfigManager {
         Ok(())
     }
 
-    /// Загрузка конфигурации из Nextcloud (заглушка)
-    pub async fn load_from_nextcloud(&mut self) -> Result<()> {
+    /// Загрузка конфигурации из Nextcloud (заглушка).
+    ///
+    /// Возвращает, заменена ли конфигурация; пока загрузка не реализована,
+    /// текущая конфигурация не меняется.
+    pub async fn load_from_nextcloud(&mut self) -> Result<bool> {
         // TODO: Реализовать загрузку через WebDAV API
-        log::info!("Загрузка конфигурации из Nextcloud...");
-        
-        // Пока используем тестовую конфигурацию
-        self.config = Config::new_test();
-        
-        log::info!("Конфигурация загружена успешно");
-        Ok(())
+        log::info!("Загрузка конфигурации из Nextcloud не реализована, используется текущая");
+        Ok(false)
     }
 
     /// Сохранение конфигурации в Nextcloud (заглушка)
@@ -373,15 +530,13 @@ impl ConfigManager {
     /// Сохранение конфигурации локально
     pub fn save_local(&self, path: &str) -> Result<()> {
         let json = self.config.to_json()?;
-        std::fs::write(path, json)
-            .map_err(|e| SurveillanceError::filesystem_error(&e.to_string()))?;
+        std::fs::write(path, json)?;
         Ok(())
     }
 
     /// Загрузка конфигурации из локального файла
     pub fn load_local(&mut self, path: &str) -> Result<()> {
-        let json = std::fs::read_to_string(path)
-            .map_err(|e| SurveillanceError::filesystem_error(&e.to_string()))?;
+        let json = std::fs::read_to_string(path)?;
         
         self.config = Config::from_json(&json)?;
         self.config.validate()?;
@@ -423,4 +578,98 @@ mod tests {
         );
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_operator_restricted_to_apartments() {
+        use crate::auth::UserRole;
+
+        let config = Config::new_test();
+        let mut operator = User::new("operator1", String::new(), UserRole::Operator);
+        operator.allowed_apartments = Some(vec!["Квартира на Ленина".to_string()]);
+
+        let apartments = config.apartments_for_user(&operator);
+        assert_eq!(apartments.len(), 1);
+
+        // Ни одна RTSP-ссылка чужих квартир не попадает в выдачу
+        let links: Vec<_> = config.cameras_for_user(&operator).iter().map(|c| c.rtsp_link.as_str()).collect();
+        assert_eq!(links, vec!["rtsp://192.168.1.200:554/stream1", "rtsp://192.168.1.201:554/stream1"]);
+
+        assert!(config.camera_for_user(4, &operator).is_ok());
+        assert!(matches!(config.camera_for_user(1, &operator), Err(SurveillanceError::PermissionDenied)));
+
+        // Без ограничений доступны все камеры
+        let admin = User::new("admin", String::new(), UserRole::Admin);
+        assert_eq!(config.cameras_for_user(&admin).len(), config.cameras.len());
+    }
+
+    #[test]
+    fn test_filtered_config_hides_security_settings() {
+        use crate::auth::UserRole;
+
+        let mut config = Config::new_test();
+        config.settings.ldap = Some(LdapConfig {
+            bind_dn: Some("cn=reader,dc=example,dc=org".to_string()),
+            bind_password: Some("Reader-secret-1".to_string()),
+            ..LdapConfig::default()
+        });
+
+        // Пароль каталога не уходит даже администратору и не попадает в аудит
+        let admin = User::new("admin", String::new(), UserRole::Admin);
+        for json in [
+            serde_json::to_string(&config.filtered_for_user(&admin)).unwrap(),
+            serde_json::to_string(&config.view()).unwrap(),
+        ] {
+            assert!(!json.contains("Reader-secret-1"));
+            assert!(json.contains("cn=reader"));
+        }
+
+        // Сохранённая конфигурация сохраняет пароль для следующего запуска
+        let restored = Config::from_json(&config.to_json().unwrap()).unwrap();
+        assert_eq!(restored.settings.ldap.unwrap().bind_password.as_deref(), Some("Reader-secret-1"));
+
+        let operator = User::new("operator1", String::new(), UserRole::Operator);
+        let filtered = config.filtered_for_user(&operator);
+        assert!(filtered.settings.ldap.is_none());
+        assert!(filtered.users.is_empty());
+        assert_eq!(filtered.settings.rotation_interval, config.settings.rotation_interval);
+    }
+
+    #[test]
+    fn test_config_view_hides_account_secrets() {
+        use crate::auth::UserRole;
+        use crate::totp::TwoFactorState;
+
+        let mut config = Config::new_test();
+        let mut user = User::new("operator1", "$argon2id$hash-current".to_string(), UserRole::Operator);
+        user.password_history = vec!["$argon2id$hash-previous".to_string()];
+        user.two_factor = Some(TwoFactorState {
+            encrypted_secret: "encrypted-totp-secret".to_string(),
+            confirmed: true,
+            recovery_code_hashes: vec!["recovery-code-hash".to_string()],
+            last_used_step: Some(1),
+        });
+        config.users.push(user);
+
+        // То, что возвращает load_config администратору и что попадает в разницу аудита
+        let admin = User::new("admin", String::new(), UserRole::Admin);
+        for json in [
+            serde_json::to_string(&config.filtered_for_user(&admin)).unwrap(),
+            serde_json::to_string(&config.view()).unwrap(),
+        ] {
+            assert!(json.contains("operator1"));
+            assert!(json.contains("\"two_factor_enabled\":true"));
+            for secret in [
+                "\"password_hash\"",
+                "\"password_history\"",
+                "\"encrypted_secret\"",
+                "\"recovery_code_hashes\"",
+                "hash-current",
+                "hash-previous",
+                "encrypted-totp-secret",
+                "recovery-code-hash",
+            ] {
+                assert!(!json.contains(secret), "{} в {}", secret, json);
+            }
+        }
+    }
 }
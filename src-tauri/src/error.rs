@@ -1,28 +1,49 @@
 // error.rs - Централизованная обработка ошибок
 
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use thiserror::Error;
+use crate::i18n::{self, Detail, Locale};
+use crate::password::PasswordViolation;
 
 /// Основные типы ошибок в системе видеонаблюдения
-#[derive(Error, Debug, Serialize, Deserialize)]
+#[derive(Error, Debug, Clone, Serialize, Deserialize)]
 pub enum SurveillanceError {
-    #[error("Ошибка авторизации: {message}")]
-    AuthError { message: String },
-
-    #[error("Ошибка конфигурации: {message}")]
-    ConfigError { message: String },
-
-    #[error("Ошибка RTSP соединения: {message}")]
-    RtspError { message: String },
-
-    #[error("Сетевая ошибка: {message}")]
-    NetworkError { message: String },
-
-    #[error("Ошибка файловой системы: {message}")]
-    FileSystemError { message: String },
-
-    #[error("Ошибка парсинга JSON: {message}")]
-    JsonError { message: String },
+    #[error("Ошибка авторизации: {detail}")]
+    AuthError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
+
+    #[error("Ошибка конфигурации: {detail}")]
+    ConfigError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
+
+    #[error("Ошибка RTSP соединения: {detail}")]
+    RtspError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
+
+    #[error("Сетевая ошибка: {detail}")]
+    NetworkError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
+
+    #[error("Ошибка файловой системы: {detail}")]
+    FileSystemError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
+
+    #[error("Ошибка парсинга JSON: {detail}")]
+    JsonError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
 
     #[error("Недостаточно прав доступа")]
     PermissionDenied,
@@ -39,57 +60,78 @@ pub enum SurveillanceError {
     #[error("Таймаут соединения")]
     ConnectionTimeout,
 
-    #[error("Внутренняя ошибка: {message}")]
-    InternalError { message: String },
+    #[error("Пароль не соответствует политике: {}", join_violations(.violations))]
+    PasswordPolicy { violations: Vec<PasswordViolation> },
+
+    #[error("Учётная запись заблокирована")]
+    AccountLocked,
+
+    #[error("Сессия истекла, войдите заново")]
+    SessionExpired,
+
+    #[error("Камера отклонила учётные данные")]
+    CameraAuthFailed,
+
+    #[error("Неподдерживаемый кодек: {codec}")]
+    UnsupportedCodec { codec: String },
+
+    #[error("Поток камеры не запущен: {camera_id}")]
+    StreamNotRunning { camera_id: u32 },
+
+    #[error("Внутренняя ошибка: {detail}")]
+    InternalError {
+        #[serde(alias = "message")]
+        detail: Detail,
+    },
 }
 
 impl SurveillanceError {
     /// Создание ошибки авторизации
-    pub fn auth_error(message: &str) -> Self {
+    pub fn auth_error(detail: impl Into<Detail>) -> Self {
         Self::AuthError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
     /// Создание ошибки конфигурации
-    pub fn config_error(message: &str) -> Self {
+    pub fn config_error(detail: impl Into<Detail>) -> Self {
         Self::ConfigError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
     /// Создание ошибки RTSP
-    pub fn rtsp_error(message: &str) -> Self {
+    pub fn rtsp_error(detail: impl Into<Detail>) -> Self {
         Self::RtspError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
     /// Создание сетевой ошибки
-    pub fn network_error(message: &str) -> Self {
+    pub fn network_error(detail: impl Into<Detail>) -> Self {
         Self::NetworkError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
     /// Создание ошибки файловой системы
-    pub fn filesystem_error(message: &str) -> Self {
+    pub fn filesystem_error(detail: impl Into<Detail>) -> Self {
         Self::FileSystemError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
     /// Создание ошибки JSON
-    pub fn json_error(message: &str) -> Self {
+    pub fn json_error(detail: impl Into<Detail>) -> Self {
         Self::JsonError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
     /// Создание внутренней ошибки
-    pub fn internal_error(message: &str) -> Self {
+    pub fn internal_error(detail: impl Into<Detail>) -> Self {
         Self::InternalError {
-            message: message.to_string(),
+            detail: detail.into(),
         }
     }
 
@@ -107,10 +149,113 @@ impl SurveillanceError {
             Self::InvalidCredentials => 1009,
             Self::CameraUnavailable { .. } => 1010,
             Self::ConnectionTimeout => 1011,
+            Self::PasswordPolicy { .. } => 1012,
+            Self::AccountLocked => 1013,
+            Self::SessionExpired => 1014,
+            Self::CameraAuthFailed => 1015,
+            Self::UnsupportedCodec { .. } => 1016,
+            Self::StreamNotRunning { .. } => 1017,
             Self::InternalError { .. } => 9999,
         }
     }
 
+    /// Стабильный ключ сообщения в каталоге `i18n`
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            Self::AuthError { .. } => "auth_error",
+            Self::ConfigError { .. } => "config_error",
+            Self::RtspError { .. } => "rtsp_error",
+            Self::NetworkError { .. } => "network_error",
+            Self::FileSystemError { .. } => "filesystem_error",
+            Self::JsonError { .. } => "json_error",
+            Self::PermissionDenied => "permission_denied",
+            Self::UserNotFound => "user_not_found",
+            Self::InvalidCredentials => "invalid_credentials",
+            Self::CameraUnavailable { .. } => "camera_unavailable",
+            Self::ConnectionTimeout => "connection_timeout",
+            Self::PasswordPolicy { .. } => "password_policy",
+            Self::AccountLocked => "account_locked",
+            Self::SessionExpired => "session_expired",
+            Self::CameraAuthFailed => "camera_auth_failed",
+            Self::UnsupportedCodec { .. } => "unsupported_codec",
+            Self::StreamNotRunning { .. } => "stream_not_running",
+            Self::InternalError { .. } => "internal_error",
+        }
+    }
+
+    /// Параметры сообщения для интерфейса
+    pub fn params(&self) -> Map<String, Value> {
+        let params = match self {
+            // Интерфейс переводит уточнение по ключу и параметрам
+            Self::AuthError { detail }
+            | Self::ConfigError { detail }
+            | Self::RtspError { detail }
+            | Self::NetworkError { detail }
+            | Self::FileSystemError { detail }
+            | Self::JsonError { detail }
+            | Self::InternalError { detail } => json!({
+                "message": detail.to_string(),
+                "detail": detail.key,
+                "detail_params": detail.params,
+            }),
+            Self::CameraUnavailable { camera_id } | Self::StreamNotRunning { camera_id } => {
+                json!({ "camera_id": camera_id })
+            }
+            Self::PasswordPolicy { violations } => json!({ "violations": violations }),
+            Self::UnsupportedCodec { codec } => json!({ "codec": codec }),
+            Self::PermissionDenied
+            | Self::UserNotFound
+            | Self::InvalidCredentials
+            | Self::ConnectionTimeout
+            | Self::AccountLocked
+            | Self::SessionExpired
+            | Self::CameraAuthFailed => json!({}),
+        };
+
+        match params {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        }
+    }
+
+    /// Уточнение из каталога у ошибок с произвольной причиной
+    pub fn detail(&self) -> Option<&Detail> {
+        match self {
+            Self::AuthError { detail }
+            | Self::ConfigError { detail }
+            | Self::RtspError { detail }
+            | Self::NetworkError { detail }
+            | Self::FileSystemError { detail }
+            | Self::JsonError { detail }
+            | Self::InternalError { detail } => Some(detail),
+            _ => None,
+        }
+    }
+
+    /// Сообщение на выбранном языке
+    pub fn localized_message(&self, locale: Locale) -> String {
+        let template = match i18n::error_template(self.message_key(), locale) {
+            Some(template) => template,
+            None => return self.to_string(),
+        };
+
+        // Уточнение переводится по ключу, нарушения политики перечисляются на языке пользователя
+        let mut params = self.params();
+        if let Some(detail) = self.detail() {
+            params.insert("message".to_string(), Value::String(detail.render(locale)));
+        }
+        if let Self::PasswordPolicy { violations } = self {
+            let joined = violations
+                .iter()
+                .map(|violation| violation.localized(locale))
+                .collect::<Vec<_>>()
+                .join(", ");
+            params.insert("violations".to_string(), Value::String(joined));
+        }
+
+        i18n::render(template, &params)
+    }
+
     /// Проверка, является ли ошибка критической
     pub fn is_critical(&self) -> bool {
         matches!(
@@ -133,13 +278,28 @@ impl SurveillanceError {
             Self::InvalidCredentials => ErrorSeverity::Warning,
             Self::CameraUnavailable { .. } => ErrorSeverity::Info,
             Self::ConnectionTimeout => ErrorSeverity::Warning,
+            Self::PasswordPolicy { .. } => ErrorSeverity::Info,
+            Self::AccountLocked => ErrorSeverity::Warning,
+            Self::SessionExpired => ErrorSeverity::Info,
+            Self::CameraAuthFailed => ErrorSeverity::Warning,
+            Self::UnsupportedCodec { .. } => ErrorSeverity::Warning,
+            Self::StreamNotRunning { .. } => ErrorSeverity::Info,
             Self::InternalError { .. } => ErrorSeverity::Critical,
         }
     }
 }
 
+/// Перечисление нарушений политики паролей через запятую
+fn join_violations(violations: &[PasswordViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| violation.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Уровни важности ошибок
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ErrorSeverity {
     Info,
     Warning,
@@ -150,8 +310,68 @@ pub enum ErrorSeverity {
 /// Результат операции в системе
 pub type Result<T> = std::result::Result<T, SurveillanceError>;
 
+/// Ошибка Tauri команды в виде, понятном интерфейсу
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommandError {
+    /// Стабильный машинный код (`SurveillanceError::error_code`)
+    pub code: u32,
+    pub severity: ErrorSeverity,
+    /// Параметры для собственного форматирования на стороне интерфейса
+    pub params: Map<String, Value>,
+    /// Готовое сообщение на языке пользователя
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(error: &SurveillanceError, locale: Locale) -> Self {
+        Self {
+            code: error.error_code(),
+            severity: error.severity(),
+            params: error.params(),
+            message: error.localized_message(locale),
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Ошибка формируется на языке текущей сессии и попадает в журнал ошибок.
+///
+/// Состояние сессии может быть заблокировано той же командой, поэтому
+/// пользователь и язык определяются без ожидания блокировки.
+impl From<SurveillanceError> for CommandError {
+    fn from(error: SurveillanceError) -> Self {
+        let (user, locale) = match crate::SYSTEM_STATE.try_lock() {
+            Ok(state) => (state.current_user.as_ref().map(|user| user.login.clone()), state.current_locale()),
+            Err(_) => (None, Locale::default()),
+        };
+        crate::error_journal::report(&error, "command", user);
+        // Обычные ошибки команд пользователь видит в ответе, критические — все операторы
+        if error.is_critical() {
+            crate::notifications::report(&error, "command");
+        }
+        Self::new(&error, locale)
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for CommandError {
+    fn from(error: std::sync::PoisonError<T>) -> Self {
+        // Блокировка освобождается до записи ошибки в журналы
+        let message = error.to_string();
+        drop(error);
+        SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", message)).into()
+    }
+}
+
+/// Результат Tauri команды
+pub type CommandResult<T> = std::result::Result<T, CommandError>;
+
 /// Структура для логирования ошибок
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorLog {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub error: SurveillanceError,
@@ -173,19 +393,19 @@ impl ErrorLog {
 /// Конвертация из стандартных ошибок
 impl From<std::io::Error> for SurveillanceError {
     fn from(error: std::io::Error) -> Self {
-        Self::filesystem_error(&error.to_string())
+        Self::filesystem_error(Detail::new("io_failed").with("error", error.to_string()))
     }
 }
 
 impl From<serde_json::Error> for SurveillanceError {
     fn from(error: serde_json::Error) -> Self {
-        Self::json_error(&error.to_string())
+        Self::json_error(Detail::new("json_invalid").with("error", error.to_string()))
     }
 }
 
 impl From<reqwest::Error> for SurveillanceError {
     fn from(error: reqwest::Error) -> Self {
-        Self::network_error(&error.to_string())
+        Self::network_error(Detail::new("network_failure").with("error", error.to_string()))
     }
 }
 
@@ -215,20 +435,61 @@ mod tests {
 
     #[test]
     fn test_error_codes() {
-        let auth_error = SurveillanceError::auth_error("test");
+        let auth_error = SurveillanceError::auth_error("invalid_verification_code");
         assert_eq!(auth_error.error_code(), 1001);
         
-        let config_error = SurveillanceError::config_error("test");
+        let config_error = SurveillanceError::config_error("camera_not_found");
         assert_eq!(config_error.error_code(), 1002);
     }
 
     #[test]
     fn test_error_severity() {
-        let auth_error = SurveillanceError::auth_error("test");
+        let auth_error = SurveillanceError::auth_error("invalid_verification_code");
         assert!(matches!(auth_error.severity(), ErrorSeverity::Warning));
         
-        let internal_error = SurveillanceError::internal_error("test");
+        let internal_error = SurveillanceError::internal_error("ffmpeg_output_unavailable");
         assert!(matches!(internal_error.severity(), ErrorSeverity::Critical));
         assert!(internal_error.is_critical());
     }
+
+    #[test]
+    fn test_localized_command_error() {
+        let errors = vec![
+            SurveillanceError::config_error("camera_not_found"),
+            SurveillanceError::CameraUnavailable { camera_id: 3 },
+            SurveillanceError::StreamNotRunning { camera_id: 5 },
+            SurveillanceError::PasswordPolicy { violations: vec![PasswordViolation::MissingDigit] },
+            SurveillanceError::PermissionDenied,
+        ];
+        // Русский каталог совпадает с текстом `Display`
+        for error in &errors {
+            assert_eq!(error.localized_message(Locale::Ru), error.to_string());
+        }
+
+        let command_error = CommandError::new(&errors[0], Locale::En);
+        assert_eq!(command_error.code, 1002);
+        assert_eq!(command_error.message, "Configuration error: Camera not found");
+        assert_eq!(command_error.params["message"], "Камера не найдена");
+        assert_eq!(command_error.params["detail"], "camera_not_found");
+
+        // Уточнение с параметрами переводится по ключу
+        let error = SurveillanceError::config_error(Detail::new("api_key_not_found").with("id", "0a1b"));
+        let command_error = CommandError::new(&error, Locale::En);
+        assert_eq!(command_error.message, "Configuration error: API key 0a1b not found");
+        assert_eq!(command_error.params["detail"], "api_key_not_found");
+        assert_eq!(command_error.params["detail_params"]["id"], "0a1b");
+
+        let command_error = CommandError::new(&errors[2], Locale::En);
+        assert_eq!((command_error.code, command_error.message.as_str()), (1017, "Camera stream is not running: 5"));
+        assert_eq!(command_error.params["camera_id"], 5);
+
+        let command_error = CommandError::new(&errors[3], Locale::En);
+        assert_eq!(command_error.message, "Password does not meet the policy: no digit");
+        assert_eq!(command_error.params["violations"][0]["rule"], "missing_digit");
+        assert_eq!(serde_json::to_value(&command_error).unwrap()["severity"], "Info");
+
+        // Ошибки из журналов прежних версий хранили готовый текст
+        let stored: SurveillanceError = serde_json::from_value(json!({ "ConfigError": { "message": "Камера не найдена" } })).unwrap();
+        assert_eq!(stored.to_string(), "Ошибка конфигурации: Камера не найдена");
+    }
 }
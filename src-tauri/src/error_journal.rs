@@ -0,0 +1,369 @@
+// error_journal.rs - Журнал ошибок с агрегацией повторов и ротацией
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::error::{ErrorLog, ErrorSeverity, SurveillanceError, Result};
+use crate::i18n::Detail;
+
+/// Имя активного файла журнала; архивы получают суффикс `.1`, `.2`, ...
+const ACTIVE_FILE: &str = "errors.json";
+
+/// Настройки журнала ошибок
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ErrorJournalSettings {
+    pub max_records_per_file: usize, // Записей в файле до ротации
+    pub max_files: usize,            // Сколько архивных файлов хранить
+    pub aggregation_window_secs: i64, // Повторы в этом окне увеличивают счётчик
+}
+
+impl Default for ErrorJournalSettings {
+    fn default() -> Self {
+        Self {
+            max_records_per_file: 500,
+            max_files: 5,
+            aggregation_window_secs: 600,
+        }
+    }
+}
+
+/// Запись журнала: последний случай ошибки и число повторов
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    pub id: u64,
+    pub code: u32,
+    pub severity: ErrorSeverity,
+    pub first_seen: DateTime<Utc>,
+    pub count: u64,
+    /// Последний случай (время в нём совпадает с последним повтором)
+    pub last: ErrorLog,
+}
+
+impl ErrorRecord {
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.last)
+    }
+}
+
+/// Одинаковые ошибки: тот же код, параметры и контекст
+fn fingerprint(log: &ErrorLog) -> String {
+    format!(
+        "{}|{}|{}",
+        log.error.error_code(),
+        log.context,
+        serde_json::Value::Object(log.error.params())
+    )
+}
+
+/// Фильтр для панели диагностики
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErrorFilter {
+    pub min_severity: Option<ErrorSeverity>,
+    pub code: Option<u32>,
+    pub context: Option<String>,
+    pub user: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl ErrorFilter {
+    pub fn matches(&self, record: &ErrorRecord) -> bool {
+        self.min_severity.is_none_or(|severity| record.severity >= severity)
+            && self.code.is_none_or(|code| record.code == code)
+            && self.context.as_ref().is_none_or(|context| record.last.context.contains(context.as_str()))
+            && self.user.as_ref().is_none_or(|user| record.last.user.as_ref() == Some(user))
+            && self.from.is_none_or(|from| record.last.timestamp >= from)
+            && self.to.is_none_or(|to| record.first_seen <= to)
+    }
+}
+
+/// Журнал ошибок: активный файл и несколько архивов.
+///
+/// Записи копятся в памяти и сбрасываются на диск `flush` из фонового
+/// таймера, поэтому шквал ошибок не переписывает файл на каждую из них.
+/// Критические ошибки записываются сразу.
+pub struct ErrorJournal {
+    dir: Option<PathBuf>,
+    settings: ErrorJournalSettings,
+    current: Vec<ErrorRecord>,
+    archived: Vec<ErrorRecord>, // Только для журнала в памяти
+    next_id: u64,
+    dirty: bool, // Есть записи, ещё не сброшенные на диск
+}
+
+impl ErrorJournal {
+    /// Журнал в памяти (до открытия каталога и для тестов)
+    pub fn in_memory(settings: ErrorJournalSettings) -> Self {
+        Self {
+            dir: None,
+            settings,
+            current: Vec::new(),
+            archived: Vec::new(),
+            next_id: 1,
+            dirty: false,
+        }
+    }
+
+    /// Открытие журнала в каталоге с продолжением нумерации
+    pub fn open(dir: &Path, settings: ErrorJournalSettings) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut journal = Self {
+            dir: Some(dir.to_path_buf()),
+            settings,
+            current: Vec::new(),
+            archived: Vec::new(),
+            next_id: 1,
+            dirty: false,
+        };
+        journal.current = read_records(&journal.file_path(0))?;
+        journal.next_id = journal
+            .read_all()?
+            .iter()
+            .map(|record| record.id + 1)
+            .max()
+            .unwrap_or(1);
+
+        Ok(journal)
+    }
+
+    pub fn set_settings(&mut self, settings: ErrorJournalSettings) {
+        self.settings = settings;
+    }
+
+    /// Путь к файлу: 0 — активный, 1.. — архивы от новых к старым
+    fn file_path(&self, index: usize) -> PathBuf {
+        let dir = self.dir.clone().unwrap_or_default();
+        match index {
+            0 => dir.join(ACTIVE_FILE),
+            n => dir.join(format!("{}.{}", ACTIVE_FILE, n)),
+        }
+    }
+
+    /// Запись ошибки; повтор в окне агрегации увеличивает счётчик
+    pub fn record(&mut self, log: ErrorLog) -> Result<ErrorRecord> {
+        let key = fingerprint(&log);
+        let window = Duration::seconds(self.settings.aggregation_window_secs);
+
+        let repeated = self.current.iter_mut().rev().find(|record| {
+            record.fingerprint() == key && log.timestamp - record.last.timestamp <= window
+        });
+
+        let record = match repeated {
+            Some(record) => {
+                record.count += 1;
+                record.last = log;
+                record.clone()
+            }
+            None => {
+                if self.current.len() >= self.settings.max_records_per_file.max(1) {
+                    self.rotate()?;
+                }
+                let record = ErrorRecord {
+                    id: self.next_id,
+                    code: log.error.error_code(),
+                    severity: log.error.severity(),
+                    first_seen: log.timestamp,
+                    count: 1,
+                    last: log,
+                };
+                self.next_id += 1;
+                self.current.push(record.clone());
+                record
+            }
+        };
+
+        self.dirty = true;
+        if record.severity == ErrorSeverity::Critical {
+            self.flush()?;
+        }
+        Ok(record)
+    }
+
+    /// Запись накопленных изменений активного файла
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if self.dir.is_some() {
+            std::fs::write(self.file_path(0), serde_json::to_string(&self.current)?)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Перенос активного файла в архив, самый старый архив удаляется
+    fn rotate(&mut self) -> Result<()> {
+        self.flush()?;
+        let current = std::mem::take(&mut self.current);
+        if self.dir.is_none() {
+            self.archived.extend(current);
+            let capacity = self.settings.max_records_per_file.max(1) * self.settings.max_files;
+            let excess = self.archived.len().saturating_sub(capacity);
+            self.archived.drain(..excess);
+            return Ok(());
+        }
+
+        let oldest = self.file_path(self.settings.max_files);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)?;
+        }
+        for index in (0..self.settings.max_files).rev() {
+            let from = self.file_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.file_path(index + 1))?;
+            }
+        }
+
+        log::info!("Журнал ошибок перенесён в архив ({} записей)", current.len());
+        Ok(())
+    }
+
+    /// Все записи от старых к новым
+    fn read_all(&self) -> Result<Vec<ErrorRecord>> {
+        if self.dir.is_none() {
+            return Ok(self.archived.iter().chain(&self.current).cloned().collect());
+        }
+
+        let mut records = Vec::new();
+        for index in (1..=self.settings.max_files).rev() {
+            records.extend(read_records(&self.file_path(index))?);
+        }
+        records.extend(self.current.iter().cloned());
+        Ok(records)
+    }
+
+    /// Выборка записей по фильтру (последние записи, если задан лимит)
+    pub fn query(&self, filter: &ErrorFilter) -> Result<Vec<ErrorRecord>> {
+        let mut records: Vec<ErrorRecord> = self
+            .read_all()?
+            .into_iter()
+            .filter(|record| filter.matches(record))
+            .collect();
+
+        if let Some(limit) = filter.limit {
+            let skip = records.len().saturating_sub(limit);
+            records.drain(..skip);
+        }
+        Ok(records)
+    }
+
+    /// Очистка журнала вместе с архивами
+    pub fn clear(&mut self) -> Result<()> {
+        self.current.clear();
+        self.archived.clear();
+        self.dirty = false;
+        if self.dir.is_some() {
+            for index in 0..=self.settings.max_files {
+                let path = self.file_path(index);
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_records(path: &Path) -> Result<Vec<ErrorRecord>> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).map_err(|e| {
+            SurveillanceError::json_error(Detail::new("file_invalid").with("path", path.display().to_string()).with("error", e.to_string()))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Журнал ошибок приложения (файловый журнал открывается при запуске)
+pub static ERROR_JOURNAL: Lazy<Mutex<ErrorJournal>> = Lazy::new(|| {
+    Mutex::new(ErrorJournal::in_memory(ErrorJournalSettings::default()))
+});
+
+/// Запись ошибки в журнал приложения; сбой журнала только логируется
+pub fn report(error: &SurveillanceError, context: &str, user: Option<String>) {
+    let log = ErrorLog::new(error.clone(), context, user);
+    match ERROR_JOURNAL.lock() {
+        Ok(mut journal) => {
+            if let Err(e) = journal.record(log) {
+                log::error!("Не удалось записать ошибку в журнал: {}", e);
+            }
+        }
+        Err(e) => log::error!("Журнал ошибок недоступен: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_at(error: SurveillanceError, context: &str, timestamp: DateTime<Utc>) -> ErrorLog {
+        let mut log = ErrorLog::new(error, context, Some("admin".to_string()));
+        log.timestamp = timestamp;
+        log
+    }
+
+    #[test]
+    fn test_repeats_are_aggregated() {
+        let mut journal = ErrorJournal::in_memory(ErrorJournalSettings::default());
+        let start = Utc::now();
+
+        // Камера недоступна каждые 30 секунд
+        for step in 0..10 {
+            let at = start + Duration::seconds(30 * step);
+            journal.record(log_at(SurveillanceError::CameraUnavailable { camera_id: 4 }, "stream", at)).unwrap();
+        }
+        journal.record(log_at(SurveillanceError::CameraUnavailable { camera_id: 5 }, "stream", start)).unwrap();
+        // После паузы дольше окна начинается новая запись
+        let later = start + Duration::hours(1);
+        journal.record(log_at(SurveillanceError::CameraUnavailable { camera_id: 4 }, "stream", later)).unwrap();
+
+        let records = journal.query(&ErrorFilter::default()).unwrap();
+        assert_eq!(records.iter().map(|r| r.count).collect::<Vec<_>>(), vec![10, 1, 1]);
+        assert_eq!(records[0].first_seen, start);
+        assert_eq!(records[0].last.timestamp, start + Duration::seconds(270));
+
+        let filter = ErrorFilter { min_severity: Some(ErrorSeverity::Warning), ..ErrorFilter::default() };
+        assert!(journal.query(&filter).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rotation_and_clear() {
+        let dir = std::env::temp_dir().join(format!("errors-{}", uuid::Uuid::new_v4()));
+        let settings = ErrorJournalSettings { max_records_per_file: 2, max_files: 2, aggregation_window_secs: 600 };
+        let mut journal = ErrorJournal::open(&dir, settings.clone()).unwrap();
+
+        for index in 0..7 {
+            let error = SurveillanceError::network_error(Detail::new("connect_failed").with("address", format!("camera-{}", index)).with("error", "timeout"));
+            journal.record(ErrorLog::new(error, "nextcloud", None)).unwrap();
+        }
+
+        // Последняя запись ещё в памяти, на диск она попадает при сбросе
+        let ids = |journal: &ErrorJournal| -> Vec<u64> {
+            journal.query(&ErrorFilter::default()).unwrap().iter().map(|r| r.id).collect()
+        };
+        assert_eq!(ids(&ErrorJournal::open(&dir, settings.clone()).unwrap()), vec![3, 4, 5, 6]);
+        journal.flush().unwrap();
+
+        // Хранится активный файл и два архива, самый старый архив удалён
+        let reopened = ErrorJournal::open(&dir, settings.clone()).unwrap();
+        assert_eq!(ids(&reopened), vec![3, 4, 5, 6, 7]);
+        assert_eq!(reopened.next_id, 8);
+
+        let filter = ErrorFilter { limit: Some(2), ..ErrorFilter::default() };
+        assert_eq!(reopened.query(&filter).unwrap().len(), 2);
+
+        // Критическая ошибка записывается сразу
+        journal.record(ErrorLog::new(SurveillanceError::internal_error("ffmpeg_output_unavailable"), "startup", None)).unwrap();
+        assert_eq!(ids(&ErrorJournal::open(&dir, settings).unwrap()), vec![3, 4, 5, 6, 7, 8]);
+
+        journal.clear().unwrap();
+        assert!(journal.query(&ErrorFilter::default()).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,360 @@
+// i18n.rs - Каталог сообщений об ошибках (русский и английский)
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Язык сообщений
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+}
+
+/// Шаблоны сообщений об ошибках: ключ, русский, английский.
+/// Параметры подставляются вместо `{имя}`.
+const ERROR_TEMPLATES: &[(&str, &str, &str)] = &[
+    ("auth_error", "Ошибка авторизации: {message}", "Authorization error: {message}"),
+    ("config_error", "Ошибка конфигурации: {message}", "Configuration error: {message}"),
+    ("rtsp_error", "Ошибка RTSP соединения: {message}", "RTSP connection error: {message}"),
+    ("network_error", "Сетевая ошибка: {message}", "Network error: {message}"),
+    ("filesystem_error", "Ошибка файловой системы: {message}", "File system error: {message}"),
+    ("json_error", "Ошибка парсинга JSON: {message}", "JSON parsing error: {message}"),
+    ("permission_denied", "Недостаточно прав доступа", "Permission denied"),
+    ("user_not_found", "Пользователь не найден", "User not found"),
+    ("invalid_credentials", "Неверный пароль", "Invalid password"),
+    ("camera_unavailable", "Камера недоступна: {camera_id}", "Camera unavailable: {camera_id}"),
+    ("connection_timeout", "Таймаут соединения", "Connection timed out"),
+    ("password_policy", "Пароль не соответствует политике: {violations}", "Password does not meet the policy: {violations}"),
+    ("account_locked", "Учётная запись заблокирована", "Account is locked"),
+    ("session_expired", "Сессия истекла, войдите заново", "Session expired, please sign in again"),
+    ("camera_auth_failed", "Камера отклонила учётные данные", "The camera rejected the credentials"),
+    ("unsupported_codec", "Неподдерживаемый кодек: {codec}", "Unsupported codec: {codec}"),
+    ("stream_not_running", "Поток камеры не запущен: {camera_id}", "Camera stream is not running: {camera_id}"),
+    ("internal_error", "Внутренняя ошибка: {message}", "Internal error: {message}"),
+];
+
+/// Уточнения ошибок и сообщения интерфейса: ключ, русский, английский.
+///
+/// Модули строят `Detail` с ключом и параметрами там, где возникает ошибка;
+/// значения на месте `{имя}` подставляются при выводе на нужном языке.
+const DETAIL_TEMPLATES: &[(&str, &str, &str)] = &[
+    ("sign_in_required", "Требуется вход в систему", "Sign-in required"),
+    ("password_change_required", "Необходимо сменить пароль", "Password change required"),
+    ("user_exists", "Пользователь уже существует", "User already exists"),
+    ("role_name_reserved", "Имя роли совпадает со встроенной ролью", "The role name matches a built-in role"),
+    ("password_check_failed", "Ошибка проверки пароля", "Password verification failed"),
+    ("password_check_failed_with", "Ошибка проверки пароля: {error}", "Password verification failed: {error}"),
+    ("password_hash_failed", "Ошибка хеширования пароля: {error}", "Password hashing failed: {error}"),
+    ("password_hash_invalid", "Некорректный хеш пароля: {error}", "Invalid password hash: {error}"),
+    ("password_hash_unknown", "Неизвестный формат хеша пароля", "Unknown password hash format"),
+    ("argon2_params_invalid", "Некорректные параметры Argon2: {error}", "Invalid Argon2 parameters: {error}"),
+    ("bcrypt_cost_invalid", "Стоимость bcrypt должна быть в диапазоне 4..=31", "bcrypt cost must be in the range 4..=31"),
+    ("password_managed_by_directory", "Пароль управляется каталогом LDAP", "Password is managed by the LDAP directory"),
+    ("role_managed_by_directory", "Роль пользователя каталога задаётся группами LDAP", "Directory user roles are assigned by LDAP groups"),
+    ("cannot_disable_self", "Нельзя отключить собственную учётную запись", "You cannot disable your own account"),
+    ("cannot_disable_last_admin", "Нельзя отключить последнего администратора", "Cannot disable the last administrator"),
+    ("cannot_demote_last_admin", "Нельзя понизить последнего администратора", "Cannot demote the last administrator"),
+    ("cannot_remove_primary_admin", "Нельзя удалить основного администратора", "Cannot remove the primary administrator"),
+    ("too_many_attempts", "Превышено число попыток, войдите заново", "Too many attempts, please sign in again"),
+    ("invalid_verification_code", "Неверный код подтверждения", "Invalid verification code"),
+    ("two_factor_not_enabled", "Двухфакторная аутентификация не подключена", "Two-factor authentication is not enabled"),
+    ("two_factor_digits_invalid", "Длина кода 2FA должна быть от 6 до 8 цифр", "Two-factor code length must be 6 to 8 digits"),
+    ("two_factor_period_invalid", "Период кода 2FA должен быть больше 0", "Two-factor code period must be greater than 0"),
+    ("two_factor_already_enabled", "Двухфакторная аутентификация уже подключена", "Two-factor authentication is already enabled"),
+    ("two_factor_enrollment_missing", "Подключение 2FA не начато", "Two-factor enrollment has not been started"),
+    ("two_factor_secret_corrupted", "Повреждён секрет 2FA", "Two-factor secret is corrupted"),
+    ("two_factor_key_corrupted", "Повреждён ключ 2FA: {error}", "Two-factor key is corrupted: {error}"),
+    ("two_factor_decrypt_failed", "Не удалось расшифровать секрет 2FA", "Failed to decrypt the two-factor secret"),
+    ("two_factor_key_length", "Ключ 2FA должен быть 32 байта", "The two-factor key must be 32 bytes"),
+    ("two_factor_encrypt_failed", "Ошибка шифрования секрета 2FA", "Failed to encrypt the two-factor secret"),
+    ("api_key_revoked", "API-ключ отозван", "API key has been revoked"),
+    ("api_key_expired", "Срок действия API-ключа истёк", "API key has expired"),
+    ("api_key_not_found", "API-ключ {id} не найден", "API key {id} not found"),
+    ("api_key_name_empty", "Имя API-ключа не может быть пустым", "API key name must not be empty"),
+    ("api_key_scopes_empty", "API-ключу нужна хотя бы одна область доступа", "API key needs at least one scope"),
+    ("api_key_expiry_past", "Срок действия API-ключа уже истёк", "API key expiry is in the past"),
+    ("api_unknown_endpoint", "Неизвестный адрес API", "Unknown API endpoint"),
+    ("api_method_not_allowed", "Метод не поддерживается", "Method not allowed"),
+    ("api_malformed_request", "Некорректный запрос", "Malformed request"),
+    ("api_request_too_long", "Слишком длинный запрос", "Request is too long"),
+    ("api_request_truncated", "Соединение закрыто до конца запроса", "Connection closed before the end of the request"),
+    ("api_bind_failed", "Не удалось открыть {address}: {error}", "Cannot listen on {address}: {error}"),
+    ("ldap_service_account_rejected", "Служебная учётная запись LDAP отклонена сервером", "The LDAP service account was rejected by the server"),
+    ("ldap_failure", "LDAP: {error}", "LDAP: {error}"),
+    ("ldap_filter_invalid", "LDAP: неверный фильтр", "LDAP: invalid filter"),
+    ("audit_integrity_failed", "Нарушена целостность журнала аудита", "Audit log integrity check failed"),
+    ("camera_connection_refused", "Соединение отклонено камерой", "Connection refused by the camera"),
+    ("camera_no_route", "Нет маршрута до камеры", "No route to the camera"),
+    ("camera_stream_not_found", "Поток не найден на камере", "Stream not found on the camera"),
+    ("camera_no_video", "Камера не передаёт видео", "The camera does not provide a video stream"),
+    ("rtsp_content_length_invalid", "Недопустимый Content-Length: {length}", "Invalid Content-Length: {length}"),
+    ("rtsp_response_too_long", "Слишком длинный ответ RTSP", "RTSP response is too long"),
+    ("rtsp_response_invalid", "Некорректный ответ RTSP", "Malformed RTSP response"),
+    ("rtsp_url_no_host", "В ссылке RTSP нет адреса камеры", "The RTSP link has no camera address"),
+    ("rtsp_connection_closed", "Камера закрыла соединение RTSP", "The camera closed the RTSP connection"),
+    ("rtsp_request_failed", "{method} {uri}: {status} {reason}", "{method} {uri}: {status} {reason}"),
+    ("rtsp_no_session", "Камера не назначила сессию RTSP", "The camera did not assign an RTSP session"),
+    ("rtsp_no_transport", "Камера не подтвердила транспорт", "The camera did not confirm the transport"),
+    ("camera_no_media", "Камера не передаёт видео или звук", "The camera provides neither video nor audio"),
+    ("udp_receive_stopped", "Приём UDP остановлен", "UDP reception stopped"),
+    ("udp_ports_unavailable", "Не удалось выделить пару UDP-портов", "Could not allocate a pair of UDP ports"),
+    ("connect_failed", "{address}: {error}", "{address}: {error}"),
+    ("sdp_no_media", "Описание SDP не содержит медиапотоков", "The SDP description has no media streams"),
+    ("rtsp_too_many_tracks", "Слишком много дорожек для TCP", "Too many tracks for TCP transport"),
+    ("rtsp_status_line_invalid", "Некорректная строка статуса: {line}", "Malformed status line: {line}"),
+    ("rtsp_url_invalid", "Некорректная ссылка RTSP: {error}", "Invalid RTSP link: {error}"),
+    ("rtsp_scheme_unsupported", "Неподдерживаемая схема: {scheme}", "Unsupported scheme: {scheme}"),
+    ("sdp_line_invalid", "Некорректная строка SDP: {line}", "Malformed SDP line: {line}"),
+    ("rtp_packet_invalid", "Некорректный пакет RTP: {reason}", "Malformed RTP packet: {reason}"),
+    ("rtp_short_header", "короткий заголовок", "short header"),
+    ("rtp_bad_version", "версия не 2", "version is not 2"),
+    ("rtp_truncated_extension", "обрезано расширение", "truncated extension"),
+    ("rtp_bad_padding", "неверное выравнивание", "invalid padding"),
+    ("rtp_no_payload", "нет полезной нагрузки", "no payload"),
+    ("ffmpeg_start_failed", "Не удалось запустить FFmpeg: {error}", "Failed to start FFmpeg: {error}"),
+    ("ffmpeg_exited", "FFmpeg завершился: {status}", "FFmpeg exited: {status}"),
+    ("ffmpeg_output_unavailable", "Нет доступа к выводу FFmpeg", "FFmpeg output is not available"),
+    ("ffmpeg_wait_failed", "Ошибка ожидания FFmpeg: {error}", "Failed to wait for FFmpeg: {error}"),
+    ("probe_target_missing", "Укажите камеру или ссылку RTSP", "Specify a camera or an RTSP link"),
+    ("audio_volume_invalid", "Громкость должна быть от 0 до 1", "Volume must be between 0 and 1"),
+    ("aac_config_invalid", "Некорректная конфигурация AAC", "Invalid AAC configuration"),
+    ("camera_not_found", "Камера не найдена", "Camera not found"),
+    ("apartment_not_found", "Квартира не найдена", "Apartment not found"),
+    ("apartment_exists", "Квартира с таким названием уже существует", "An apartment with this name already exists"),
+    ("apartments_required", "Должна быть хотя бы одна квартира", "At least one apartment is required"),
+    ("cameras_required", "Должна быть хотя бы одна камера", "At least one camera is required"),
+    ("camera_unknown_apartment", "Камера '{camera}' ссылается на несуществующую квартиру '{apartment}'", "Camera '{camera}' refers to a missing apartment '{apartment}'"),
+    ("user_role_reserved", "Роль пользователя '{login}' совпадает со встроенной ролью", "The role of user '{login}' matches a built-in role"),
+    ("rotation_interval_invalid", "Интервал ротации должен быть больше 0", "Rotation interval must be greater than 0"),
+    ("connection_timeout_invalid", "Таймаут соединения должен быть больше 0", "Connection timeout must be greater than 0"),
+    ("io_failed", "{error}", "{error}"),
+    ("json_invalid", "{error}", "{error}"),
+    ("file_invalid", "{path}: {error}", "{path}: {error}"),
+    ("network_failure", "{error}", "{error}"),
+    ("lock_poisoned", "Блокировка повреждена: {error}", "Lock poisoned: {error}"),
+    ("legacy_message", "{text}", "{text}"),
+    ("login_success", "Авторизация успешна", "Signed in successfully"),
+    ("two_factor_enrollment_required", "Необходимо подключить двухфакторную аутентификацию", "Two-factor authentication must be set up"),
+    ("two_factor_code_required", "Введите код подтверждения", "Enter the verification code"),
+];
+
+/// Шаблон сообщения для ключа ошибки
+pub fn error_template(key: &str, locale: Locale) -> Option<&'static str> {
+    ERROR_TEMPLATES
+        .iter()
+        .find(|(template_key, _, _)| *template_key == key)
+        .map(|(_, ru, en)| match locale {
+            Locale::Ru => *ru,
+            Locale::En => *en,
+        })
+}
+
+/// Уточнение из каталога: ключ шаблона и параметры.
+///
+/// Строится там, где возникает ошибка; текст на нужном языке получается
+/// подстановкой параметров в шаблон `DETAIL_TEMPLATES`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredDetail")]
+pub struct Detail {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub params: Map<String, Value>,
+}
+
+impl Detail {
+    pub fn new(key: &str) -> Self {
+        Self { key: key.to_string(), params: Map::new() }
+    }
+
+    /// Параметр шаблона
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Вложенное уточнение, переводимое вместе с внешним
+    pub fn with_detail(self, name: &str, detail: Detail) -> Self {
+        let value = serde_json::to_value(detail).unwrap_or(Value::Null);
+        self.with(name, value)
+    }
+
+    /// Текст на выбранном языке; ключ без шаблона выводится как есть
+    pub fn render(&self, locale: Locale) -> String {
+        let template = DETAIL_TEMPLATES
+            .iter()
+            .find(|(key, _, _)| *key == self.key)
+            .map(|(_, ru, en)| match locale {
+                Locale::Ru => *ru,
+                Locale::En => *en,
+            });
+        let Some(template) = template else {
+            log::warn!("Уточнение {} отсутствует в каталоге", self.key);
+            return self.key.clone();
+        };
+
+        let params = self
+            .params
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::Object(_) => match serde_json::from_value::<Detail>(value.clone()) {
+                        Ok(nested) => Value::String(nested.render(locale)),
+                        Err(_) => value.clone(),
+                    },
+                    other => other.clone(),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        render(template, &params)
+    }
+}
+
+impl From<&'static str> for Detail {
+    fn from(key: &'static str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl std::fmt::Display for Detail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(Locale::Ru))
+    }
+}
+
+/// Уточнение в сохранённых журналах: ключ с параметрами или текст прежних версий
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredDetail {
+    Keyed {
+        key: String,
+        #[serde(default)]
+        params: Map<String, Value>,
+    },
+    Text(String),
+}
+
+impl From<StoredDetail> for Detail {
+    fn from(stored: StoredDetail) -> Self {
+        match stored {
+            StoredDetail::Keyed { key, params } => Self { key, params },
+            StoredDetail::Text(text) => Self::new("legacy_message").with("text", text),
+        }
+    }
+}
+
+/// Подстановка параметров в шаблон
+pub fn render(template: &str, params: &Map<String, Value>) -> String {
+    params.iter().fold(template.to_string(), |message, (name, value)| {
+        let value = match value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        message.replace(&format!("{{{}}}", name), &value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Имена параметров шаблона
+    fn placeholders(template: &str) -> std::collections::BTreeSet<&str> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn test_catalog_complete_for_both_locales() {
+        let mut keys = std::collections::BTreeSet::new();
+        for (key, ru, en) in ERROR_TEMPLATES.iter().chain(DETAIL_TEMPLATES) {
+            assert!(!ru.is_empty() && !en.is_empty(), "{}", key);
+            assert!(keys.insert(key), "ключ {} повторяется", key);
+            // Английский шаблон использует те же параметры, что и русский
+            assert_eq!(placeholders(ru), placeholders(en), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_render_and_translate() {
+        let params = json!({ "camera_id": 7, "message": "x" });
+        let params = params.as_object().unwrap();
+        assert_eq!(render("Камера недоступна: {camera_id}", params), "Камера недоступна: 7");
+
+        let detail = Detail::new("camera_not_found");
+        assert_eq!(detail.render(Locale::En), "Camera not found");
+        assert_eq!(detail.to_string(), "Камера не найдена");
+    }
+
+    #[test]
+    fn test_detail_translated_by_key_and_params() {
+        let detail = Detail::new("camera_unknown_apartment").with("camera", "Двор").with("apartment", "Кв. 5");
+        assert_eq!(detail.to_string(), "Камера 'Двор' ссылается на несуществующую квартиру 'Кв. 5'");
+        assert_eq!(detail.render(Locale::En), "Camera 'Двор' refers to a missing apartment 'Кв. 5'");
+
+        // Вложенное уточнение переводится вместе с внешним
+        let detail = Detail::new("rtp_packet_invalid").with_detail("reason", Detail::new("rtp_bad_version"));
+        assert_eq!(detail.render(Locale::En), "Malformed RTP packet: version is not 2");
+        assert_eq!(detail.to_string(), "Некорректный пакет RTP: версия не 2");
+
+        // Ключ и параметры переживают сохранение; текст прежних версий читается как есть
+        let stored: Detail = serde_json::from_value(serde_json::to_value(&detail).unwrap()).unwrap();
+        assert_eq!(stored, detail);
+        let legacy: Detail = serde_json::from_value(json!("Камера не найдена")).unwrap();
+        assert_eq!(legacy.render(Locale::En), "Камера не найдена");
+    }
+
+    /// Ключи, переданные строковым литералом в `Detail::new` и конструкторы ошибок
+    fn emitted_keys(source: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        for pattern in ["Detail::new(\"", "_error(\""] {
+            for (index, _) in source.match_indices(pattern) {
+                let rest = &source[index + pattern.len()..];
+                if let Some(end) = rest.find('"') {
+                    keys.push(rest[..end].to_string());
+                }
+            }
+        }
+        keys
+    }
+
+    fn source_files(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                source_files(&path, files);
+            } else if path.extension().is_some_and(|extension| extension == "rs") {
+                files.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_emitted_details_are_catalogued() {
+        let dir = std::path::Path::new(file!()).parent().unwrap();
+        let mut files = Vec::new();
+        source_files(dir, &mut files);
+        assert!(files.len() > 10, "исходники не найдены в {}", dir.display());
+
+        let mut checked = 0;
+        for file in files {
+            let source = std::fs::read_to_string(&file).unwrap();
+            for key in emitted_keys(&source) {
+                assert!(
+                    DETAIL_TEMPLATES.iter().any(|(template_key, _, _)| *template_key == key),
+                    "{}: уточнение {:?} отсутствует в каталоге",
+                    file.display(),
+                    key
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 100);
+    }
+}
@@ -0,0 +1,361 @@
+// ldap.rs - Аутентификация через LDAP / Active Directory
+
+use ldap3::{LdapConn, LdapConnSettings, LdapError, Scope, SearchEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::auth::UserRole;
+use crate::auth_backend::{AuthBackend, BackendIdentity, UserSource};
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+
+/// Код LDAP «неверные учётные данные» (RFC 4511)
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+
+/// Соответствие группы каталога роли в системе
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LdapGroupRole {
+    /// DN группы или её CN
+    pub group: String,
+    pub role: UserRole,
+}
+
+/// Настройки подключения к каталогу
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LdapConfig {
+    pub url: String,                        // ldap://host:389 или ldaps://host:636
+    pub starttls: bool,                     // Включить StartTLS для ldap://
+    pub timeout_secs: u64,                  // Таймаут подключения
+    pub bind_dn: Option<String>,            // Служебная учётная запись для поиска
+    /// Пароль служебной учётной записи; сохраняется в конфигурации, но не
+    /// попадает ни во фронтенд, ни в журнал аудита (см. `Config::view`)
+    pub bind_password: Option<String>,
+    pub base_dn: String,                    // Где искать пользователей
+    pub user_filter: String,                // Фильтр поиска, `{login}` заменяется логином
+    pub display_name_attribute: String,
+    pub group_attribute: String,            // Атрибут со списком групп
+    pub group_roles: Vec<LdapGroupRole>,    // Проверяются по порядку, первая подходящая побеждает
+}
+
+impl Default for LdapConfig {
+    fn default() -> Self {
+        Self {
+            url: "ldap://localhost:389".to_string(),
+            starttls: false,
+            timeout_secs: 5,
+            bind_dn: None,
+            bind_password: None,
+            base_dn: String::new(),
+            user_filter: "(sAMAccountName={login})".to_string(),
+            display_name_attribute: "displayName".to_string(),
+            group_attribute: "memberOf".to_string(),
+            group_roles: Vec::new(),
+        }
+    }
+}
+
+impl LdapConfig {
+    /// Фильтр поиска пользователя с экранированием логина (RFC 4515)
+    pub fn filter_for(&self, login: &str) -> String {
+        self.user_filter.replace("{login}", &ldap3::ldap_escape(login))
+    }
+
+    /// Роль по списку групп пользователя
+    pub fn role_for_groups(&self, groups: &[String]) -> Option<UserRole> {
+        self.group_roles
+            .iter()
+            .find(|mapping| groups.iter().any(|group| group_matches(group, &mapping.group)))
+            .map(|mapping| mapping.role.clone())
+    }
+}
+
+/// Сравнение группы из каталога с группой из настроек (по DN или CN, без учёта регистра)
+fn group_matches(member_of: &str, configured: &str) -> bool {
+    if member_of.eq_ignore_ascii_case(configured) {
+        return true;
+    }
+
+    let first_rdn = member_of.split(',').next().unwrap_or_default();
+    match first_rdn.split_once('=') {
+        Some((attribute, value)) => {
+            attribute.trim().eq_ignore_ascii_case("cn") && value.trim().eq_ignore_ascii_case(configured)
+        }
+        None => false,
+    }
+}
+
+/// Запись каталога
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DirectoryEntry {
+    pub dn: String,
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl DirectoryEntry {
+    /// Значения атрибута (имена атрибутов LDAP не чувствительны к регистру)
+    pub fn values(&self, attribute: &str) -> &[String] {
+        self.attributes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(attribute))
+            .map(|(_, values)| values.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Открытое соединение с каталогом
+pub trait Directory {
+    /// Простая привязка; неверный пароль — `InvalidCredentials`
+    fn bind(&mut self, dn: &str, password: &str) -> Result<()>;
+
+    /// Поиск записей в поддереве `base`
+    fn search(&mut self, base: &str, filter: &str, attributes: &[&str]) -> Result<Vec<DirectoryEntry>>;
+}
+
+/// Фабрика соединений с каталогом
+pub trait DirectoryConnector: Send + Sync {
+    fn connect(&self, config: &LdapConfig) -> Result<Box<dyn Directory>>;
+}
+
+/// Соединение через клиент `ldap3`
+struct Ldap3Directory {
+    conn: LdapConn,
+}
+
+impl Drop for Ldap3Directory {
+    fn drop(&mut self) {
+        let _ = self.conn.unbind();
+    }
+}
+
+fn map_ldap_error(error: LdapError) -> SurveillanceError {
+    match error {
+        LdapError::LdapResult { result } if result.rc == LDAP_INVALID_CREDENTIALS => {
+            SurveillanceError::InvalidCredentials
+        }
+        other => SurveillanceError::network_error(Detail::new("ldap_failure").with("error", other.to_string())),
+    }
+}
+
+impl Directory for Ldap3Directory {
+    fn bind(&mut self, dn: &str, password: &str) -> Result<()> {
+        self.conn
+            .simple_bind(dn, password)
+            .and_then(|result| result.success())
+            .map(|_| ())
+            .map_err(map_ldap_error)
+    }
+
+    fn search(&mut self, base: &str, filter: &str, attributes: &[&str]) -> Result<Vec<DirectoryEntry>> {
+        let (entries, _) = self.conn
+            .search(base, Scope::Subtree, filter, attributes.to_vec())
+            .and_then(|result| result.success())
+            .map_err(map_ldap_error)?;
+
+        Ok(entries
+            .into_iter()
+            .map(SearchEntry::construct)
+            .map(|entry| DirectoryEntry { dn: entry.dn, attributes: entry.attrs })
+            .collect())
+    }
+}
+
+/// Подключение к настоящему серверу каталога
+pub struct Ldap3Connector;
+
+impl DirectoryConnector for Ldap3Connector {
+    fn connect(&self, config: &LdapConfig) -> Result<Box<dyn Directory>> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(Duration::from_secs(config.timeout_secs))
+            .set_starttls(config.starttls);
+        let conn = LdapConn::with_settings(settings, &config.url).map_err(map_ldap_error)?;
+        Ok(Box::new(Ldap3Directory { conn }))
+    }
+}
+
+/// Проверка пароля привязкой к каталогу и выбор роли по группам
+pub struct LdapBackend {
+    config: LdapConfig,
+    connector: Box<dyn DirectoryConnector>,
+}
+
+impl LdapBackend {
+    pub fn new(config: LdapConfig) -> Self {
+        Self::with_connector(config, Box::new(Ldap3Connector))
+    }
+
+    pub fn with_connector(config: LdapConfig, connector: Box<dyn DirectoryConnector>) -> Self {
+        Self { config, connector }
+    }
+}
+
+impl AuthBackend for LdapBackend {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    fn authenticate(&self, login: &str, password: &str) -> Result<BackendIdentity> {
+        // Пустой пароль означает анонимную привязку, которую сервер примет
+        if login.is_empty() || password.is_empty() {
+            return Err(SurveillanceError::InvalidCredentials);
+        }
+
+        let mut directory = self.connector.connect(&self.config)?;
+        if let Some(bind_dn) = &self.config.bind_dn {
+            let bind_password = self.config.bind_password.as_deref().unwrap_or_default();
+            directory.bind(bind_dn, bind_password).map_err(|_| {
+                SurveillanceError::config_error("ldap_service_account_rejected")
+            })?;
+        }
+
+        let attributes = [self.config.display_name_attribute.as_str(), self.config.group_attribute.as_str()];
+        let mut entries = directory.search(&self.config.base_dn, &self.config.filter_for(login), &attributes)?;
+        if entries.len() > 1 {
+            log::warn!("Логин {} неоднозначен в каталоге: найдено {} записей", login, entries.len());
+            return Err(SurveillanceError::UserNotFound);
+        }
+        let entry = entries.pop().ok_or(SurveillanceError::UserNotFound)?;
+
+        directory.bind(&entry.dn, password)?;
+
+        let groups = entry.values(&self.config.group_attribute);
+        let role = self.config.role_for_groups(groups).ok_or_else(|| {
+            log::warn!("Пользователь каталога {} не входит ни в одну разрешённую группу", login);
+            SurveillanceError::PermissionDenied
+        })?;
+
+        Ok(BackendIdentity {
+            login: login.to_string(),
+            role,
+            display_name: entry.values(&self.config.display_name_attribute).first().cloned(),
+            source: UserSource::Directory,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Заменитель сервера каталога в памяти: понимает фильтры вида `(attr=value)`
+    #[derive(Clone, Default)]
+    pub(crate) struct StandInDirectory {
+        entries: Arc<Vec<(DirectoryEntry, String)>>,
+    }
+
+    impl StandInDirectory {
+        pub(crate) fn new(users: &[(&str, &str, &str, &[&str])]) -> Self {
+            let entries = users
+                .iter()
+                .map(|(uid, password, display_name, groups)| {
+                    let mut attributes = HashMap::new();
+                    attributes.insert("uid".to_string(), vec![uid.to_string()]);
+                    attributes.insert("displayName".to_string(), vec![display_name.to_string()]);
+                    attributes.insert("memberOf".to_string(), groups.iter().map(|g| g.to_string()).collect());
+                    let dn = format!("uid={},ou=people,dc=example,dc=org", uid);
+                    (DirectoryEntry { dn, attributes }, password.to_string())
+                })
+                .collect();
+            Self { entries: Arc::new(entries) }
+        }
+
+        pub(crate) fn config() -> LdapConfig {
+            LdapConfig {
+                base_dn: "ou=people,dc=example,dc=org".to_string(),
+                user_filter: "(uid={login})".to_string(),
+                group_roles: vec![
+                    LdapGroupRole { group: "cn=cctv-admins,ou=groups,dc=example,dc=org".to_string(), role: UserRole::Admin },
+                    LdapGroupRole { group: "cctv-operators".to_string(), role: UserRole::Operator },
+                ],
+                ..LdapConfig::default()
+            }
+        }
+
+        /// Сравнение значения фильтра: `*` — подстановка, `\XX` — экранированный байт
+        fn value_matches(pattern: &str, value: &str) -> bool {
+            if pattern.contains('*') {
+                return true;
+            }
+            let mut unescaped = Vec::new();
+            let bytes = pattern.as_bytes();
+            let mut index = 0;
+            while index < bytes.len() {
+                if bytes[index] == b'\\' && index + 2 < bytes.len() {
+                    let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap();
+                    unescaped.push(u8::from_str_radix(hex, 16).unwrap());
+                    index += 3;
+                } else {
+                    unescaped.push(bytes[index]);
+                    index += 1;
+                }
+            }
+            unescaped == value.as_bytes()
+        }
+    }
+
+    impl Directory for StandInDirectory {
+        fn bind(&mut self, dn: &str, password: &str) -> Result<()> {
+            let known = self.entries.iter().any(|(entry, secret)| entry.dn == dn && secret == password);
+            if known { Ok(()) } else { Err(SurveillanceError::InvalidCredentials) }
+        }
+
+        fn search(&mut self, base: &str, filter: &str, _attributes: &[&str]) -> Result<Vec<DirectoryEntry>> {
+            let (attribute, pattern) = filter
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .split_once('=')
+                .ok_or_else(|| SurveillanceError::network_error("ldap_filter_invalid"))?;
+
+            Ok(self.entries
+                .iter()
+                .map(|(entry, _)| entry)
+                .filter(|entry| entry.dn.ends_with(base))
+                .filter(|entry| entry.values(attribute).iter().any(|value| Self::value_matches(pattern, value)))
+                .cloned()
+                .collect())
+        }
+    }
+
+    impl DirectoryConnector for StandInDirectory {
+        fn connect(&self, _config: &LdapConfig) -> Result<Box<dyn Directory>> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    pub(crate) fn stand_in_backend() -> LdapBackend {
+        let directory = StandInDirectory::new(&[
+            ("anna", "Anna-pass-1", "Анна Смирнова", &["cn=cctv-admins,ou=groups,dc=example,dc=org"]),
+            ("oleg", "Oleg-pass-1", "Олег Петров", &["CN=CCTV-Operators,OU=Groups,DC=example,DC=org"]),
+            ("guest", "Guest-pass-1", "Гость", &["cn=staff,ou=groups,dc=example,dc=org"]),
+        ]);
+        LdapBackend::with_connector(StandInDirectory::config(), Box::new(directory))
+    }
+
+    #[test]
+    fn test_bind_and_group_mapping() {
+        let backend = stand_in_backend();
+
+        let anna = backend.authenticate("anna", "Anna-pass-1").unwrap();
+        assert_eq!(anna.role, UserRole::Admin);
+        assert_eq!(anna.display_name.as_deref(), Some("Анна Смирнова"));
+        assert_eq!(anna.source, UserSource::Directory);
+
+        // Группа из настроек по CN совпадает без учёта регистра
+        assert_eq!(backend.authenticate("oleg", "Oleg-pass-1").unwrap().role, UserRole::Operator);
+
+        assert!(matches!(backend.authenticate("anna", "wrong"), Err(SurveillanceError::InvalidCredentials)));
+        assert!(matches!(backend.authenticate("guest", "Guest-pass-1"), Err(SurveillanceError::PermissionDenied)));
+        assert!(matches!(backend.authenticate("nobody", "x"), Err(SurveillanceError::UserNotFound)));
+    }
+
+    #[test]
+    fn test_rejects_anonymous_bind_and_filter_injection() {
+        let backend = stand_in_backend();
+
+        assert!(matches!(backend.authenticate("anna", ""), Err(SurveillanceError::InvalidCredentials)));
+        // `*` экранируется и не превращается в подстановку
+        assert_eq!(StandInDirectory::config().filter_for("*"), "(uid=\\2a)");
+        assert!(matches!(backend.authenticate("*", "Anna-pass-1"), Err(SurveillanceError::UserNotFound)));
+    }
+}
@@ -1,16 +1,57 @@
 // lib.rs - Основная библиотека модулей
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 // Публичные модули
+pub mod api_keys;
+pub mod api_server;
+pub mod audio;
+pub mod audit;
 pub mod auth;
+pub mod auth_backend;
+pub mod camera_status;
 pub mod config;
 pub mod error;
+pub mod error_journal;
+pub mod i18n;
+pub mod ldap;
+pub mod notifications;
+pub mod password;
+pub mod permissions;
+pub mod reconnect;
+pub mod rotation;
+pub mod rtsp;
+pub mod sdp;
+pub mod stream;
+pub mod totp;
 
 // Переэкспорт основных типов для удобства
-pub use auth::{AuthManager, User, UserRole, LoginRequest, LoginResponse};
-pub use config::{Config, Camera, Apartment, Settings, ConfigManager};
-pub use error::{SurveillanceError, Result};
+pub use api_keys::{ApiKey, ApiKeyPrincipal, ApiKeyStatus, ApiKeyStore, ApiKeySummary, CreatedApiKey};
+pub use api_server::{ApiRequest, ApiResponse, ApiRouter, ApiServer, ApiServerSettings};
+pub use audio::{AudioManager, AudioSettings, AudioState};
+pub use audit::{AuditAction, AuditEntry, AuditFilter, AuditLog, AuditVerification};
+pub use auth::{AuthManager, User, UserRole, LoginRequest, LoginResponse, TwoFactorChallenge, UserInfo, UserSummary};
+pub use auth_backend::{AuthBackend, BackendIdentity, LocalBackend, UserSource};
+pub use camera_status::{CameraHealthSettings, CameraObservation, CameraState, CameraStatus, CameraStatusBoard, CameraStatusEvent, CameraSummary};
+pub use config::{Config, ConfigView, Camera, Apartment, Settings, ConfigManager};
+pub use error::{CommandError, CommandResult, ErrorSeverity, SurveillanceError, Result};
+pub use error_journal::{ErrorFilter, ErrorJournal, ErrorJournalSettings, ErrorRecord};
+pub use i18n::{Detail, Locale};
+pub use ldap::{LdapBackend, LdapConfig, LdapGroupRole};
+pub use notifications::{Notification, NotificationCenter, NotificationEvent, NotificationSettings};
+pub use password::{HashAlgorithm, PasswordHashPolicy, PasswordPolicy, PasswordService, PasswordViolation};
+pub use permissions::{AccessLevel, Permission, RoleDefinition, RoleRegistry};
+pub use reconnect::{CircuitState, Clock, ManualClock, ReconnectPolicy, ReconnectSettings, ReconnectStatus, Reconnector, SystemClock};
+pub use rotation::{RotationGroup, RotationPage, RotationScheduler, RotationSettings, RotationState};
+pub use rtsp::{MediaPacket, RtspClient, RtspSession, RtspTrack, RtspTransport};
+pub use sdp::{MediaDescription, SessionDescription};
+pub use stream::hls::{HlsOptions, HlsPackager, HlsRegistry, HlsResource, HlsService, HlsSettings};
+pub use stream::probe::{AudioProbe, ProbeOptions, ProbeReport, VideoProbe};
+pub use stream::rtp::{Depacketizer, Frame, RtpCodec, RtpPacket};
+pub use stream::sps::SpsInfo;
+pub use stream::{StreamOptions, StreamQuality, StreamReconnectStatus, StreamState, StreamStatus, StreamSupervisor};
+pub use totp::{SecretCipher, TotpEnrollment, TwoFactorPolicy};
 
 // Основные структуры данных для всей системы
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +59,9 @@ pub struct SystemState {
     pub current_user: Option<User>,
     pub config: Option<Config>,
     pub is_authenticated: bool,
+    /// Язык интерфейса этой сессии; сохраняется и на экране входа после выхода
+    #[serde(default)]
+    pub locale: Locale,
 }
 
 impl SystemState {
@@ -26,10 +70,12 @@ impl SystemState {
             current_user: None,
             config: None,
             is_authenticated: false,
+            locale: Locale::default(),
         }
     }
 
     pub fn authenticate(&mut self, user: User) {
+        self.locale = user.locale;
         self.current_user = Some(user);
         self.is_authenticated = true;
     }
@@ -38,6 +84,102 @@ impl SystemState {
         self.current_user = None;
         self.is_authenticated = false;
     }
+
+    /// Обновление пользователя сессии после изменения его учётной записи.
+    ///
+    /// Если запись отключена, сессия завершается; возвращает `true` в этом случае.
+    pub fn refresh_user(&mut self, updated: &User) -> bool {
+        let matches = self.current_user.as_ref().is_some_and(|user| user.login == updated.login);
+        if !matches {
+            return false;
+        }
+        if updated.disabled {
+            self.logout();
+            return true;
+        }
+        self.current_user = Some(updated.clone());
+        false
+    }
+
+    /// Реестр ролей из загруженной конфигурации (или роли по умолчанию)
+    pub fn role_registry(&self) -> RoleRegistry {
+        self.config
+            .as_ref()
+            .map(Config::role_registry)
+            .unwrap_or_default()
+    }
+
+    /// Публичные сведения о текущем пользователе для фронтенда
+    pub fn current_user_info(&self) -> Option<UserInfo> {
+        match (&self.current_user, self.is_authenticated) {
+            (Some(user), true) => {
+                let mut info = UserInfo::new(user, &self.role_registry());
+                info.permissions = self.current_permissions().into_iter().collect();
+                Some(info)
+            }
+            _ => None,
+        }
+    }
+
+    /// Язык текущей сессии
+    pub fn current_locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Выбор языка сессии; у вошедшего пользователя он запоминается в записи
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+        if let (Some(user), true) = (self.current_user.as_mut(), self.is_authenticated) {
+            user.locale = locale;
+        }
+    }
+
+    /// Права текущего пользователя
+    pub fn current_permissions(&self) -> BTreeSet<Permission> {
+        match (&self.current_user, self.is_authenticated) {
+            (Some(user), true) => self.role_registry().permissions_for(&user.role),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Пользователь текущей сессии (в том числе до обязательной смены пароля)
+    fn session_user(&self) -> Result<&User> {
+        match (&self.current_user, self.is_authenticated) {
+            (Some(user), true) => Ok(user),
+            _ => Err(SurveillanceError::auth_error("sign_in_required")),
+        }
+    }
+
+    /// Пользователь, которому разрешена обычная работа
+    fn active_user(&self) -> Result<&User> {
+        let user = self.session_user()?;
+        if user.must_change_password {
+            return Err(SurveillanceError::auth_error("password_change_required"));
+        }
+        Ok(user)
+    }
+
+    /// Проверка права текущего пользователя
+    pub fn authorize(&self, permission: Permission) -> Result<User> {
+        let user = self.active_user()?;
+
+        if self.current_permissions().contains(&permission) {
+            Ok(user.clone())
+        } else {
+            log::warn!("Пользователю {} отказано в праве {:?}", user.login, permission);
+            Err(SurveillanceError::PermissionDenied)
+        }
+    }
+
+    /// Проверка уровня доступа команды
+    pub fn check_access(&self, level: AccessLevel) -> Result<()> {
+        match level {
+            AccessLevel::Public => Ok(()),
+            AccessLevel::Session => self.session_user().map(|_| ()),
+            AccessLevel::Authenticated => self.active_user().map(|_| ()),
+            AccessLevel::Permission(permission) => self.authorize(permission).map(|_| ()),
+        }
+    }
 }
 
 impl Default for SystemState {
@@ -55,6 +197,11 @@ pub static SYSTEM_STATE: Lazy<Mutex<SystemState>> = Lazy::new(|| {
 });
 
 // Вспомогательные функции
+/// Язык текущей сессии; если состояние занято вызывающим кодом, используется язык по умолчанию
+pub fn session_locale() -> Locale {
+    SYSTEM_STATE.try_lock().map(|state| state.current_locale()).unwrap_or_default()
+}
+
 pub fn get_current_user() -> Option<User> {
     SYSTEM_STATE.lock().unwrap().current_user.clone()
 }
@@ -63,6 +210,22 @@ pub fn is_authenticated() -> bool {
     SYSTEM_STATE.lock().unwrap().is_authenticated
 }
 
+/// Проверка уровня доступа для текущего пользователя
+pub fn check_access(level: AccessLevel) -> Result<()> {
+    SYSTEM_STATE
+        .lock()
+        .map_err(|e| SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", e.to_string())))?
+        .check_access(level)
+}
+
+/// Единая проверка прав для Tauri команд
+pub fn require_permission(permission: Permission) -> Result<User> {
+    SYSTEM_STATE
+        .lock()
+        .map_err(|e| SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", e.to_string())))?
+        .authorize(permission)
+}
+
 pub fn has_admin_role() -> bool {
     if let Some(user) = get_current_user() {
         matches!(user.role, UserRole::Admin)
@@ -70,3 +233,89 @@ pub fn has_admin_role() -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(login: &str, role: UserRole) -> User {
+        User::new(login, String::new(), role)
+    }
+
+    #[test]
+    fn test_authorize() {
+        let mut state = SystemState::new();
+        assert!(matches!(state.authorize(Permission::ViewLive), Err(SurveillanceError::AuthError { .. })));
+
+        state.authenticate(user("operator1", UserRole::Operator));
+        assert!(state.authorize(Permission::ViewLive).is_ok());
+        assert!(matches!(state.authorize(Permission::ManageCameras), Err(SurveillanceError::PermissionDenied)));
+
+        let mut config = Config::new_test();
+        config.roles.push(RoleDefinition::new("Operator", &[Permission::ViewLive, Permission::ManageCameras]));
+        state.config = Some(config);
+        assert!(state.authorize(Permission::ManageCameras).is_ok());
+    }
+
+    #[test]
+    fn test_check_access() {
+        let mut state = SystemState::new();
+        assert!(state.check_access(AccessLevel::Public).is_ok());
+        assert!(state.check_access(AccessLevel::Authenticated).is_err());
+        assert!(state.check_access(AccessLevel::Permission(Permission::ViewLive)).is_err());
+
+        state.authenticate(user("operator1", UserRole::Operator));
+        assert!(state.check_access(AccessLevel::Authenticated).is_ok());
+        assert!(state.check_access(AccessLevel::Permission(Permission::ViewLive)).is_ok());
+        assert!(state.check_access(AccessLevel::Permission(Permission::ManageUsers)).is_err());
+
+        state.logout();
+        assert!(state.check_access(AccessLevel::Authenticated).is_err());
+
+        // До смены сброшенного пароля доступны только команды сессии
+        let mut reset = user("ivan", UserRole::Operator);
+        reset.must_change_password = true;
+        state.authenticate(reset);
+        assert!(state.check_access(AccessLevel::Session).is_ok());
+        assert!(state.check_access(AccessLevel::Authenticated).is_err());
+        assert!(state.check_access(AccessLevel::Permission(Permission::ViewLive)).is_err());
+    }
+
+    #[test]
+    fn test_locale_belongs_to_session() {
+        let mut state = SystemState::new();
+        let mut operator = user("operator1", UserRole::Operator);
+        operator.locale = Locale::En;
+        state.authenticate(operator);
+        assert_eq!(state.current_locale(), Locale::En);
+
+        // Выход не сбрасывает выбранный язык экрана входа
+        state.logout();
+        assert_eq!(state.current_locale(), Locale::En);
+        state.set_locale(Locale::Ru);
+        assert_eq!(state.current_locale(), Locale::Ru);
+    }
+
+    #[test]
+    fn test_refresh_user_applies_account_changes() {
+        let mut state = SystemState::new();
+        let mut operator = user("operator1", UserRole::Operator);
+        state.authenticate(operator.clone());
+
+        // Изменения чужой учётной записи сессию не трогают
+        let mut other = user("ivan", UserRole::Operator);
+        other.disabled = true;
+        assert!(!state.refresh_user(&other));
+        assert!(state.is_authenticated);
+
+        // Смена роли сразу меняет права сессии
+        operator.role = UserRole::Custom("guest".to_string());
+        assert!(!state.refresh_user(&operator));
+        assert!(state.check_access(AccessLevel::Authenticated).is_ok());
+        assert!(state.check_access(AccessLevel::Permission(Permission::ViewLive)).is_err());
+
+        operator.disabled = true;
+        assert!(state.refresh_user(&operator));
+        assert!(state.check_access(AccessLevel::Authenticated).is_err());
+    }
+}
@@ -7,9 +7,19 @@
 
 use surveillance_system::{
     AuthManager, ConfigManager, LoginRequest, LoginResponse, 
-    User, Config, Apartment, Camera, get_current_user, is_authenticated, has_admin_role, SYSTEM_STATE
+    User, UserInfo, Config, ConfigView, Apartment, Camera, Permission, get_current_user, is_authenticated, has_admin_role,
+    UserRole, UserSummary, AccessLevel, CommandError, CommandResult, Detail, Locale, ApiKeyStore, ApiKeySummary, ApiRouter, ApiServer, CreatedApiKey, AuthBackend, HlsOptions, HlsService, LdapBackend, AuditAction, AuditEntry, AuditFilter, AuditLog, AuditVerification, AudioState, ErrorFilter, ErrorJournal, ErrorRecord, ErrorSeverity, Notification, NotificationCenter, NotificationEvent, ProbeOptions, ProbeReport, SecretCipher, Settings, StreamOptions, StreamQuality, StreamReconnectStatus, CameraStatus, CameraSummary, RotationState, StreamStatus, StreamSupervisor, SurveillanceError, TotpEnrollment, check_access, require_permission,
+    SYSTEM_STATE
 };
+use surveillance_system::audit::json_diff;
+use surveillance_system::audio::AUDIO;
+use surveillance_system::camera_status::CAMERA_STATUS;
+use surveillance_system::rotation::{self, ROTATION};
+use surveillance_system::error_journal::{self, ERROR_JOURNAL};
+use surveillance_system::notifications::{self, NOTIFICATIONS};
+use surveillance_system::stream;
 use std::sync::Mutex;
+use tauri::Manager;
 use once_cell::sync::Lazy;
 
 // Глобальные менеджеры
@@ -21,94 +31,457 @@ static CONFIG_MANAGER: Lazy<Mutex<ConfigManager>> = Lazy::new(|| {
     Mutex::new(ConfigManager::new())
 });
 
+// Журнал аудита (файловый журнал открывается при запуске)
+static AUDIT_LOG: Lazy<Mutex<AuditLog>> = Lazy::new(|| {
+    Mutex::new(AuditLog::in_memory())
+});
+
+// API-ключи машинных клиентов (файловое хранилище открывается при запуске)
+static API_KEYS: Lazy<Mutex<ApiKeyStore>> = Lazy::new(|| {
+    Mutex::new(ApiKeyStore::in_memory())
+});
+
+// HTTP API для машинных клиентов: ключ проверяется на каждом запросе
+static API_SERVER: Lazy<ApiServer> = Lazy::new(|| ApiServer::new(api_router()));
+
+// Процессы FFmpeg; сегменты HLS пишутся в каталог кэша
+static STREAMS: Lazy<StreamSupervisor> = Lazy::new(|| {
+    let output_dir = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("surveillance-system")
+        .join("streams");
+    StreamSupervisor::new(StreamOptions::from_settings(&Settings::default(), &output_dir))
+});
+
+// Встроенный упаковщик HLS; сегменты живут в памяти и отдаются по протоколу hls://
+static HLS: Lazy<HlsService> = Lazy::new(|| {
+    HlsService::new(HlsOptions::from_settings(&Settings::default()))
+});
+
+/// Ошибка фоновой задачи: в журнал ошибок и в центр уведомлений
+fn report_error(error: &SurveillanceError, context: &str) {
+    error_journal::report(error, context, None);
+    notifications::report(error, context);
+}
+
+/// Запись события в журнал аудита; сбой записи не прерывает команду
+fn audit(actor: Option<&str>, action: AuditAction, target: Option<&str>, details: serde_json::Value) {
+    match AUDIT_LOG.lock() {
+        Ok(mut journal) => {
+            if let Err(e) = journal.record(actor, action, target, details) {
+                log::error!("Не удалось записать событие аудита {:?}: {}", action, e);
+            }
+        }
+        Err(e) => log::error!("Журнал аудита недоступен: {}", e),
+    }
+}
+
+/// Запись изменения конфигурации с разницей между версиями
+fn audit_config_change(actor: &str, target: &str, before: &Config, after: &Config) {
+    // Разница строится по представлению без секретов учётных записей
+    let diff = match (serde_json::to_value(before.view()), serde_json::to_value(after.view())) {
+        (Ok(before), Ok(after)) => json_diff(&before, &after),
+        _ => serde_json::Value::Null,
+    };
+    audit(Some(actor), AuditAction::ConfigChanged, Some(target), diff);
+}
+
 // Tauri команды для авторизации
 #[tauri::command]
-fn login(request: LoginRequest) -> Result<LoginResponse, String> {
+fn login(request: LoginRequest) -> CommandResult<LoginResponse> {
     log::info!("Попытка входа пользователя: {}", request.login);
     
-    let auth_manager = AUTH_MANAGER.lock().map_err(|e| e.to_string())?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
     let response = auth_manager.authenticate(&request);
     
     if response.success {
-        // Обновляем глобальное состояние
-        if let Some(user) = &response.user {
-            SYSTEM_STATE.lock().map_err(|e| e.to_string())?.authenticate(user.clone());
-            log::info!("Пользователь {} успешно авторизован", user.login);
-        }
+        finish_login(&auth_manager, &response)?;
+        audit(Some(&request.login), AuditAction::LoginSuccess, None, serde_json::Value::Null);
+    } else if response.two_factor.is_some() {
+        log::info!("Пользователь {} ожидает подтверждения вторым фактором", request.login);
+        audit(Some(&request.login), AuditAction::TwoFactorChallenge, None, serde_json::Value::Null);
     } else {
         log::warn!("Неудачная попытка входа для пользователя: {}", request.login);
+        audit(
+            Some(&request.login),
+            AuditAction::LoginFailure,
+            None,
+            serde_json::json!({ "reason": response.message, "code": response.code }),
+        );
     }
     
     Ok(response)
 }
 
+/// Обновление глобального состояния после успешного входа
+fn finish_login(auth_manager: &AuthManager, response: &LoginResponse) -> CommandResult<()> {
+    let user = response.user
+        .as_ref()
+        .and_then(|info| auth_manager.get_user(&info.login));
+
+    if let Some(user) = user {
+        SYSTEM_STATE.lock()?.authenticate(user.clone());
+        log::info!("Пользователь {} успешно авторизован", user.login);
+
+        // Оператор сразу видит критические уведомления, пришедшие без него
+        NOTIFICATIONS.lock()?.replay_pending(user.locale);
+        // Новая сессия начинается без звука и потоков предыдущего пользователя
+        stop_session_playback()?;
+        sync_rotation()?;
+    }
+    Ok(())
+}
+
+/// Камеры ротации по доступу текущего пользователя; без сессии ротация пуста
+fn sync_rotation() -> CommandResult<()> {
+    let groups = match get_current_user() {
+        Some(user) => {
+            let config_manager = CONFIG_MANAGER.lock()?;
+            let config = config_manager.get_config();
+            rotation::rotation_groups(config, &user, config.settings.rotation.group_by_apartment)
+        }
+        None => Vec::new(),
+    };
+    ROTATION.lock()?.set_groups(groups);
+    Ok(())
+}
+
+/// Пользователь текущей сессии
+fn session_user() -> CommandResult<User> {
+    get_current_user().ok_or_else(|| SurveillanceError::auth_error("sign_in_required").into())
+}
+
 #[tauri::command]
-fn logout() -> Result<(), String> {
+fn verify_two_factor(challenge_token: String, code: String) -> CommandResult<LoginResponse> {
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    let response = auth_manager.verify_two_factor(&challenge_token, &code);
+
+    let login = response.user.as_ref().map(|user| user.login.clone());
+    if response.success {
+        finish_login(&auth_manager, &response)?;
+        audit(login.as_deref(), AuditAction::LoginSuccess, None, serde_json::json!({ "two_factor": true }));
+    } else {
+        log::warn!("Неверный код второго фактора");
+        audit(None, AuditAction::TwoFactorFailure, None, serde_json::json!({ "reason": response.message, "code": response.code }));
+    }
+
+    Ok(response)
+}
+
+#[tauri::command]
+fn start_two_factor_enrollment(challenge_token: String) -> CommandResult<TotpEnrollment> {
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    auth_manager
+        .begin_totp_enrollment_for_challenge(&challenge_token)
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+fn begin_totp_enrollment() -> CommandResult<TotpEnrollment> {
+    let user = session_user()?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    Ok(auth_manager.begin_totp_enrollment(&user.login)?)
+}
+
+#[tauri::command]
+fn confirm_totp_enrollment(code: String) -> CommandResult<()> {
+    let user = session_user()?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    auth_manager.confirm_totp_enrollment(&user.login, &code)?;
+    audit(Some(&user.login), AuditAction::TwoFactorEnrolled, Some(&user.login), serde_json::Value::Null);
+    Ok(())
+}
+
+#[tauri::command]
+fn disable_totp(password: String, code: String) -> CommandResult<()> {
+    let user = session_user()?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    auth_manager.disable_totp(&user.login, &password, &code)?;
+    audit(Some(&user.login), AuditAction::TwoFactorDisabled, Some(&user.login), serde_json::Value::Null);
+    Ok(())
+}
+
+#[tauri::command]
+fn logout() -> CommandResult<()> {
     log::info!("Выход пользователя из системы");
     
-    SYSTEM_STATE.lock().map_err(|e| e.to_string())?.logout();
+    if let Some(user) = get_current_user() {
+        audit(Some(&user.login), AuditAction::Logout, None, serde_json::Value::Null);
+    }
+    SYSTEM_STATE.lock()?.logout();
+    stop_session_playback()?;
+    sync_rotation()?;
     
     Ok(())
 }
 
+/// Конец просмотра сессии: звук выключается, потоки FFmpeg и публикации HLS останавливаются
+fn stop_session_playback() -> CommandResult<()> {
+    AUDIO.lock()?.mute_all();
+    STREAMS.signal_stop_all()?;
+    Ok(HLS.signal_stop_all()?)
+}
+
+/// Выбор языка сообщений; для вошедшего пользователя сохраняется в его учётной записи
 #[tauri::command]
-fn get_current_user_info() -> Result<Option<User>, String> {
-    Ok(get_current_user())
+fn set_language(locale: Locale) -> CommandResult<()> {
+    // Состояние освобождается до сохранения: ошибка сама читает язык сессии
+    let login = {
+        let mut state = SYSTEM_STATE.lock()?;
+        state.set_locale(locale);
+        state.current_user_info().map(|user| user.login)
+    };
+    if let Some(login) = login {
+        AUTH_MANAGER.lock()?.set_locale(&login, locale)?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
-fn check_authentication() -> Result<bool, String> {
+fn get_current_user_info() -> CommandResult<Option<UserInfo>> {
+    Ok(SYSTEM_STATE.lock()?.current_user_info())
+}
+
+#[tauri::command]
+fn check_authentication() -> CommandResult<bool> {
     Ok(is_authenticated())
 }
 
 #[tauri::command]
-fn check_admin_role() -> Result<bool, String> {
+fn check_admin_role() -> CommandResult<bool> {
     Ok(has_admin_role())
 }
 
+#[tauri::command]
+fn get_current_permissions() -> CommandResult<Vec<Permission>> {
+    let state = SYSTEM_STATE.lock()?;
+    Ok(state.current_permissions().into_iter().collect())
+}
+
+// Tauri команды управления пользователями
+#[tauri::command]
+fn list_users() -> CommandResult<Vec<UserSummary>> {
+    let auth_manager = AUTH_MANAGER.lock()?;
+    Ok(auth_manager.list_users())
+}
+
+#[tauri::command]
+fn create_user(
+    login: String,
+    password: String,
+    role: UserRole,
+    display_name: Option<String>,
+    allowed_apartments: Option<Vec<String>>,
+) -> CommandResult<()> {
+    let actor = require_permission(Permission::ManageUsers)?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+
+    auth_manager.add_user(login.clone(), password, role.clone())?;
+    auth_manager.set_allowed_apartments(&login, allowed_apartments.clone())?;
+    auth_manager.set_display_name(&login, display_name)?;
+
+    log::info!("Администратор {} создал пользователя {}", actor.login, login);
+    audit(
+        Some(&actor.login),
+        AuditAction::UserCreated,
+        Some(&login),
+        serde_json::json!({ "role": role, "allowed_apartments": allowed_apartments }),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+fn set_user_disabled(login: String, disabled: bool) -> CommandResult<()> {
+    let actor = require_permission(Permission::ManageUsers)?;
+    if actor.login == login && disabled {
+        return Err(SurveillanceError::auth_error("cannot_disable_self").into());
+    }
+
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    auth_manager.set_user_disabled(&login, disabled)?;
+    refresh_session_user(&auth_manager, &login)?;
+
+    audit(Some(&actor.login), AuditAction::UserUpdated, Some(&login), serde_json::json!({ "disabled": disabled }));
+    Ok(())
+}
+
+/// Перенос изменений учётной записи в текущую сессию; отключённый пользователь выходит
+fn refresh_session_user(auth_manager: &AuthManager, login: &str) -> CommandResult<()> {
+    let Some(updated) = auth_manager.get_user(login) else {
+        return Ok(());
+    };
+    let ended = SYSTEM_STATE.lock()?.refresh_user(updated);
+    if ended {
+        log::info!("Сессия пользователя {} завершена: учётная запись отключена", login);
+        stop_session_playback()?;
+    }
+    // Доступные квартиры могли измениться
+    sync_rotation()
+}
+
+#[tauri::command]
+fn set_user_role(login: String, role: UserRole) -> CommandResult<()> {
+    let actor = require_permission(Permission::ManageUsers)?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+
+    let previous = auth_manager.get_user(&login).map(|user| user.role.clone()).ok_or(SurveillanceError::UserNotFound)?;
+    auth_manager.set_user_role(&login, role.clone())?;
+    refresh_session_user(&auth_manager, &login)?;
+
+    audit(
+        Some(&actor.login),
+        AuditAction::UserUpdated,
+        Some(&login),
+        serde_json::json!({ "role": { "old": previous, "new": role } }),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+fn set_user_apartments(login: String, allowed_apartments: Option<Vec<String>>) -> CommandResult<()> {
+    let actor = require_permission(Permission::ManageUsers)?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    auth_manager.set_allowed_apartments(&login, allowed_apartments.clone())?;
+    refresh_session_user(&auth_manager, &login)?;
+
+    audit(
+        Some(&actor.login),
+        AuditAction::UserUpdated,
+        Some(&login),
+        serde_json::json!({ "allowed_apartments": allowed_apartments }),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+fn reset_user_password(login: String) -> CommandResult<String> {
+    let actor = require_permission(Permission::ManageUsers)?;
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    let temporary = auth_manager.reset_password(&login)?;
+
+    log::info!("Администратор {} сбросил пароль пользователя {}", actor.login, login);
+    audit(Some(&actor.login), AuditAction::PasswordReset, Some(&login), serde_json::Value::Null);
+    Ok(temporary)
+}
+
+#[tauri::command]
+fn change_own_password(old_password: String, new_password: String) -> CommandResult<()> {
+    let user = session_user()?;
+    let updated = {
+        let mut auth_manager = AUTH_MANAGER.lock()?;
+        auth_manager
+            .change_password(&user.login, &old_password, &new_password)
+            ?;
+        auth_manager.get_user(&user.login).cloned()
+    };
+
+    // Снимаем ограничение сессии, если пароль был сброшен администратором
+    if let Some(updated) = updated {
+        SYSTEM_STATE.lock()?.authenticate(updated);
+    }
+
+    audit(Some(&user.login), AuditAction::PasswordChanged, Some(&user.login), serde_json::Value::Null);
+    Ok(())
+}
+
+/// Применение настроек авторизации, журналов и потоков из конфигурации
+fn apply_settings(config: &Config) -> CommandResult<()> {
+    ERROR_JOURNAL.lock()?.set_settings(config.settings.error_journal.clone());
+    NOTIFICATIONS.lock()?.set_settings(config.settings.notifications.clone());
+    STREAMS.apply_settings(&config.settings)?;
+    HLS.apply_settings(&config.settings)?;
+    let api_settings = config.settings.api_server.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = API_SERVER.apply_settings(&api_settings).await {
+            report_error(&e, "api_server");
+        }
+    });
+    {
+        let mut cameras = CAMERA_STATUS.lock()?;
+        cameras.set_settings(config.settings.camera_health.clone());
+        cameras.sync_cameras(&config.cameras);
+    }
+    ROTATION.lock()?.apply_settings(&config.settings);
+    AUDIO.lock()?.apply_settings(&config.settings);
+
+    let mut auth_manager = AUTH_MANAGER.lock()?;
+    auth_manager.set_hash_policy(config.settings.password_hashing.clone())?;
+    auth_manager.set_password_policy(config.settings.password_policy.clone());
+    auth_manager.set_two_factor_policy(config.settings.two_factor.clone())?;
+    auth_manager.set_role_registry(config.role_registry());
+    auth_manager.set_directory_backend(
+        config.settings.ldap
+            .clone()
+            .map(|ldap| Box::new(LdapBackend::new(ldap)) as Box<dyn AuthBackend>),
+    );
+    Ok(())
+}
+
 // Tauri команды для конфигурации
 #[tauri::command]
-async fn load_config() -> Result<Config, String> {
+async fn load_config() -> CommandResult<ConfigView> {
+    let user = require_permission(Permission::ViewLive)?;
     log::info!("Загрузка конфигурации");
     
-    // Создаем временный ConfigManager для async операций
-    let mut temp_config_manager = ConfigManager::new();
-    temp_config_manager.load_from_nextcloud().await.map_err(|e| e.to_string())?;
-    
-    let config = temp_config_manager.get_config().clone();
-    
-    // Обновляем глобальный ConfigManager синхронно
-    {
-        let mut config_manager = CONFIG_MANAGER.lock().map_err(|e| e.to_string())?;
-        config_manager.update_config(config.clone()).map_err(|e| e.to_string())?;
+    // Перечитать конфигурацию и её политики безопасности может только пользователь
+    // с правом изменения настроек; остальные получают уже установленную
+    if check_access(AccessLevel::Permission(Permission::ManageSettings)).is_ok() {
+        // Создаем временный ConfigManager для async операций
+        let mut temp_config_manager = ConfigManager::new();
+        if temp_config_manager.load_from_nextcloud().await? {
+            let config = temp_config_manager.get_config().clone();
+            {
+                let mut config_manager = CONFIG_MANAGER.lock()?;
+                let previous = config_manager.get_config().clone();
+                config_manager.update_config(config.clone())?;
+                audit_config_change(&user.login, "config", &previous, &config);
+            }
+            install_config(&config)?;
+            STREAMS.retain_cameras(&config.cameras).await?;
+            HLS.retain_cameras(&config.cameras).await?;
+            log::info!("Конфигурация загружена: {} квартир, {} камер", 
+                       config.apartments.len(), config.cameras.len());
+        }
     }
     
-    // Обновляем глобальное состояние
-    SYSTEM_STATE.lock().map_err(|e| e.to_string())?.config = Some(config.clone());
-    
-    log::info!("Конфигурация загружена: {} квартир, {} камер", 
-               config.apartments.len(), config.cameras.len());
-    
-    Ok(config)
+    // Пользователю возвращаются только доступные ему квартиры и камеры
+    let config = CONFIG_MANAGER.lock()?.get_config().clone();
+    Ok(config.filtered_for_user(&user))
+}
+
+/// Применение настроек и публикация конфигурации в глобальном состоянии
+fn install_config(config: &Config) -> CommandResult<()> {
+    // Применяем парольные политики и источник учётных данных из настроек
+    apply_settings(config)?;
+    // Пользовательские роли берутся из конфигурации состояния
+    SYSTEM_STATE.lock()?.config = Some(config.clone());
+    sync_rotation()
 }
 
 #[tauri::command]
-fn get_apartments() -> Result<Vec<Apartment>, String> {
-    let config_manager = CONFIG_MANAGER.lock().map_err(|e| e.to_string())?;
+fn get_apartments() -> CommandResult<Vec<Apartment>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
     let config = config_manager.get_config();
-    Ok(config.apartments.clone())
+    Ok(config.apartments_for_user(&user).into_iter().cloned().collect())
 }
 
 #[tauri::command]
-fn get_cameras() -> Result<Vec<Camera>, String> {
-    let config_manager = CONFIG_MANAGER.lock().map_err(|e| e.to_string())?;
+fn get_cameras() -> CommandResult<Vec<Camera>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
     let config = config_manager.get_config();
-    Ok(config.cameras.clone())
+    Ok(config.cameras_for_user(&user).into_iter().cloned().collect())
 }
 
 #[tauri::command]
-fn get_cameras_by_apartment(apartment_name: String) -> Result<Vec<Camera>, String> {
-    let config_manager = CONFIG_MANAGER.lock().map_err(|e| e.to_string())?;
+fn get_cameras_by_apartment(apartment_name: String) -> CommandResult<Vec<Camera>> {
+    let user = require_permission(Permission::ViewLive)?;
+    if !user.can_access_apartment(&apartment_name) {
+        return Err(SurveillanceError::PermissionDenied.into());
+    }
+
+    let config_manager = CONFIG_MANAGER.lock()?;
     let config = config_manager.get_config();
     
     let cameras = config.get_cameras_by_apartment(&apartment_name)
@@ -120,53 +493,64 @@ fn get_cameras_by_apartment(apartment_name: String) -> Result<Vec<Camera>, Strin
 }
 
 #[tauri::command]
-fn add_camera(name: String, apartment: String, rtsp_link: String) -> Result<u32, String> {
-    // Проверяем права администратора
-    if !has_admin_role() {
-        return Err("Недостаточно прав доступа".to_string());
-    }
+fn get_camera(camera_id: u32) -> CommandResult<Camera> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
+    config_manager.get_config()
+        .camera_for_user(camera_id, &user)
+        .cloned()
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+fn add_camera(name: String, apartment: String, rtsp_link: String) -> CommandResult<u32> {
+    let user = require_permission(Permission::ManageCameras)?;
     
     log::info!("Добавление камеры: {} в квартиру {}", name, apartment);
     
     let camera_id = {
-        let mut config_manager = CONFIG_MANAGER.lock().map_err(|e| e.to_string())?;
+        let mut config_manager = CONFIG_MANAGER.lock()?;
         
         // Получаем мутабельную ссылку на конфигурацию
         let config = config_manager.get_config().clone();
         let mut updated_config = config;
         
         let camera_id = updated_config.add_camera(name, apartment, rtsp_link)
-            .map_err(|e| e.to_string())?;
+            ?;
         
         // Обновляем конфигурацию
-        config_manager.update_config(updated_config).map_err(|e| e.to_string())?;
+        let previous = config_manager.get_config().clone();
+        config_manager.update_config(updated_config.clone())?;
+        audit_config_change(&user.login, &format!("camera:{}", camera_id), &previous, &updated_config);
+        // Статус отслеживается только для камер, попавших в сохранённую конфигурацию
+        CAMERA_STATUS.lock()?.sync_cameras(&updated_config.cameras);
         
         camera_id
     };
     
+    sync_rotation()?;
     log::info!("Камера добавлена с ID: {}", camera_id);
     Ok(camera_id)
 }
 
 #[tauri::command]
-fn add_apartment(name: String, number: String) -> Result<u32, String> {
-    // Проверяем права администратора
-    if !has_admin_role() {
-        return Err("Недостаточно прав доступа".to_string());
-    }
+fn add_apartment(name: String, number: String) -> CommandResult<u32> {
+    let user = require_permission(Permission::ManageApartments)?;
     
     log::info!("Добавление квартиры: {} ({})", name, number);
     
     let apartment_id = {
-        let mut config_manager = CONFIG_MANAGER.lock().map_err(|e| e.to_string())?;
+        let mut config_manager = CONFIG_MANAGER.lock()?;
         
         let config = config_manager.get_config().clone();
         let mut updated_config = config;
         
         let apartment_id = updated_config.add_apartment(name, number)
-            .map_err(|e| e.to_string())?;
+            ?;
         
-        config_manager.update_config(updated_config).map_err(|e| e.to_string())?;
+        let previous = config_manager.get_config().clone();
+        config_manager.update_config(updated_config.clone())?;
+        audit_config_change(&user.login, &format!("apartment:{}", apartment_id), &previous, &updated_config);
         
         apartment_id
     };
@@ -175,18 +559,431 @@ fn add_apartment(name: String, number: String) -> Result<u32, String> {
     Ok(apartment_id)
 }
 
+// Tauri команды журнала аудита
+#[tauri::command]
+fn query_audit_log(filter: AuditFilter) -> CommandResult<Vec<AuditEntry>> {
+    let journal = AUDIT_LOG.lock()?;
+    Ok(journal.query(&filter)?)
+}
+
+#[tauri::command]
+fn export_audit_log(filter: AuditFilter) -> CommandResult<String> {
+    let user = session_user()?;
+    let journal = AUDIT_LOG.lock()?;
+    let export = journal.export(&filter)?;
+    log::info!("Пользователь {} выгрузил журнал аудита", user.login);
+    Ok(export)
+}
+
+#[tauri::command]
+fn verify_audit_log() -> CommandResult<AuditVerification> {
+    let verification = AUDIT_LOG.lock()?.verify()?;
+    if !verification.valid {
+        log::error!(
+            "Журнал аудита нарушен: запись {:?}, неразобранные строки {:?}",
+            verification.first_invalid_seq,
+            verification.corrupt_lines
+        );
+        let notified = NOTIFICATIONS
+            .lock()?
+            .notify(ErrorSeverity::Critical, "audit", Detail::new("audit_integrity_failed"));
+        notified?;
+    }
+    Ok(verification)
+}
+
+// Tauri команды журнала ошибок (панель диагностики)
+#[tauri::command]
+fn query_error_journal(filter: ErrorFilter) -> CommandResult<Vec<ErrorRecord>> {
+    // Ошибка преобразуется после освобождения журнала: она сама в него записывается
+    let records = ERROR_JOURNAL.lock()?.query(&filter);
+    Ok(records?)
+}
+
+#[tauri::command]
+fn clear_error_journal() -> CommandResult<()> {
+    let user = session_user()?;
+    let cleared = ERROR_JOURNAL.lock()?.clear();
+    cleared?;
+
+    log::info!("Пользователь {} очистил журнал ошибок", user.login);
+    audit(Some(&user.login), AuditAction::ErrorJournalCleared, None, serde_json::Value::Null);
+    Ok(())
+}
+
+// Tauri команды управления потоками.
+//
+// Интерфейс показывает камеры через встроенный упаковщик (`start_hls_stream`,
+// протокол hls://). FFmpeg (`start_stream`) — запасной путь для камер, которые
+// упаковщик не принимает (например, H.265), с двумя качествами на диске.
+// `stop_stream` останавливает камеру на обоих путях.
+#[tauri::command]
+async fn start_stream(camera_id: u32, quality: StreamQuality) -> CommandResult<StreamStatus> {
+    let user = require_permission(Permission::ViewLive)?;
+    let camera = CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?.clone();
+    Ok(STREAMS.start(&camera, quality)?)
+}
+
+#[tauri::command]
+async fn stop_stream(camera_id: u32) -> CommandResult<Option<StreamStatus>> {
+    let user = require_permission(Permission::ViewLive)?;
+    CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?;
+    AUDIO.lock()?.mute(camera_id);
+    HLS.stop(camera_id).await?;
+    Ok(STREAMS.stop(camera_id).await?)
+}
+
+/// Публикация камеры встроенным упаковщиком; возвращает адрес плейлиста для webview
+#[tauri::command]
+async fn start_hls_stream(camera_id: u32) -> CommandResult<String> {
+    let user = require_permission(Permission::ViewLive)?;
+    let camera = CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?.clone();
+    Ok(HLS.start(&camera)?)
+}
+
+#[tauri::command]
+async fn stop_hls_stream(camera_id: u32) -> CommandResult<bool> {
+    let user = require_permission(Permission::ViewLive)?;
+    CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?;
+    Ok(HLS.stop(camera_id).await?)
+}
+
+/// Ответ протокола hls://: плейлист или сегмент камеры, доступной пользователю сессии.
+///
+/// Доступ проверяется так же, как у команд просмотра: право ViewLive и
+/// сменённый пароль, затем квартира камеры.
+fn hls_response(uri: &str) -> Result<tauri::http::Response, Box<dyn std::error::Error>> {
+    let path = stream::hls::request_path(uri);
+    let allowed = path
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .and_then(|camera_id| camera_id.parse::<u32>().ok())
+        .zip(require_permission(Permission::ViewLive).ok())
+        .is_some_and(|(camera_id, user)| {
+            CONFIG_MANAGER
+                .lock()
+                .is_ok_and(|config_manager| config_manager.get_config().camera_for_user(camera_id, &user).is_ok())
+        });
+    if !allowed {
+        return tauri::http::ResponseBuilder::new().status(403).body(Vec::new());
+    }
+    match HLS.registry().respond(path) {
+        Some(resource) => tauri::http::ResponseBuilder::new()
+            .status(200)
+            .mimetype(resource.content_type)
+            // Плейлист меняется каждые несколько секунд
+            .header("Cache-Control", "no-cache")
+            .body(resource.body),
+        None => tauri::http::ResponseBuilder::new().status(404).body(Vec::new()),
+    }
+}
+
+/// Переключение сетки и полного экрана без переподключения к камере
+#[tauri::command]
+fn set_stream_quality(camera_id: u32, quality: StreamQuality) -> CommandResult<StreamStatus> {
+    let user = require_permission(Permission::ViewLive)?;
+    CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?;
+    Ok(STREAMS.set_quality(camera_id, quality)?)
+}
+
+/// Включение или выключение звука камеры; в эксклюзивном режиме предыдущая камера замолкает
+#[tauri::command]
+fn toggle_audio(camera_id: u32) -> CommandResult<AudioState> {
+    let user = require_permission(Permission::ListenAudio)?;
+    let camera = CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?.clone();
+    let mut audio = AUDIO.lock()?;
+    if !camera.enabled && !audio.is_audible(camera_id) {
+        drop(audio);
+        return Err(SurveillanceError::CameraUnavailable { camera_id }.into());
+    }
+    audio.toggle(camera_id);
+    Ok(audio.state())
+}
+
+#[tauri::command]
+fn set_audio_volume(camera_id: u32, volume: f32) -> CommandResult<AudioState> {
+    let user = require_permission(Permission::ListenAudio)?;
+    CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?;
+    let mut audio = AUDIO.lock()?;
+    let result = audio.set_volume(camera_id, volume).map(|_| audio.state());
+    // Ошибка преобразуется после снятия блокировки
+    drop(audio);
+    Ok(result?)
+}
+
+#[tauri::command]
+fn get_audio_state() -> CommandResult<AudioState> {
+    require_permission(Permission::ViewLive)?;
+    Ok(AUDIO.lock()?.state())
+}
+
+/// Состояние потоков камер, доступных пользователю
+#[tauri::command]
+fn get_stream_status() -> CommandResult<Vec<StreamStatus>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
+    let config = config_manager.get_config();
+    Ok(STREAMS
+        .statuses()?
+        .into_iter()
+        .filter(|status| config.camera_for_user(status.camera_id, &user).is_ok())
+        .collect())
+}
+
+/// Состояние камер, доступных пользователю
+#[tauri::command]
+fn get_camera_statuses() -> CommandResult<Vec<CameraStatus>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
+    let config = config_manager.get_config();
+    let statuses = CAMERA_STATUS.lock()?.list();
+    Ok(statuses
+        .into_iter()
+        .filter(|status| config.camera_for_user(status.camera_id, &user).is_ok())
+        .collect())
+}
+
+// Tauri команды ротации сетки
+#[tauri::command]
+fn get_rotation_state() -> CommandResult<RotationState> {
+    require_permission(Permission::ViewLive)?;
+    Ok(ROTATION.lock()?.state())
+}
+
+#[tauri::command]
+fn set_rotation_paused(paused: bool) -> CommandResult<RotationState> {
+    require_permission(Permission::ViewLive)?;
+    let mut scheduler = ROTATION.lock()?;
+    scheduler.set_paused(paused);
+    Ok(scheduler.state())
+}
+
+/// Переход вперёд или назад на `offset` страниц
+#[tauri::command]
+fn step_rotation(offset: i64) -> CommandResult<RotationState> {
+    require_permission(Permission::ViewLive)?;
+    let mut scheduler = ROTATION.lock()?;
+    scheduler.step(offset);
+    Ok(scheduler.state())
+}
+
+#[tauri::command]
+fn go_to_rotation_page(index: usize) -> CommandResult<RotationState> {
+    require_permission(Permission::ViewLive)?;
+    let mut scheduler = ROTATION.lock()?;
+    scheduler.go_to(index);
+    Ok(scheduler.state())
+}
+
+#[tauri::command]
+fn pin_camera(camera_id: u32, pinned: bool) -> CommandResult<RotationState> {
+    let user = require_permission(Permission::ViewLive)?;
+    CONFIG_MANAGER.lock()?.get_config().camera_for_user(camera_id, &user)?;
+    let mut scheduler = ROTATION.lock()?;
+    scheduler.set_pinned(camera_id, pinned);
+    Ok(scheduler.state())
+}
+
+/// Состояние переподключения потоков камер, доступных пользователю
+#[tauri::command]
+fn get_reconnect_status(camera_id: Option<u32>) -> CommandResult<Vec<StreamReconnectStatus>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
+    let config = config_manager.get_config();
+    Ok(STREAMS
+        .reconnect_statuses()?
+        .into_iter()
+        .filter(|status| camera_id.is_none() || camera_id == Some(status.camera_id))
+        .filter(|status| config.camera_for_user(status.camera_id, &user).is_ok())
+        .collect())
+}
+
+/// Проверка камеры из конфигурации или ссылки, которую администратор ещё не сохранил
+#[tauri::command]
+async fn probe_camera(camera_id: Option<u32>, url: Option<String>) -> CommandResult<ProbeReport> {
+    let user = require_permission(Permission::ManageCameras)?;
+    let (options, camera) = {
+        let config_manager = CONFIG_MANAGER.lock()?;
+        let config = config_manager.get_config();
+        let camera = match camera_id {
+            Some(camera_id) => Some(config.camera_for_user(camera_id, &user)?.clone()),
+            None => None,
+        };
+        (ProbeOptions::from_settings(&config.settings), camera)
+    };
+
+    let report = match (camera, url) {
+        (Some(camera), _) => stream::probe::probe_camera(&camera, &options).await,
+        (None, Some(url)) => stream::probe::probe_url(&url, &options).await,
+        (None, None) => Err(SurveillanceError::config_error("probe_target_missing")),
+    };
+    Ok(report?)
+}
+
+// Tauri команды центра уведомлений
+#[tauri::command]
+fn list_notifications(unacknowledged_only: bool) -> CommandResult<Vec<Notification>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let locale = surveillance_system::session_locale();
+    let config_manager = CONFIG_MANAGER.lock()?;
+    let config = config_manager.get_config();
+    let notifications = NOTIFICATIONS.lock()?.list(unacknowledged_only);
+    Ok(notifications
+        .iter()
+        .filter(|notification| notification.visible_to(&user, config))
+        .map(|notification| notification.localized(locale))
+        .collect())
+}
+
+/// Подтверждение уведомлений; пустой список подтверждает все видимые пользователю
+#[tauri::command]
+fn acknowledge_notifications(ids: Vec<u64>) -> CommandResult<Vec<u64>> {
+    let user = require_permission(Permission::ViewLive)?;
+    let config_manager = CONFIG_MANAGER.lock()?;
+    let config = config_manager.get_config();
+    let visible: Vec<u64> = NOTIFICATIONS.lock()?
+        .list(true)
+        .iter()
+        .filter(|notification| ids.is_empty() || ids.contains(&notification.id))
+        .filter(|notification| notification.visible_to(&user, config))
+        .map(|notification| notification.id)
+        .collect();
+    // Пустой список центр понял бы как «подтвердить все»
+    if visible.is_empty() {
+        return Ok(Vec::new());
+    }
+    let acknowledged = NOTIFICATIONS.lock()?.acknowledge(&visible, &user.login);
+    let acknowledged = acknowledged?;
+    log::info!("Пользователь {} подтвердил уведомления {:?}", user.login, acknowledged);
+    Ok(acknowledged)
+}
+
+/// Событие центра уведомлений в пределах доступа текущего пользователя.
+///
+/// Вызывается под блокировкой центра, поэтому сессия и конфигурация берутся
+/// без ожидания; непроверенное событие не отправляется, интерфейс получит
+/// уведомление со списком.
+fn visible_notification_event(event: &NotificationEvent) -> Option<NotificationEvent> {
+    let user = SYSTEM_STATE.try_lock().ok()?.current_user.clone()?;
+    let config_manager = CONFIG_MANAGER.try_lock().ok()?;
+    let config = config_manager.get_config();
+    match event {
+        NotificationEvent::Raised { notification } => {
+            notification.visible_to(&user, config).then(|| event.clone())
+        }
+        NotificationEvent::Pending { notifications } => {
+            let notifications: Vec<Notification> = notifications
+                .iter()
+                .filter(|notification| notification.visible_to(&user, config))
+                .cloned()
+                .collect();
+            (!notifications.is_empty()).then_some(NotificationEvent::Pending { notifications })
+        }
+        NotificationEvent::Acknowledged { .. } => Some(event.clone()),
+    }
+}
+
+/// Блокировка для обработчиков HTTP API, которые возвращают `SurveillanceError`
+fn api_lock<T>(mutex: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>, SurveillanceError> {
+    mutex.lock().map_err(|e| SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", e.to_string())))
+}
+
+/// Маршруты HTTP API; данные те же, что у одноимённых команд интерфейса
+fn api_router() -> ApiRouter {
+    ApiRouter::new(Box::new(|key| {
+        let principal = api_lock(&API_KEYS)?.authenticate(key);
+        if let Err(e) = &principal {
+            log::warn!("Отклонён запрос с API-ключом: {}", e);
+            audit(None, AuditAction::LoginFailure, None, serde_json::json!({ "method": "api_key" }));
+        }
+        principal
+    }))
+    .route("/api/v1/cameras", Permission::ViewLive, Box::new(|_| {
+        Ok(serde_json::to_value(api_lock(&CAMERA_STATUS)?.list())?)
+    }))
+    .route("/api/v1/streams", Permission::ViewLive, Box::new(|_| {
+        Ok(serde_json::to_value(STREAMS.statuses()?)?)
+    }))
+    .route("/api/v1/audit", Permission::ViewAuditLog, Box::new(|request| {
+        let filter = AuditFilter {
+            actor: request.query.get("actor").cloned(),
+            limit: request.query.get("limit").and_then(|limit| limit.parse().ok()),
+            ..AuditFilter::default()
+        };
+        Ok(serde_json::to_value(api_lock(&AUDIT_LOG)?.query(&filter)?)?)
+    }))
+}
+
+// Tauri команды управления API-ключами (сами ключи работают только через HTTP API)
+#[tauri::command]
+fn create_api_key(
+    name: String,
+    scopes: Vec<Permission>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> CommandResult<CreatedApiKey> {
+    let actor = require_permission(Permission::ManageUsers)?;
+
+    // Ключ не может получить прав больше, чем у выпустившего его администратора
+    let granted = SYSTEM_STATE.lock()?.current_permissions();
+    if let Some(missing) = scopes.iter().find(|scope| !granted.contains(scope)) {
+        log::warn!("Администратор {} пытался выдать ключу право {:?}", actor.login, missing);
+        return Err(SurveillanceError::PermissionDenied.into());
+    }
+
+    let created = API_KEYS
+        .lock()?
+        .create(&name, scopes.into_iter().collect(), expires_at, &actor.login)?;
+
+    log::info!("Администратор {} выпустил API-ключ {}", actor.login, created.summary.name);
+    audit(
+        Some(&actor.login),
+        AuditAction::ApiKeyCreated,
+        Some(&created.summary.id),
+        serde_json::json!({
+            "name": created.summary.name,
+            "scopes": created.summary.scopes,
+            "expires_at": created.summary.expires_at,
+        }),
+    );
+    Ok(created)
+}
+
+#[tauri::command]
+fn list_api_keys() -> CommandResult<Vec<ApiKeySummary>> {
+    Ok(API_KEYS.lock()?.list())
+}
+
+#[tauri::command]
+fn revoke_api_key(id: String) -> CommandResult<()> {
+    let actor = require_permission(Permission::ManageUsers)?;
+    API_KEYS.lock()?.revoke(&id)?;
+
+    log::info!("Администратор {} отозвал API-ключ {}", actor.login, id);
+    audit(Some(&actor.login), AuditAction::ApiKeyRevoked, Some(&id), serde_json::Value::Null);
+    Ok(())
+}
+
 // Вспомогательная команда для проверки работы
 #[tauri::command]
-fn greet(name: &str) -> Result<String, String> {
+fn greet(name: &str) -> CommandResult<String> {
     Ok(format!("Привет, {}! Система видеонаблюдения работает.", name))
 }
 
 // Команда для получения статуса системы
 #[tauri::command]
-fn get_system_status() -> Result<SystemStatus, String> {
+fn get_system_status() -> CommandResult<SystemStatus> {
     let is_auth = is_authenticated();
     let user = get_current_user();
-    let config = SYSTEM_STATE.lock().map_err(|e| e.to_string())?.config.clone();
+    let config = SYSTEM_STATE.lock()?.config.clone();
+    // Сводка только по камерам, доступным пользователю
+    let statuses = CAMERA_STATUS.lock()?.list();
+    let cameras = match (&config, &user) {
+        (Some(config), Some(user)) => CameraSummary::from_statuses(
+            statuses.iter().filter(|status| config.camera_for_user(status.camera_id, user).is_ok()),
+        ),
+        _ => CameraSummary::default(),
+    };
     
     Ok(SystemStatus {
         is_authenticated: is_auth,
@@ -195,6 +992,7 @@ fn get_system_status() -> Result<SystemStatus, String> {
         config_loaded: config.is_some(),
         apartments_count: config.as_ref().map(|c| c.apartments.len()).unwrap_or(0),
         cameras_count: config.as_ref().map(|c| c.cameras.len()).unwrap_or(0),
+        cameras,
     })
 }
 
@@ -206,6 +1004,125 @@ struct SystemStatus {
     config_loaded: bool,
     apartments_count: usize,
     cameras_count: usize,
+    cameras: CameraSummary,
+}
+
+/// Регистрация команд вместе с обязательным уровнем доступа.
+///
+/// Команду нельзя зарегистрировать, не указав уровень доступа: макрос
+/// строит и обработчик Tauri, и таблицу доступа из одного списка, а
+/// проверка выполняется до вызова команды.
+macro_rules! secured_commands {
+    ($($command:ident => $access:expr),* $(,)?) => {
+        /// Уровень доступа зарегистрированной команды
+        fn command_access(command: &str) -> Option<AccessLevel> {
+            match command {
+                $(stringify!($command) => Some($access),)*
+                _ => None,
+            }
+        }
+
+        /// Имена всех зарегистрированных команд
+        #[cfg(test)]
+        const REGISTERED_COMMANDS: &[&str] = &[$(stringify!($command)),*];
+
+        /// Обработчик вызовов, проверяющий доступ перед выполнением команды
+        fn secured_invoke_handler() -> impl Fn(tauri::Invoke<tauri::Wry>) + Send + Sync + 'static {
+            let handler = typed_handler(tauri::generate_handler![$($command),*]);
+
+            move |invoke: tauri::Invoke<tauri::Wry>| {
+                let command = invoke.message.command().to_string();
+                let access = command_access(&command)
+                    .ok_or_else(|| SurveillanceError::PermissionDenied)
+                    .and_then(check_access);
+
+                match access {
+                    Ok(()) => handler(invoke),
+                    Err(e) => {
+                        log::warn!("Отказ в доступе к команде {}: {}", command, e);
+                        let actor = get_current_user().map(|user| user.login);
+                        audit(actor.as_deref(), AuditAction::AccessDenied, Some(&command), serde_json::Value::Null);
+                        invoke.resolver.reject(CommandError::from(e));
+                    }
+                }
+            }
+        }
+    };
+}
+
+/// Фиксирует тип обработчика, сгенерированного `tauri::generate_handler!`
+fn typed_handler<F>(handler: F) -> F
+where
+    F: Fn(tauri::Invoke<tauri::Wry>) + Send + Sync + 'static,
+{
+    handler
+}
+
+secured_commands! {
+    // Авторизация
+    login => AccessLevel::Public,
+    verify_two_factor => AccessLevel::Public,
+    start_two_factor_enrollment => AccessLevel::Public,
+    logout => AccessLevel::Public,
+    get_current_user_info => AccessLevel::Public,
+    check_authentication => AccessLevel::Public,
+    check_admin_role => AccessLevel::Public,
+    set_language => AccessLevel::Public,
+    get_current_permissions => AccessLevel::Authenticated,
+    begin_totp_enrollment => AccessLevel::Authenticated,
+    confirm_totp_enrollment => AccessLevel::Authenticated,
+    disable_totp => AccessLevel::Authenticated,
+    change_own_password => AccessLevel::Session,
+    // Управление пользователями
+    list_users => AccessLevel::Permission(Permission::ManageUsers),
+    create_user => AccessLevel::Permission(Permission::ManageUsers),
+    set_user_disabled => AccessLevel::Permission(Permission::ManageUsers),
+    set_user_role => AccessLevel::Permission(Permission::ManageUsers),
+    set_user_apartments => AccessLevel::Permission(Permission::ManageUsers),
+    reset_user_password => AccessLevel::Permission(Permission::ManageUsers),
+    // Конфигурация
+    load_config => AccessLevel::Permission(Permission::ViewLive),
+    get_apartments => AccessLevel::Permission(Permission::ViewLive),
+    get_cameras => AccessLevel::Permission(Permission::ViewLive),
+    get_cameras_by_apartment => AccessLevel::Permission(Permission::ViewLive),
+    get_camera => AccessLevel::Permission(Permission::ViewLive),
+    add_camera => AccessLevel::Permission(Permission::ManageCameras),
+    add_apartment => AccessLevel::Permission(Permission::ManageApartments),
+    // Журнал аудита
+    query_audit_log => AccessLevel::Permission(Permission::ViewAuditLog),
+    export_audit_log => AccessLevel::Permission(Permission::ViewAuditLog),
+    verify_audit_log => AccessLevel::Permission(Permission::ViewAuditLog),
+    // Журнал ошибок
+    query_error_journal => AccessLevel::Permission(Permission::ManageSettings),
+    clear_error_journal => AccessLevel::Permission(Permission::ManageSettings),
+    // Потоки
+    start_stream => AccessLevel::Permission(Permission::ViewLive),
+    stop_stream => AccessLevel::Permission(Permission::ViewLive),
+    set_stream_quality => AccessLevel::Permission(Permission::ViewLive),
+    start_hls_stream => AccessLevel::Permission(Permission::ViewLive),
+    stop_hls_stream => AccessLevel::Permission(Permission::ViewLive),
+    get_stream_status => AccessLevel::Permission(Permission::ViewLive),
+    toggle_audio => AccessLevel::Permission(Permission::ListenAudio),
+    set_audio_volume => AccessLevel::Permission(Permission::ListenAudio),
+    get_audio_state => AccessLevel::Permission(Permission::ViewLive),
+    get_camera_statuses => AccessLevel::Permission(Permission::ViewLive),
+    get_rotation_state => AccessLevel::Permission(Permission::ViewLive),
+    set_rotation_paused => AccessLevel::Permission(Permission::ViewLive),
+    step_rotation => AccessLevel::Permission(Permission::ViewLive),
+    go_to_rotation_page => AccessLevel::Permission(Permission::ViewLive),
+    pin_camera => AccessLevel::Permission(Permission::ViewLive),
+    get_reconnect_status => AccessLevel::Permission(Permission::ViewLive),
+    probe_camera => AccessLevel::Permission(Permission::ManageCameras),
+    // Уведомления
+    list_notifications => AccessLevel::Permission(Permission::ViewLive),
+    acknowledge_notifications => AccessLevel::Permission(Permission::ViewLive),
+    // API-ключи
+    create_api_key => AccessLevel::Permission(Permission::ManageUsers),
+    list_api_keys => AccessLevel::Permission(Permission::ManageUsers),
+    revoke_api_key => AccessLevel::Permission(Permission::ManageUsers),
+    // Вспомогательные
+    greet => AccessLevel::Public,
+    get_system_status => AccessLevel::Authenticated,
 }
 
 fn main() {
@@ -214,36 +1131,249 @@ fn main() {
     
     log::info!("🚀 Запуск системы видеонаблюдения");
     
+    // Журналы аудита и ошибок хранятся в каталоге данных приложения
+    if let Some(data_dir) = dirs::data_dir() {
+        let errors_dir = data_dir.join("surveillance-system").join("errors");
+        match ErrorJournal::open(&errors_dir, Default::default()) {
+            Ok(journal) => {
+                if let Ok(mut current) = ERROR_JOURNAL.lock() {
+                    *current = journal;
+                }
+            }
+            Err(e) => log::error!("Не удалось открыть журнал ошибок: {}", e),
+        }
+
+        let notifications_path = data_dir.join("surveillance-system").join("notifications.json");
+        match NotificationCenter::open(&notifications_path, Default::default()) {
+            Ok(center) => {
+                if let Ok(mut current) = NOTIFICATIONS.lock() {
+                    *current = center;
+                }
+            }
+            Err(e) => report_error(&e, "startup"),
+        }
+
+        let audit_path = data_dir.join("surveillance-system").join("audit.log");
+        match AuditLog::open(&audit_path) {
+            Ok(journal) => {
+                if let Ok(mut current) = AUDIT_LOG.lock() {
+                    *current = journal;
+                }
+            }
+            Err(e) => {
+                log::error!("Не удалось открыть журнал аудита: {}", e);
+                report_error(&e, "startup");
+            }
+        }
+
+        let keys_path = data_dir.join("surveillance-system").join("api_keys.json");
+        match ApiKeyStore::open(&keys_path) {
+            Ok(store) => {
+                if let Ok(mut current) = API_KEYS.lock() {
+                    *current = store;
+                }
+            }
+            Err(e) => {
+                log::error!("Не удалось открыть хранилище API-ключей: {}", e);
+                report_error(&e, "startup");
+            }
+        }
+    }
+    
+    // Ключ шифрования секретов 2FA хранится рядом с настройками пользователя
+    if let Some(config_dir) = dirs::config_dir() {
+        let key_path = config_dir.join("surveillance-system").join("totp.key");
+        match SecretCipher::load_or_create(&key_path) {
+            Ok(cipher) => {
+                if let Ok(mut auth_manager) = AUTH_MANAGER.lock() {
+                    auth_manager.set_secret_cipher(cipher);
+                }
+            }
+            Err(e) => {
+                log::error!("Не удалось загрузить ключ 2FA: {}", e);
+                report_error(&e, "startup");
+            }
+        }
+
+        // Локальная конфигурация нужна до первого входа: в ней задаётся каталог LDAP
+        let config_path = config_dir.join("surveillance-system").join("config.json");
+        if config_path.exists() {
+            let mut local_config = ConfigManager::new();
+            match local_config.load_local(&config_path.to_string_lossy()) {
+                Ok(()) => {
+                    let config = local_config.get_config().clone();
+                    if let Ok(mut config_manager) = CONFIG_MANAGER.lock() {
+                        *config_manager = local_config;
+                    }
+                    if let Err(e) = install_config(&config) {
+                        log::error!("Не удалось применить настройки: {}", e);
+                    }
+                }
+                Err(e) => {
+                    log::error!("Не удалось загрузить локальную конфигурацию: {}", e);
+                    report_error(&e, "startup");
+                }
+            }
+        }
+    }
+    
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![
-            // Авторизация
-            login,
-            logout,
-            get_current_user_info,
-            check_authentication,
-            check_admin_role,
-            // Конфигурация
-            load_config,
-            get_apartments,
-            get_cameras,
-            get_cameras_by_apartment,
-            add_camera,
-            add_apartment,
-            // Вспомогательные
-            greet,
-            get_system_status
-        ])
+        .invoke_handler(secured_invoke_handler())
+        .register_uri_scheme_protocol(stream::hls::HLS_SCHEME, |_app, request| hls_response(request.uri()))
         .setup(|app| {
             log::info!("Tauri приложение инициализировано");
             
-            // Можно добавить инициализацию при запуске
-            let _handle = app.handle();
+            // Уведомления отправляются в интерфейс событием "notification"
+            let handle = app.handle();
+            match NOTIFICATIONS.lock() {
+                Ok(mut center) => center.set_sink(Box::new(move |event| {
+                    let Some(event) = visible_notification_event(event) else {
+                        return;
+                    };
+                    if handle.emit_all("notification", event).is_err() {
+                        log::warn!("Не удалось отправить уведомление в интерфейс");
+                    }
+                })),
+                Err(e) => log::error!("Центр уведомлений недоступен: {}", e),
+            }
+            // Переходы состояний камер — событием "camera-status"
+            let handle = app.handle();
+            match CAMERA_STATUS.lock() {
+                Ok(mut cameras) => cameras.set_sink(Box::new(move |event| {
+                    if handle.emit_all("camera-status", event.clone()).is_err() {
+                        log::warn!("Не удалось отправить состояние камеры в интерфейс");
+                    }
+                })),
+                Err(e) => log::error!("Состояние камер недоступно: {}", e),
+            }
+            // Смена страницы сетки — событием "rotation", чтобы окна показывали одно и то же
+            let handle = app.handle();
+            match ROTATION.lock() {
+                Ok(mut scheduler) => scheduler.set_sink(Box::new(move |state| {
+                    if handle.emit_all("rotation", state.clone()).is_err() {
+                        log::warn!("Не удалось отправить страницу сетки в интерфейс");
+                    }
+                })),
+                Err(e) => log::error!("Ротация сетки недоступна: {}", e),
+            }
+            // Какая камера слышна — событием "audio" во все окна
+            let handle = app.handle();
+            match AUDIO.lock() {
+                Ok(mut audio) => audio.set_sink(Box::new(move |state| {
+                    if handle.emit_all("audio", state.clone()).is_err() {
+                        log::warn!("Не удалось отправить состояние звука в интерфейс");
+                    }
+                })),
+                Err(e) => log::error!("Управление звуком недоступно: {}", e),
+            }
             tauri::async_runtime::spawn(async move {
                 log::info!("Фоновая инициализация завершена");
+                // Задержка кадров и таймер ротации проверяются раз в секунду
+                let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+                loop {
+                    ticker.tick().await;
+                    let unavailable = match CAMERA_STATUS.lock() {
+                        Ok(mut cameras) => {
+                            cameras.refresh();
+                            cameras
+                                .list()
+                                .into_iter()
+                                .filter(|status| status.is_unavailable())
+                                .map(|status| status.camera_id)
+                                .collect()
+                        }
+                        Err(_) => continue,
+                    };
+                    if let Ok(mut scheduler) = ROTATION.lock() {
+                        scheduler.set_unavailable(unavailable);
+                        scheduler.tick();
+                    }
+                    // Накопленные записи журнала ошибок и уведомления сбрасываются на диск одним файлом
+                    if let Ok(mut journal) = ERROR_JOURNAL.lock() {
+                        if let Err(e) = journal.flush() {
+                            log::error!("Не удалось сохранить журнал ошибок: {}", e);
+                        }
+                    }
+                    if let Ok(mut center) = NOTIFICATIONS.lock() {
+                        if let Err(e) = center.flush() {
+                            log::error!("Не удалось сохранить уведомления: {}", e);
+                        }
+                    }
+                }
             });
             
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown();
+            }
+        });
+}
+
+/// Остановка фоновых процессов при выходе, чтобы FFmpeg не пережил приложение,
+/// и сброс накопленных записей журнала ошибок и уведомлений
+fn shutdown() {
+    log::info!("Завершение работы: остановка потоков");
+    if let Err(e) = tauri::async_runtime::block_on(STREAMS.stop_all()) {
+        log::error!("Не удалось остановить потоки при выходе: {}", e);
+    }
+    if let Err(e) = tauri::async_runtime::block_on(HLS.stop_all()) {
+        log::error!("Не удалось остановить публикации HLS при выходе: {}", e);
+    }
+    if let Ok(mut journal) = ERROR_JOURNAL.lock() {
+        if let Err(e) = journal.flush() {
+            log::error!("Не удалось сохранить журнал ошибок при выходе: {}", e);
+        }
+    }
+    if let Ok(mut center) = NOTIFICATIONS.lock() {
+        if let Err(e) = center.flush() {
+            log::error!("Не удалось сохранить уведомления при выходе: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_command_declares_access_level() {
+        // Каждая функция с #[tauri::command] должна быть зарегистрирована с уровнем доступа
+        let source = include_str!("main.rs");
+        let mut lines = source.lines();
+        let mut declared = Vec::new();
+
+        while let Some(line) = lines.next() {
+            if line.trim() != "#[tauri::command]" {
+                continue;
+            }
+            let signature = lines.next().unwrap_or_default();
+            let name = signature
+                .split("fn ")
+                .nth(1)
+                .and_then(|rest| rest.split(['(', '<']).next())
+                .unwrap_or_default();
+            declared.push(name.to_string());
+        }
+
+        assert!(!declared.is_empty());
+        for name in &declared {
+            assert!(
+                REGISTERED_COMMANDS.contains(&name.as_str()) && command_access(name).is_some(),
+                "команда {} зарегистрирована без уровня доступа",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_commands_require_authentication() {
+        for command in ["get_apartments", "get_cameras", "get_cameras_by_apartment", "get_camera", "get_system_status"] {
+            assert_ne!(command_access(command), Some(AccessLevel::Public), "{}", command);
+        }
+        assert_eq!(command_access("unknown_command"), None);
+    }
 }
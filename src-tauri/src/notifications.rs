@@ -0,0 +1,467 @@
+// notifications.rs - Центр уведомлений: ошибки и системные события для оператора
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::auth::User;
+use crate::config::Config;
+use crate::error::{ErrorSeverity, SurveillanceError, Result};
+use crate::i18n::{Detail, Locale};
+
+/// Настройки уведомлений
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub min_severity: ErrorSeverity, // Ошибки ниже этого уровня не показываются
+    pub max_stored: usize,           // Сколько уведомлений хранить
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            min_severity: ErrorSeverity::Warning,
+            max_stored: 200,
+        }
+    }
+}
+
+/// Уведомление для оператора
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: u64,
+    pub severity: ErrorSeverity,
+    pub source: String,             // Подсистема: "command", "stream", "startup", ...
+    pub code: Option<u32>,          // Код ошибки (для системных событий нет)
+    #[serde(default)]
+    pub camera_id: Option<u32>,     // Камера, к которой относится событие
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub count: u64,                 // Сколько раз повторилось до подтверждения
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<SurveillanceError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<Detail>,
+}
+
+impl Notification {
+    pub fn is_acknowledged(&self) -> bool {
+        self.acknowledged_at.is_some()
+    }
+
+    /// Неподтверждённое критическое уведомление не удаляется при очистке
+    pub fn is_pending_critical(&self) -> bool {
+        !self.is_acknowledged() && self.severity == ErrorSeverity::Critical
+    }
+
+    /// Копия с сообщением на нужном языке
+    pub fn localized(&self, locale: Locale) -> Self {
+        let message = match (&self.error, &self.detail) {
+            (Some(error), _) => error.localized_message(locale),
+            (None, Some(detail)) => detail.render(locale),
+            (None, None) => self.message.clone(),
+        };
+        Self { message, ..self.clone() }
+    }
+
+    /// Событие камеры видно пользователю с доступом к её квартире,
+    /// системное — только пользователю без ограничения по квартирам
+    pub fn visible_to(&self, user: &User, config: &Config) -> bool {
+        match self.camera_id {
+            Some(camera_id) => config.camera_for_user(camera_id, user).is_ok(),
+            None => user.allowed_apartments.is_none(),
+        }
+    }
+
+    /// Повторы: то же неподтверждённое событие из того же источника
+    fn same_event(&self, source: &str, code: Option<u32>, camera_id: Option<u32>, message: &str) -> bool {
+        !self.is_acknowledged()
+            && self.source == source
+            && self.code == code
+            && self.camera_id == camera_id
+            && self.message == message
+    }
+}
+
+/// События, отправляемые в интерфейс
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// Новое уведомление или повтор существующего
+    Raised { notification: Notification },
+    /// Уведомления подтверждены
+    Acknowledged { ids: Vec<u64>, by: String },
+    /// Неподтверждённые критические уведомления для вошедшего оператора
+    Pending { notifications: Vec<Notification> },
+}
+
+/// Получатель событий (в приложении — отправка события Tauri)
+pub type NotificationSink = Box<dyn Fn(&NotificationEvent) + Send>;
+
+/// Центр уведомлений с хранением в JSON-файле.
+///
+/// Изменения сбрасываются на диск `flush` из фонового таймера, поэтому
+/// повторы одной ошибки не переписывают файл каждый раз. Критические
+/// уведомления записываются сразу.
+pub struct NotificationCenter {
+    path: Option<PathBuf>,
+    settings: NotificationSettings,
+    notifications: Vec<Notification>,
+    next_id: u64,
+    sink: Option<NotificationSink>,
+    dirty: bool, // Есть изменения, ещё не сброшенные на диск
+}
+
+impl NotificationCenter {
+    /// Центр в памяти (до открытия файла и для тестов)
+    pub fn in_memory(settings: NotificationSettings) -> Self {
+        Self {
+            path: None,
+            settings,
+            notifications: Vec::new(),
+            next_id: 1,
+            sink: None,
+            dirty: false,
+        }
+    }
+
+    /// Открытие файлового хранилища
+    pub fn open(path: &Path, settings: NotificationSettings) -> Result<Self> {
+        let notifications: Vec<Notification> = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let next_id = notifications.iter().map(|n| n.id + 1).max().unwrap_or(1);
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            settings,
+            notifications,
+            next_id,
+            sink: None,
+            dirty: false,
+        })
+    }
+
+    pub fn set_settings(&mut self, settings: NotificationSettings) {
+        self.settings = settings;
+    }
+
+    pub fn set_sink(&mut self, sink: NotificationSink) {
+        self.sink = Some(sink);
+    }
+
+    fn emit(&self, event: NotificationEvent) {
+        if let Some(sink) = &self.sink {
+            sink(&event);
+        }
+    }
+
+    /// Запись накопленных изменений
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(&self.notifications)?)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Уведомление об ошибке; ошибки ниже порога важности пропускаются
+    pub fn notify_error(&mut self, error: &SurveillanceError, source: &str) -> Result<Option<Notification>> {
+        let camera_id = match error {
+            SurveillanceError::CameraUnavailable { camera_id } | SurveillanceError::StreamNotRunning { camera_id } => {
+                Some(*camera_id)
+            }
+            _ => None,
+        };
+        self.notify_camera_error(camera_id, error, source)
+    }
+
+    /// Уведомление об ошибке камеры; видно только пользователям с доступом к ней
+    pub fn notify_camera_error(
+        &mut self,
+        camera_id: Option<u32>,
+        error: &SurveillanceError,
+        source: &str,
+    ) -> Result<Option<Notification>> {
+        let severity = error.severity();
+        if severity < self.settings.min_severity && !error.is_critical() {
+            return Ok(None);
+        }
+        let event = RaisedEvent {
+            severity,
+            code: Some(error.error_code()),
+            camera_id,
+            message: error.to_string(),
+            error: Some(error.clone()),
+            detail: None,
+        };
+        self.raise(source, event).map(Some)
+    }
+
+    /// Уведомление о системном событии; текст переводится по ключу каталога
+    pub fn notify(&mut self, severity: ErrorSeverity, source: &str, detail: Detail) -> Result<Notification> {
+        let event = RaisedEvent {
+            severity,
+            code: None,
+            camera_id: None,
+            message: detail.to_string(),
+            error: None,
+            detail: Some(detail),
+        };
+        self.raise(source, event)
+    }
+
+    fn raise(&mut self, source: &str, event: RaisedEvent) -> Result<Notification> {
+        let RaisedEvent { severity, code, camera_id, message, error, detail } = event;
+        let now = Utc::now();
+        let existing = self.notifications
+            .iter_mut()
+            .find(|notification| notification.same_event(source, code, camera_id, &message));
+
+        let notification = match existing {
+            Some(notification) => {
+                notification.count += 1;
+                notification.last_seen = now;
+                notification.clone()
+            }
+            None => {
+                let notification = Notification {
+                    id: self.next_id,
+                    severity,
+                    source: source.to_string(),
+                    code,
+                    camera_id,
+                    message,
+                    created_at: now,
+                    last_seen: now,
+                    count: 1,
+                    acknowledged_by: None,
+                    acknowledged_at: None,
+                    error,
+                    detail,
+                };
+                self.next_id += 1;
+                self.notifications.push(notification.clone());
+                self.prune();
+                notification
+            }
+        };
+
+        self.dirty = true;
+        if severity == ErrorSeverity::Critical {
+            self.flush()?;
+        }
+        self.emit(NotificationEvent::Raised { notification: notification.clone() });
+        Ok(notification)
+    }
+
+    /// Удаление лишних уведомлений: сначала подтверждённые, затем старые
+    /// некритические; неподтверждённые критические сохраняются всегда
+    fn prune(&mut self) {
+        let excess = self.notifications.len().saturating_sub(self.settings.max_stored);
+        if excess == 0 {
+            return;
+        }
+
+        let mut victims: Vec<u64> = self.notifications
+            .iter()
+            .filter(|notification| notification.is_acknowledged())
+            .chain(self.notifications.iter().filter(|notification| {
+                !notification.is_acknowledged() && !notification.is_pending_critical()
+            }))
+            .map(|notification| notification.id)
+            .take(excess)
+            .collect();
+        victims.sort_unstable();
+
+        self.notifications.retain(|notification| victims.binary_search(&notification.id).is_err());
+    }
+
+    /// Подтверждение уведомлений; пустой список подтверждает все
+    pub fn acknowledge(&mut self, ids: &[u64], user: &str) -> Result<Vec<u64>> {
+        let now = Utc::now();
+        let mut acknowledged = Vec::new();
+        for notification in self.notifications.iter_mut() {
+            if notification.is_acknowledged() || !(ids.is_empty() || ids.contains(&notification.id)) {
+                continue;
+            }
+            notification.acknowledged_by = Some(user.to_string());
+            notification.acknowledged_at = Some(now);
+            acknowledged.push(notification.id);
+        }
+
+        if !acknowledged.is_empty() {
+            self.dirty = true;
+            self.emit(NotificationEvent::Acknowledged { ids: acknowledged.clone(), by: user.to_string() });
+        }
+        Ok(acknowledged)
+    }
+
+    /// Уведомления от новых к старым
+    pub fn list(&self, unacknowledged_only: bool) -> Vec<Notification> {
+        self.notifications
+            .iter()
+            .rev()
+            .filter(|notification| !unacknowledged_only || !notification.is_acknowledged())
+            .cloned()
+            .collect()
+    }
+
+    /// Неподтверждённые критические уведомления
+    pub fn pending_critical(&self) -> Vec<Notification> {
+        self.notifications
+            .iter()
+            .filter(|notification| notification.is_pending_critical())
+            .cloned()
+            .collect()
+    }
+
+    /// Повторная отправка неподтверждённых критических уведомлений (после входа)
+    pub fn replay_pending(&self, locale: Locale) {
+        let notifications: Vec<Notification> = self
+            .pending_critical()
+            .iter()
+            .map(|notification| notification.localized(locale))
+            .collect();
+        if !notifications.is_empty() {
+            self.emit(NotificationEvent::Pending { notifications });
+        }
+    }
+}
+
+/// Новое событие до проверки на повтор
+struct RaisedEvent {
+    severity: ErrorSeverity,
+    code: Option<u32>,
+    camera_id: Option<u32>,
+    message: String,
+    error: Option<SurveillanceError>,
+    detail: Option<Detail>,
+}
+
+/// Центр уведомлений приложения (файловое хранилище открывается при запуске)
+pub static NOTIFICATIONS: Lazy<Mutex<NotificationCenter>> = Lazy::new(|| {
+    Mutex::new(NotificationCenter::in_memory(NotificationSettings::default()))
+});
+
+/// Уведомление об ошибке фоновой задачи; сбой центра только логируется
+pub fn report(error: &SurveillanceError, source: &str) {
+    match NOTIFICATIONS.lock() {
+        Ok(mut center) => {
+            if let Err(e) = center.notify_error(error, source) {
+                log::error!("Не удалось сохранить уведомление: {}", e);
+            }
+        }
+        Err(e) => log::error!("Центр уведомлений недоступен: {}", e),
+    }
+}
+
+/// Уведомление об ошибке потока конкретной камеры
+pub fn report_camera(camera_id: u32, error: &SurveillanceError, source: &str) {
+    match NOTIFICATIONS.lock() {
+        Ok(mut center) => {
+            if let Err(e) = center.notify_camera_error(Some(camera_id), error, source) {
+                log::error!("Не удалось сохранить уведомление: {}", e);
+            }
+        }
+        Err(e) => log::error!("Центр уведомлений недоступен: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_deduplication_and_threshold() {
+        let mut center = NotificationCenter::in_memory(NotificationSettings::default());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = events.clone();
+        center.set_sink(Box::new(move |event| received.lock().unwrap().push(event.clone())));
+
+        // Info ниже порога по умолчанию
+        assert!(center.notify_error(&SurveillanceError::CameraUnavailable { camera_id: 3 }, "stream").unwrap().is_none());
+
+        let timeout = SurveillanceError::ConnectionTimeout;
+        center.notify_error(&timeout, "stream").unwrap();
+        let repeated = center.notify_error(&timeout, "stream").unwrap().unwrap();
+        assert_eq!(repeated.count, 2);
+        assert_eq!(center.list(false).len(), 1);
+        assert_eq!(events.lock().unwrap().len(), 2);
+
+        // После подтверждения та же ошибка создаёт новое уведомление
+        assert_eq!(center.acknowledge(&[repeated.id], "operator").unwrap(), vec![repeated.id]);
+        center.notify_error(&timeout, "stream").unwrap();
+        assert_eq!(center.list(false).len(), 2);
+        assert_eq!(center.list(true).len(), 1);
+        assert!(matches!(events.lock().unwrap()[2], NotificationEvent::Acknowledged { .. }));
+    }
+
+    #[test]
+    fn test_pending_critical_survive_restart_and_pruning() {
+        let path = std::env::temp_dir().join(format!("notifications-{}.json", uuid::Uuid::new_v4()));
+        let settings = NotificationSettings { min_severity: ErrorSeverity::Info, max_stored: 3 };
+        let mut center = NotificationCenter::open(&path, settings.clone()).unwrap();
+
+        let disk_full = SurveillanceError::filesystem_error(Detail::new("io_failed").with("error", "No space left on device"));
+        center.notify_error(&disk_full, "recorder").unwrap();
+        for camera_id in 0..5 {
+            center.notify_error(&SurveillanceError::CameraUnavailable { camera_id }, "stream").unwrap();
+        }
+        assert_eq!(center.list(false).len(), 3);
+
+        // Некритические уведомления попадают на диск при сбросе, критическое записано сразу
+        assert_eq!(NotificationCenter::open(&path, settings.clone()).unwrap().list(false).len(), 1);
+        center.flush().unwrap();
+
+        // Оператор, вошедший позже, видит критическое уведомление
+        let reopened = NotificationCenter::open(&path, settings).unwrap();
+        assert_eq!(reopened.list(false).len(), 3);
+        let pending = reopened.pending_critical();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].code, Some(SurveillanceError::filesystem_error("io_failed").error_code()));
+        assert!(pending[0].localized(Locale::En).message.starts_with("File system error"));
+
+        // Системное событие переводится по ключу каталога
+        let mut center = NotificationCenter::in_memory(NotificationSettings::default());
+        let event = center.notify(ErrorSeverity::Critical, "audit", Detail::new("audit_integrity_failed")).unwrap();
+        assert_eq!(event.message, "Нарушена целостность журнала аудита");
+        assert_eq!(event.localized(Locale::En).message, "Audit log integrity check failed");
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_visibility_follows_camera_access() {
+        let config = Config::new_test();
+        let admin = User::new("admin", String::new(), crate::auth::UserRole::Admin);
+        let mut operator = User::new("operator1", String::new(), crate::auth::UserRole::Operator);
+        operator.allowed_apartments = Some(vec!["Квартира на Ленина".to_string()]);
+
+        let mut center = NotificationCenter::in_memory(NotificationSettings::default());
+        let timeout = SurveillanceError::ConnectionTimeout;
+        let own = center.notify_camera_error(Some(4), &timeout, "stream").unwrap().unwrap();
+        let foreign = center.notify_camera_error(Some(1), &timeout, "stream").unwrap().unwrap();
+        let system = center.notify(ErrorSeverity::Critical, "audit", Detail::new("audit_integrity_failed")).unwrap();
+
+        // Одна ошибка разных камер — разные уведомления
+        assert_ne!(own.id, foreign.id);
+        assert!(own.visible_to(&operator, &config));
+        assert!(!foreign.visible_to(&operator, &config));
+        assert!(!system.visible_to(&operator, &config));
+        assert!([own, foreign, system].iter().all(|notification| notification.visible_to(&admin, &config)));
+    }
+}
@@ -0,0 +1,520 @@
+// password.rs - Хеширование паролей (Argon2id / bcrypt)
+
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher as _, PasswordVerifier as _, Version};
+use serde::{Deserialize, Serialize};
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::{Detail, Locale};
+
+/// Поддерживаемые алгоритмы хеширования
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+/// Политика хеширования паролей (параметры текущего алгоритма)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PasswordHashPolicy {
+    pub algorithm: HashAlgorithm,
+    pub argon2_memory_kib: u32,   // Объём памяти Argon2 в КиБ
+    pub argon2_iterations: u32,   // Количество проходов Argon2
+    pub argon2_parallelism: u32,  // Степень параллелизма Argon2
+    pub bcrypt_cost: u32,         // Стоимость bcrypt (4..=31)
+}
+
+impl Default for PasswordHashPolicy {
+    fn default() -> Self {
+        // Рекомендации OWASP для Argon2id: 19 МиБ, 2 прохода, 1 поток
+        Self {
+            algorithm: HashAlgorithm::Argon2id,
+            argon2_memory_kib: 19 * 1024,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+            bcrypt_cost: bcrypt::DEFAULT_COST,
+        }
+    }
+}
+
+impl PasswordHashPolicy {
+    /// Создание хешера, соответствующего политике
+    pub fn hasher(&self) -> Result<Box<dyn PasswordHasher>> {
+        match self.algorithm {
+            HashAlgorithm::Argon2id => Ok(Box::new(Argon2idHasher::new(
+                self.argon2_memory_kib,
+                self.argon2_iterations,
+                self.argon2_parallelism,
+            )?)),
+            HashAlgorithm::Bcrypt => Ok(Box::new(BcryptHasher::new(self.bcrypt_cost)?)),
+        }
+    }
+}
+
+/// Абстракция алгоритма хеширования паролей.
+///
+/// Хеши хранятся в самоописывающем формате (PHC-строка для Argon2id,
+/// `$2b$` для bcrypt), поэтому по сохранённому значению всегда можно
+/// определить алгоритм и параметры.
+pub trait PasswordHasher: Send + Sync {
+    /// Алгоритм, которым хеширует данный хешер
+    fn algorithm(&self) -> HashAlgorithm;
+
+    /// Хеширование пароля со случайной солью
+    fn hash(&self, password: &str) -> Result<String>;
+
+    /// Проверка пароля по сохранённому хешу этого алгоритма
+    fn verify(&self, password: &str, stored_hash: &str) -> Result<bool>;
+
+    /// Слабее ли параметры сохранённого хеша текущих параметров хешера
+    fn is_weaker(&self, stored_hash: &str) -> bool;
+}
+
+/// Хешер Argon2id
+pub struct Argon2idHasher {
+    params: Params,
+}
+
+impl Argon2idHasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| SurveillanceError::config_error(Detail::new("argon2_params_invalid").with("error", e.to_string())))?;
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl PasswordHasher for Argon2idHasher {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Argon2id
+    }
+
+    fn hash(&self, password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| SurveillanceError::internal_error(Detail::new("password_hash_failed").with("error", e.to_string())))
+    }
+
+    fn verify(&self, password: &str, stored_hash: &str) -> Result<bool> {
+        let parsed = PasswordHash::new(stored_hash)
+            .map_err(|e| SurveillanceError::auth_error(Detail::new("password_hash_invalid").with("error", e.to_string())))?;
+
+        // Параметры берутся из самого хеша, поэтому старые хеши тоже проверяются
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(SurveillanceError::auth_error(Detail::new("password_check_failed_with").with("error", e.to_string()))),
+        }
+    }
+
+    fn is_weaker(&self, stored_hash: &str) -> bool {
+        let stored = match PasswordHash::new(stored_hash).and_then(|hash| Params::try_from(&hash)) {
+            Ok(params) => params,
+            Err(_) => return true,
+        };
+
+        stored.m_cost() < self.params.m_cost()
+            || stored.t_cost() < self.params.t_cost()
+            || stored.p_cost() < self.params.p_cost()
+    }
+}
+
+/// Хешер bcrypt
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+impl BcryptHasher {
+    pub fn new(cost: u32) -> Result<Self> {
+        if !(4..=31).contains(&cost) {
+            return Err(SurveillanceError::config_error("bcrypt_cost_invalid"));
+        }
+        Ok(Self { cost })
+    }
+
+    /// Извлечение стоимости из строки вида `$2b$12$...`
+    fn stored_cost(stored_hash: &str) -> Option<u32> {
+        stored_hash.split('$').nth(2)?.parse().ok()
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Bcrypt
+    }
+
+    fn hash(&self, password: &str) -> Result<String> {
+        bcrypt::hash(password, self.cost)
+            .map_err(|e| SurveillanceError::internal_error(Detail::new("password_hash_failed").with("error", e.to_string())))
+    }
+
+    fn verify(&self, password: &str, stored_hash: &str) -> Result<bool> {
+        bcrypt::verify(password, stored_hash)
+            .map_err(|e| SurveillanceError::auth_error(Detail::new("password_check_failed_with").with("error", e.to_string())))
+    }
+
+    fn is_weaker(&self, stored_hash: &str) -> bool {
+        Self::stored_cost(stored_hash)
+            .map(|cost| cost < self.cost)
+            .unwrap_or(true)
+    }
+}
+
+/// Определение алгоритма по сохранённому хешу
+pub fn detect_algorithm(stored_hash: &str) -> Option<HashAlgorithm> {
+    if stored_hash.starts_with("$argon2id$") {
+        Some(HashAlgorithm::Argon2id)
+    } else if ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| stored_hash.starts_with(prefix)) {
+        Some(HashAlgorithm::Bcrypt)
+    } else {
+        None
+    }
+}
+
+/// Сервис паролей: хеширует по текущей политике и проверяет любые
+/// поддерживаемые хеши, сообщая о необходимости перехеширования
+pub struct PasswordService {
+    policy: PasswordHashPolicy,
+    current: Box<dyn PasswordHasher>,
+}
+
+impl PasswordService {
+    pub fn new(policy: PasswordHashPolicy) -> Result<Self> {
+        let current = policy.hasher()?;
+        Ok(Self { policy, current })
+    }
+
+    pub fn policy(&self) -> &PasswordHashPolicy {
+        &self.policy
+    }
+
+    /// Хеширование пароля по текущей политике
+    pub fn hash(&self, password: &str) -> Result<String> {
+        self.current.hash(password)
+    }
+
+    /// Проверка пароля по хешу любого поддерживаемого алгоритма
+    pub fn verify(&self, password: &str, stored_hash: &str) -> Result<bool> {
+        match detect_algorithm(stored_hash) {
+            Some(algorithm) if algorithm == self.current.algorithm() => self.current.verify(password, stored_hash),
+            // Для проверки чужого алгоритма параметры берутся из самого хеша
+            Some(HashAlgorithm::Argon2id) => {
+                let defaults = PasswordHashPolicy::default();
+                Argon2idHasher::new(defaults.argon2_memory_kib, defaults.argon2_iterations, defaults.argon2_parallelism)?
+                    .verify(password, stored_hash)
+            }
+            Some(HashAlgorithm::Bcrypt) => BcryptHasher::new(bcrypt::DEFAULT_COST)?.verify(password, stored_hash),
+            None => Err(SurveillanceError::auth_error("password_hash_unknown")),
+        }
+    }
+
+    /// Нужно ли перехешировать пароль по текущей политике
+    pub fn needs_rehash(&self, stored_hash: &str) -> bool {
+        match detect_algorithm(stored_hash) {
+            Some(algorithm) if algorithm == self.current.algorithm() => self.current.is_weaker(stored_hash),
+            _ => true,
+        }
+    }
+}
+
+/// Наиболее распространённые пароли, запрещённые всегда
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "12345678", "123456789", "1234567890", "password", "password1",
+    "password123", "qwerty", "qwerty123", "qwertyuiop", "111111", "000000",
+    "abc123", "admin", "admin123", "administrator", "operator", "operator123",
+    "letmein", "welcome", "iloveyou", "monkey", "dragon", "master", "123123",
+    "1q2w3e4r", "1qaz2wsx", "zaq12wsx", "passw0rd", "P@ssw0rd", "changeme",
+    "йцукен", "пароль", "qwerty12345",
+];
+
+/// Нарушение политики паролей
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PasswordViolation {
+    TooShort { min_length: usize },
+    TooLong { max_length: usize },
+    MissingUppercase,
+    MissingLowercase,
+    MissingDigit,
+    MissingSpecial,
+    CommonPassword,
+    ContainsLogin,
+    ReusedPassword { history_size: usize },
+}
+
+impl PasswordViolation {
+    /// Текст нарушения на выбранном языке
+    pub fn localized(&self, locale: Locale) -> String {
+        match (self, locale) {
+            (Self::TooShort { min_length }, Locale::Ru) => format!("длина меньше {} символов", min_length),
+            (Self::TooShort { min_length }, Locale::En) => format!("shorter than {} characters", min_length),
+            (Self::TooLong { max_length }, Locale::Ru) => format!("длина больше {} символов", max_length),
+            (Self::TooLong { max_length }, Locale::En) => format!("longer than {} characters", max_length),
+            (Self::MissingUppercase, Locale::Ru) => "нет заглавной буквы".to_string(),
+            (Self::MissingUppercase, Locale::En) => "no uppercase letter".to_string(),
+            (Self::MissingLowercase, Locale::Ru) => "нет строчной буквы".to_string(),
+            (Self::MissingLowercase, Locale::En) => "no lowercase letter".to_string(),
+            (Self::MissingDigit, Locale::Ru) => "нет цифры".to_string(),
+            (Self::MissingDigit, Locale::En) => "no digit".to_string(),
+            (Self::MissingSpecial, Locale::Ru) => "нет специального символа".to_string(),
+            (Self::MissingSpecial, Locale::En) => "no special character".to_string(),
+            (Self::CommonPassword, Locale::Ru) => "пароль слишком распространён".to_string(),
+            (Self::CommonPassword, Locale::En) => "password is too common".to_string(),
+            (Self::ContainsLogin, Locale::Ru) => "пароль содержит логин".to_string(),
+            (Self::ContainsLogin, Locale::En) => "password contains the login".to_string(),
+            (Self::ReusedPassword { history_size }, Locale::Ru) => {
+                format!("пароль совпадает с одним из последних {}", history_size)
+            }
+            (Self::ReusedPassword { history_size }, Locale::En) => {
+                format!("password matches one of the last {}", history_size)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PasswordViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.localized(Locale::Ru))
+    }
+}
+
+/// Политика сложности паролей
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    pub deny_common_passwords: bool,  // Запрет распространённых паролей
+    pub deny_login: bool,             // Запрет пароля, содержащего логин
+    pub denied_passwords: Vec<String>, // Дополнительный список запрещённых паролей
+    pub history_size: usize,          // Сколько последних паролей нельзя повторять
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 10,
+            max_length: 128,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_special: false,
+            deny_common_passwords: true,
+            deny_login: true,
+            denied_passwords: Vec::new(),
+            history_size: 5,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Проверка пароля без учёта истории.
+    ///
+    /// Возвращает все найденные нарушения, а не только первое,
+    /// чтобы интерфейс мог показать их списком.
+    pub fn violations(&self, login: &str, password: &str) -> Vec<PasswordViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            violations.push(PasswordViolation::TooShort { min_length: self.min_length });
+        }
+        if length > self.max_length {
+            violations.push(PasswordViolation::TooLong { max_length: self.max_length });
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordViolation::MissingUppercase);
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordViolation::MissingLowercase);
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PasswordViolation::MissingDigit);
+        }
+        if self.require_special && password.chars().all(char::is_alphanumeric) {
+            violations.push(PasswordViolation::MissingSpecial);
+        }
+
+        let lowered = password.to_lowercase();
+        let is_common = self.deny_common_passwords
+            && COMMON_PASSWORDS.iter().any(|denied| denied.to_lowercase() == lowered);
+        let is_denied = self.denied_passwords.iter().any(|denied| denied.to_lowercase() == lowered);
+        if is_common || is_denied {
+            violations.push(PasswordViolation::CommonPassword);
+        }
+
+        if self.deny_login && !login.is_empty() && lowered.contains(&login.to_lowercase()) {
+            violations.push(PasswordViolation::ContainsLogin);
+        }
+
+        violations
+    }
+
+    /// Генерация временного пароля, удовлетворяющего политике
+    pub fn generate_temporary(&self, login: &str) -> String {
+        use rand::seq::SliceRandom;
+
+        const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+        const LOWER: &[u8] = b"abcdefghijkmnpqrstuvwxyz";
+        const DIGITS: &[u8] = b"23456789";
+        const SPECIAL: &[u8] = b"!@#$%^&*-_=+";
+
+        let mut rng = rand::thread_rng();
+        let length = self.min_length.max(16).min(self.max_length.max(4));
+        let all: Vec<u8> = [UPPER, LOWER, DIGITS, SPECIAL].concat();
+
+        let mut candidate = String::new();
+        for _ in 0..100 {
+            // По одному символу каждого класса, остальное — из общего набора
+            let mut chars: Vec<u8> = [UPPER, LOWER, DIGITS, SPECIAL]
+                .iter()
+                .filter_map(|class| class.choose(&mut rng).copied())
+                .collect();
+            while chars.len() < length {
+                chars.extend(all.choose(&mut rng));
+            }
+            chars.shuffle(&mut rng);
+
+            candidate = String::from_utf8(chars).unwrap_or_default();
+            if self.violations(login, &candidate).is_empty() {
+                break;
+            }
+        }
+        candidate
+    }
+
+    /// Полная проверка пароля, включая повтор.
+    ///
+    /// `previous_hashes` — текущий хеш и за ним история: запрещены текущий
+    /// пароль и `history_size` предыдущих.
+    pub fn check(
+        &self,
+        login: &str,
+        password: &str,
+        previous_hashes: &[String],
+        service: &PasswordService,
+    ) -> Result<()> {
+        let mut violations = self.violations(login, password);
+
+        let reused = previous_hashes
+            .iter()
+            .take(self.history_size + 1)
+            .any(|stored| service.verify(password, stored).unwrap_or(false));
+        if reused {
+            violations.push(PasswordViolation::ReusedPassword { history_size: self.history_size });
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(SurveillanceError::PasswordPolicy { violations })
+        }
+    }
+}
+
+impl Default for PasswordService {
+    fn default() -> Self {
+        Self::new(PasswordHashPolicy::default()).expect("параметры хеширования по умолчанию корректны")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_argon2() -> PasswordHashPolicy {
+        PasswordHashPolicy {
+            argon2_memory_kib: 1024,
+            argon2_iterations: 1,
+            ..PasswordHashPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_argon2id_phc_roundtrip() {
+        let service = PasswordService::new(fast_argon2()).unwrap();
+        let hash = service.hash("secret").unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(service.verify("secret", &hash).unwrap());
+        assert!(!service.verify("wrong", &hash).unwrap());
+        assert!(!service.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_rehash_on_weaker_parameters() {
+        let bcrypt_hash = bcrypt::hash("secret", 4).unwrap();
+
+        // bcrypt-хеш проверяется при политике Argon2id и требует перехеширования
+        let argon = PasswordService::new(fast_argon2()).unwrap();
+        assert!(argon.verify("secret", &bcrypt_hash).unwrap());
+        assert!(argon.needs_rehash(&bcrypt_hash));
+
+        // Более слабая стоимость bcrypt тоже требует перехеширования
+        let stronger_bcrypt = PasswordService::new(PasswordHashPolicy {
+            algorithm: HashAlgorithm::Bcrypt,
+            bcrypt_cost: 5,
+            ..PasswordHashPolicy::default()
+        }).unwrap();
+        assert!(stronger_bcrypt.needs_rehash(&bcrypt_hash));
+
+        // Увеличение памяти Argon2 делает старые хеши слабыми
+        let weak_hash = argon.hash("secret").unwrap();
+        let stronger_argon = PasswordService::new(PasswordHashPolicy {
+            argon2_memory_kib: 2048,
+            ..fast_argon2()
+        }).unwrap();
+        assert!(stronger_argon.needs_rehash(&weak_hash));
+        assert!(stronger_argon.verify("secret", &weak_hash).unwrap());
+    }
+
+    #[test]
+    fn test_password_policy_violations() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.violations("ivan", "Kx7pLm29qZ").is_empty());
+
+        let violations = policy.violations("ivan", "");
+        assert!(violations.contains(&PasswordViolation::TooShort { min_length: 10 }));
+        assert!(violations.contains(&PasswordViolation::MissingDigit));
+
+        assert!(policy.violations("ivan", "Password123").contains(&PasswordViolation::CommonPassword));
+        assert!(policy.violations("ivan", "Ivan2024Secure").contains(&PasswordViolation::ContainsLogin));
+    }
+
+    #[test]
+    fn test_password_policy_history() {
+        let service = PasswordService::new(fast_argon2()).unwrap();
+        let policy = PasswordPolicy { history_size: 2, ..PasswordPolicy::default() };
+        // Текущий пароль и два предыдущих
+        let history = vec![
+            service.hash("Kx7pLm29qZ").unwrap(),
+            service.hash("Older1Pass").unwrap(),
+            service.hash("Oldest1Pass").unwrap(),
+            service.hash("Ancient1Pass").unwrap(),
+        ];
+
+        // Текущий пароль и ровно `history_size` предыдущих запрещены
+        for reused in ["Kx7pLm29qZ", "Older1Pass", "Oldest1Pass"] {
+            match policy.check("ivan", reused, &history, &service) {
+                Err(SurveillanceError::PasswordPolicy { violations }) => {
+                    assert_eq!(violations, vec![PasswordViolation::ReusedPassword { history_size: 2 }]);
+                }
+                other => panic!("ожидалось нарушение политики для {}, получено {:?}", reused, other),
+            }
+        }
+
+        // Пароль старше глубины истории снова разрешён
+        assert!(policy.check("ivan", "Ancient1Pass", &history, &service).is_ok());
+    }
+}
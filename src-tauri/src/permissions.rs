@@ -0,0 +1,174 @@
+// permissions.rs - Модель прав доступа
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use crate::auth::UserRole;
+
+/// Отдельное право доступа
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    ViewLive,          // Просмотр живого видео
+    ListenAudio,       // Прослушивание звука
+    ControlPtz,        // Управление поворотными камерами
+    ExportVideo,       // Экспорт видео
+    ManageCameras,     // Добавление и изменение камер
+    ManageApartments,  // Добавление и изменение квартир
+    ManageSettings,    // Изменение системных настроек
+    ManageUsers,       // Управление пользователями
+    ViewAuditLog,      // Просмотр журнала аудита
+}
+
+impl Permission {
+    /// Полный список прав
+    pub const ALL: [Permission; 9] = [
+        Permission::ViewLive,
+        Permission::ListenAudio,
+        Permission::ControlPtz,
+        Permission::ExportVideo,
+        Permission::ManageCameras,
+        Permission::ManageApartments,
+        Permission::ManageSettings,
+        Permission::ManageUsers,
+        Permission::ViewAuditLog,
+    ];
+}
+
+/// Уровень доступа, который обязана объявить каждая Tauri команда
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessLevel {
+    /// Доступна без входа в систему
+    Public,
+    /// Требуется вход; доступна и до обязательной смены пароля
+    Session,
+    /// Требуется вход в систему
+    Authenticated,
+    /// Требуется конкретное право
+    Permission(Permission),
+}
+
+/// Именованный набор прав (роль), задаётся в конфигурации
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoleDefinition {
+    pub name: String,
+    pub permissions: BTreeSet<Permission>,
+}
+
+impl RoleDefinition {
+    pub fn new(name: &str, permissions: &[Permission]) -> Self {
+        Self {
+            name: name.to_string(),
+            permissions: permissions.iter().copied().collect(),
+        }
+    }
+
+    /// Роли по умолчанию, соответствующие прежнему поведению Admin/Operator
+    pub fn defaults() -> Vec<RoleDefinition> {
+        vec![
+            RoleDefinition::new(UserRole::Admin.name(), &Permission::ALL),
+            RoleDefinition::new(
+                UserRole::Operator.name(),
+                &[Permission::ViewLive, Permission::ListenAudio],
+            ),
+        ]
+    }
+}
+
+/// Реестр ролей: сопоставляет роль пользователя с набором прав
+#[derive(Debug, Clone)]
+pub struct RoleRegistry {
+    roles: HashMap<String, BTreeSet<Permission>>,
+}
+
+impl RoleRegistry {
+    /// Построение реестра: роли из конфигурации переопределяют роли по умолчанию.
+    ///
+    /// Права Admin не переопределяются: иначе конфигурация могла бы лишить
+    /// всех администраторов управления пользователями и настройками.
+    pub fn new(definitions: &[RoleDefinition]) -> Self {
+        let admin = UserRole::Admin.name();
+        let roles = RoleDefinition::defaults()
+            .into_iter()
+            .chain(definitions.iter().filter(|role| {
+                let redefines_admin = role.name.eq_ignore_ascii_case(admin)
+                    && role.permissions != Permission::ALL.into_iter().collect();
+                if redefines_admin {
+                    log::warn!("Права роли {} из конфигурации проигнорированы", role.name);
+                }
+                !role.name.eq_ignore_ascii_case(admin)
+            }).cloned())
+            .map(|role| (role.name, role.permissions))
+            .collect();
+
+        Self { roles }
+    }
+
+    /// Права, назначенные роли (неизвестная роль не имеет прав)
+    pub fn permissions_for(&self, role: &UserRole) -> BTreeSet<Permission> {
+        match role {
+            UserRole::Admin => Permission::ALL.into_iter().collect(),
+            role if role.is_reserved_custom() => BTreeSet::new(),
+            role => self.roles.get(role.name()).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Проверка наличия права у роли
+    pub fn has_permission(&self, role: &UserRole, permission: Permission) -> bool {
+        self.permissions_for(role).contains(&permission)
+    }
+}
+
+impl Default for RoleRegistry {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_roles() {
+        let registry = RoleRegistry::default();
+
+        assert!(registry.has_permission(&UserRole::Admin, Permission::ManageUsers));
+        assert!(registry.has_permission(&UserRole::Operator, Permission::ViewLive));
+        assert!(!registry.has_permission(&UserRole::Operator, Permission::ManageCameras));
+    }
+
+    #[test]
+    fn test_roles_from_config() {
+        let registry = RoleRegistry::new(&[
+            RoleDefinition::new("Operator", &[Permission::ViewLive, Permission::ControlPtz]),
+            RoleDefinition::new("auditor", &[Permission::ExportVideo]),
+        ]);
+
+        assert!(registry.has_permission(&UserRole::Operator, Permission::ControlPtz));
+        assert!(!registry.has_permission(&UserRole::Operator, Permission::ListenAudio));
+
+        let auditor = UserRole::Custom("auditor".to_string());
+        assert!(registry.has_permission(&auditor, Permission::ExportVideo));
+        assert!(registry.permissions_for(&UserRole::Custom("unknown".to_string())).is_empty());
+    }
+
+    #[test]
+    fn test_builtin_roles_protected() {
+        let registry = RoleRegistry::new(&[
+            RoleDefinition::new("Admin", &[Permission::ViewLive]),
+            RoleDefinition::new("admin", &[Permission::ViewLive]),
+        ]);
+
+        // Администратор не теряет прав из-за конфигурации
+        assert_eq!(registry.permissions_for(&UserRole::Admin).len(), Permission::ALL.len());
+        assert!(registry.has_permission(&UserRole::Admin, Permission::ManageUsers));
+
+        // Пользовательская роль с именем встроенной не наследует её права
+        for name in ["Admin", "admin", "Operator"] {
+            let role = UserRole::Custom(name.to_string());
+            assert!(role.is_reserved_custom());
+            assert!(registry.permissions_for(&role).is_empty());
+        }
+        assert!(!UserRole::Custom("auditor".to_string()).is_reserved_custom());
+    }
+}
@@ -0,0 +1,310 @@
+// reconnect.rs - Политика переподключения: экспоненциальная задержка, предохранитель и медленные повторы
+
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::config::Settings;
+use crate::error::{CommandError, SurveillanceError};
+
+/// Настройки переподключения.
+///
+/// Предельная задержка и число попыток берутся из `Settings::retry_interval`
+/// и `Settings::max_retry_attempts`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ReconnectSettings {
+    pub initial_delay_secs: u32,        // Первая задержка после сбоя
+    pub multiplier: f64,                // Рост задержки с каждой попыткой
+    pub jitter: f64,                    // Случайное отклонение задержки (доля, 0.2 = ±20%)
+    pub slow_retry_interval_secs: u32,  // Интервал проверки камеры после исчерпания попыток
+}
+
+impl Default for ReconnectSettings {
+    fn default() -> Self {
+        Self {
+            initial_delay_secs: 1,
+            multiplier: 2.0,
+            jitter: 0.2,
+            slow_retry_interval_secs: 300,
+        }
+    }
+}
+
+/// Источник времени; в тестах заменяется на `ManualClock`
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Системное время
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Время, которое идёт только по команде
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self { now: Mutex::new(start) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero());
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.lock().map(|now| *now).unwrap_or_else(|e| *e.into_inner())
+    }
+}
+
+/// Параметры задержек
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+    /// Сбоев подряд до размыкания предохранителя
+    pub max_attempts: u32,
+    pub slow_retry_interval: Duration,
+}
+
+impl ReconnectPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let reconnect = &settings.reconnect;
+        let max_delay = Duration::from_secs(settings.retry_interval.max(1).into());
+        Self {
+            initial_delay: Duration::from_secs(reconnect.initial_delay_secs.into()).min(max_delay),
+            max_delay,
+            multiplier: reconnect.multiplier.max(1.0),
+            jitter: reconnect.jitter.clamp(0.0, 1.0),
+            max_attempts: settings.max_retry_attempts.max(1),
+            slow_retry_interval: Duration::from_secs(reconnect.slow_retry_interval_secs.max(1).into()),
+        }
+    }
+
+    /// Задержка без отклонения после `failures` сбоев подряд
+    pub fn base_delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(64) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+}
+
+/// Состояние предохранителя
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Обычные повторы с растущей задержкой
+    Closed,
+    /// Попытки исчерпаны, следующая — через медленный интервал
+    Open,
+    /// Пробная попытка после медленного интервала
+    HalfOpen,
+}
+
+/// Состояние переподключения для интерфейса
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReconnectStatus {
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// Последняя ошибка с кодом и параметрами каталога
+    pub last_error: Option<CommandError>,
+}
+
+/// Переподключение одного потока
+pub struct Reconnector {
+    policy: ReconnectPolicy,
+    clock: Arc<dyn Clock>,
+    rng: StdRng,
+    status: ReconnectStatus,
+}
+
+impl Reconnector {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self::with_clock(policy, Arc::new(SystemClock), StdRng::from_entropy())
+    }
+
+    /// Детерминированный вариант для тестов: свои часы и зерно отклонений
+    pub fn with_clock(policy: ReconnectPolicy, clock: Arc<dyn Clock>, rng: StdRng) -> Self {
+        Self {
+            policy,
+            clock,
+            rng,
+            status: ReconnectStatus {
+                circuit: CircuitState::Closed,
+                consecutive_failures: 0,
+                total_failures: 0,
+                next_attempt_at: None,
+                last_failure_at: None,
+                last_success_at: None,
+                last_error: None,
+            },
+        }
+    }
+
+    pub fn status(&self) -> &ReconnectStatus {
+        &self.status
+    }
+
+    pub fn policy(&self) -> &ReconnectPolicy {
+        &self.policy
+    }
+
+    /// Сколько ждать до следующей попытки (ноль — можно подключаться)
+    pub fn wait_time(&self) -> Duration {
+        self.status
+            .next_attempt_at
+            .and_then(|at| (at - self.clock.now()).to_std().ok())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Начало попытки; после медленного интервала предохранитель переходит в пробный режим
+    pub fn try_attempt(&mut self) -> bool {
+        if !self.wait_time().is_zero() {
+            return false;
+        }
+        if self.status.circuit == CircuitState::Open {
+            self.status.circuit = CircuitState::HalfOpen;
+        }
+        true
+    }
+
+    /// Успешное подключение сбрасывает счётчик и замыкает предохранитель
+    pub fn record_success(&mut self) {
+        self.status.circuit = CircuitState::Closed;
+        self.status.consecutive_failures = 0;
+        self.status.next_attempt_at = None;
+        self.status.last_success_at = Some(self.clock.now());
+    }
+
+    /// Сбой подключения; возвращает задержку до следующей попытки
+    pub fn record_failure(&mut self, error: &SurveillanceError) -> Duration {
+        let now = self.clock.now();
+        self.status.consecutive_failures += 1;
+        self.status.total_failures += 1;
+        self.status.last_failure_at = Some(now);
+        self.status.last_error = Some(CommandError::new(error, crate::session_locale()));
+
+        let delay = match self.status.circuit {
+            CircuitState::Closed if self.status.consecutive_failures < self.policy.max_attempts => {
+                let delay = self.policy.base_delay(self.status.consecutive_failures);
+                self.jittered(delay).min(self.policy.max_delay)
+            }
+            // Бюджет исчерпан или пробная попытка не удалась
+            _ => {
+                self.status.circuit = CircuitState::Open;
+                self.jittered(self.policy.slow_retry_interval)
+            }
+        };
+        self.status.next_attempt_at =
+            Some(now + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero()));
+        delay
+    }
+
+    /// Отклонение задержки разводит переподключения камер одного регистратора
+    fn jittered(&mut self, delay: Duration) -> Duration {
+        if self.policy.jitter <= 0.0 {
+            return delay;
+        }
+        let factor = 1.0 + self.rng.gen_range(-self.policy.jitter..=self.policy.jitter);
+        Duration::from_secs_f64(delay.as_secs_f64() * factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn policy(jitter: f64) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter,
+            max_attempts: 5,
+            slow_retry_interval: Duration::from_secs(300),
+        }
+    }
+
+    fn reconnector(jitter: f64, seed: u64) -> (Arc<ManualClock>, Reconnector) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()));
+        let reconnector = Reconnector::with_clock(policy(jitter), clock.clone(), StdRng::seed_from_u64(seed));
+        (clock, reconnector)
+    }
+
+    #[test]
+    fn test_backoff_breaker_and_slow_retry() {
+        let (clock, mut reconnector) = reconnector(0.0, 1);
+        let error = SurveillanceError::ConnectionTimeout;
+
+        // 1, 2, 4, 8 секунд, затем предохранитель размыкается
+        for expected in [1, 2, 4, 8] {
+            assert!(reconnector.try_attempt());
+            assert_eq!(reconnector.record_failure(&error), Duration::from_secs(expected));
+            assert_eq!(reconnector.status().circuit, CircuitState::Closed);
+            assert!(!reconnector.try_attempt());
+            clock.advance(Duration::from_secs(expected));
+        }
+        assert!(reconnector.try_attempt());
+        assert_eq!(reconnector.record_failure(&error), Duration::from_secs(300));
+        assert_eq!(reconnector.status().circuit, CircuitState::Open);
+        assert_eq!(reconnector.status().consecutive_failures, 5);
+
+        // Пробная попытка только после медленного интервала; неудача снова размыкает
+        clock.advance(Duration::from_secs(299));
+        assert!(!reconnector.try_attempt());
+        assert_eq!(reconnector.wait_time(), Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+        assert!(reconnector.try_attempt());
+        assert_eq!(reconnector.status().circuit, CircuitState::HalfOpen);
+        assert_eq!(reconnector.record_failure(&error), Duration::from_secs(300));
+        assert_eq!(reconnector.status().circuit, CircuitState::Open);
+
+        clock.advance(Duration::from_secs(300));
+        assert!(reconnector.try_attempt());
+        reconnector.record_success();
+        let status = reconnector.status();
+        assert_eq!((status.circuit, status.consecutive_failures, status.total_failures), (CircuitState::Closed, 0, 6));
+        assert_eq!(status.last_success_at, Some(clock.now()));
+        assert_eq!(reconnector.record_failure(&error), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_delay_cap_and_jitter() {
+        let capped = policy(0.0);
+        assert_eq!(capped.base_delay(6), Duration::from_secs(30));
+        assert_eq!(capped.base_delay(1000), Duration::from_secs(30));
+
+        // Отклонение в пределах ±20% и воспроизводимо при одном зерне
+        let (_, mut first) = reconnector(0.2, 7);
+        let (_, mut second) = reconnector(0.2, 7);
+        let error = SurveillanceError::ConnectionTimeout;
+        for failures in 1..=4 {
+            let delay = first.record_failure(&error);
+            assert_eq!(delay, second.record_failure(&error));
+            let base = policy(0.0).base_delay(failures).as_secs_f64();
+            assert!((base * 0.8..=base * 1.2).contains(&delay.as_secs_f64()), "{:?}", delay);
+        }
+    }
+}
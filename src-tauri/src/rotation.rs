@@ -0,0 +1,393 @@
+// rotation.rs - Ротация страниц сетки камер по таймеру
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::auth::User;
+use crate::config::{Config, Settings};
+use crate::reconnect::{Clock, SystemClock};
+
+/// Настройки ротации (интервал и размер сетки задаются в `Settings`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RotationSettings {
+    pub group_by_apartment: bool, // Страница содержит камеры только одной квартиры
+    pub skip_offline: bool,       // Недоступные камеры не занимают места в сетке
+}
+
+impl Default for RotationSettings {
+    fn default() -> Self {
+        Self {
+            group_by_apartment: false,
+            skip_offline: true,
+        }
+    }
+}
+
+/// Камеры, которые делятся на страницы вместе
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationGroup {
+    pub apartment: Option<String>,
+    pub camera_ids: Vec<u32>,
+}
+
+/// Группы для пользователя: включённые камеры доступных ему квартир
+pub fn rotation_groups(config: &Config, user: &User, group_by_apartment: bool) -> Vec<RotationGroup> {
+    if !group_by_apartment {
+        let camera_ids = config
+            .cameras_for_user(user)
+            .into_iter()
+            .filter(|camera| camera.enabled)
+            .map(|camera| camera.id)
+            .collect();
+        return vec![RotationGroup { apartment: None, camera_ids }];
+    }
+
+    let mut grouped = config.get_cameras_grouped_by_apartments();
+    // Квартиры в порядке конфигурации, квартиры без описания — в конце по названию
+    let mut names: Vec<String> = config
+        .apartments
+        .iter()
+        .map(|apartment| apartment.apartment_name.clone())
+        .filter(|name| grouped.contains_key(name))
+        .collect();
+    let mut unlisted: Vec<String> = grouped.keys().filter(|name| !names.contains(name)).cloned().collect();
+    unlisted.sort();
+    names.extend(unlisted);
+
+    names
+        .into_iter()
+        .filter(|name| user.can_access_apartment(name))
+        .filter_map(|name| {
+            let cameras = grouped.remove(&name)?;
+            Some(RotationGroup {
+                camera_ids: cameras.into_iter().map(|camera| camera.id).collect(),
+                apartment: Some(name),
+            })
+        })
+        .collect()
+}
+
+/// Страница сетки
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RotationPage {
+    pub index: usize,
+    pub apartment: Option<String>,
+    /// Закреплённые камеры идут первыми
+    pub camera_ids: Vec<u32>,
+}
+
+/// Состояние ротации; одинаково во всех окнах
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RotationState {
+    pub page: Option<RotationPage>,
+    pub page_count: usize,
+    pub paused: bool,
+    pub pinned: Vec<u32>,
+    pub next_switch_at: Option<DateTime<Utc>>,
+}
+
+/// Получатель событий (в приложении — отправка события Tauri)
+pub type RotationSink = Box<dyn Fn(&RotationState) + Send>;
+
+/// Планировщик ротации страниц
+pub struct RotationScheduler {
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+    grid_size: usize,
+    skip_offline: bool,
+    groups: Vec<RotationGroup>,
+    unavailable: BTreeSet<u32>,
+    pinned: Vec<u32>,
+    pages: Vec<RotationPage>,
+    current: usize,
+    paused: bool,
+    next_switch_at: DateTime<Utc>,
+    sink: Option<RotationSink>,
+}
+
+impl RotationScheduler {
+    pub fn new(settings: &Settings) -> Self {
+        Self::with_clock(settings, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(settings: &Settings, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        let mut scheduler = Self {
+            clock,
+            interval: Duration::ZERO,
+            grid_size: 1,
+            skip_offline: false,
+            groups: Vec::new(),
+            unavailable: BTreeSet::new(),
+            pinned: Vec::new(),
+            pages: Vec::new(),
+            current: 0,
+            paused: false,
+            next_switch_at: now,
+            sink: None,
+        };
+        scheduler.apply_settings(settings);
+        scheduler
+    }
+
+    /// Новый интервал действует с текущего момента
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.interval = Duration::from_secs(settings.rotation_interval.max(1).into());
+        self.grid_size = settings.grid_size.max(1) as usize;
+        self.skip_offline = settings.rotation.skip_offline;
+        self.restart_timer();
+        self.rebuild();
+    }
+
+    pub fn set_sink(&mut self, sink: RotationSink) {
+        self.sink = Some(sink);
+    }
+
+    /// Камеры для ротации (после входа, смены пользователя или конфигурации)
+    pub fn set_groups(&mut self, groups: Vec<RotationGroup>) {
+        if self.groups != groups {
+            self.groups = groups;
+            let known: Vec<u32> = self.groups.iter().flat_map(|group| group.camera_ids.clone()).collect();
+            self.pinned.retain(|id| known.contains(id));
+            self.rebuild();
+        }
+    }
+
+    /// Недоступные камеры; при `skip_offline` страницы пересобираются без них
+    pub fn set_unavailable(&mut self, unavailable: BTreeSet<u32>) {
+        if self.unavailable != unavailable {
+            self.unavailable = unavailable;
+            if self.skip_offline {
+                self.rebuild();
+            }
+        }
+    }
+
+    /// Смена страницы по таймеру; возвращает true, если страница сменилась
+    pub fn tick(&mut self) -> bool {
+        if self.paused || self.pages.len() < 2 || self.clock.now() < self.next_switch_at {
+            return false;
+        }
+        self.current = (self.current + 1) % self.pages.len();
+        self.restart_timer();
+        self.emit();
+        true
+    }
+
+    /// Переход на соседнюю страницу (отрицательное смещение — назад)
+    pub fn step(&mut self, offset: i64) {
+        if self.pages.is_empty() {
+            return;
+        }
+        let count = self.pages.len() as i64;
+        self.current = (self.current as i64 + offset).rem_euclid(count) as usize;
+        self.restart_timer();
+        self.emit();
+    }
+
+    pub fn go_to(&mut self, index: usize) {
+        if index < self.pages.len() {
+            self.current = index;
+            self.restart_timer();
+            self.emit();
+        }
+    }
+
+    /// Пауза останавливает таймер; после возобновления страница показывается полный интервал
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused != paused {
+            self.paused = paused;
+            self.restart_timer();
+            self.emit();
+        }
+    }
+
+    /// Закреплённая камера показывается на каждой странице
+    pub fn set_pinned(&mut self, camera_id: u32, pinned: bool) {
+        let present = self.pinned.contains(&camera_id);
+        if pinned && !present {
+            self.pinned.push(camera_id);
+        } else if !pinned && present {
+            self.pinned.retain(|id| *id != camera_id);
+        } else {
+            return;
+        }
+        self.rebuild();
+    }
+
+    pub fn state(&self) -> RotationState {
+        RotationState {
+            page: self.pages.get(self.current).cloned(),
+            page_count: self.pages.len(),
+            paused: self.paused,
+            pinned: self.pinned.clone(),
+            next_switch_at: (!self.paused && self.pages.len() > 1).then_some(self.next_switch_at),
+        }
+    }
+
+    fn restart_timer(&mut self) {
+        self.next_switch_at = self.clock.now()
+            + chrono::Duration::from_std(self.interval).unwrap_or_else(|_| chrono::Duration::zero());
+    }
+
+    fn emit(&self) {
+        if let Some(sink) = &self.sink {
+            sink(&self.state());
+        }
+    }
+
+    /// Пересборка страниц; событие отправляется, только если текущая страница изменилась
+    fn rebuild(&mut self) {
+        let previous = self.pages.get(self.current).cloned();
+        let skipped = |id: &u32| self.skip_offline && self.unavailable.contains(id);
+        let pinned: Vec<u32> = self.pinned.iter().copied().filter(|id| !skipped(id)).collect();
+        let slots = self.grid_size.saturating_sub(pinned.len()).max(1);
+
+        let mut pages = Vec::new();
+        for group in &self.groups {
+            let cameras: Vec<u32> = group
+                .camera_ids
+                .iter()
+                .copied()
+                .filter(|id| !self.pinned.contains(id) && !skipped(id))
+                .collect();
+            for chunk in cameras.chunks(slots) {
+                pages.push(RotationPage {
+                    index: pages.len(),
+                    apartment: group.apartment.clone(),
+                    camera_ids: pinned.iter().copied().chain(chunk.iter().copied()).collect(),
+                });
+            }
+        }
+        if pages.is_empty() && !pinned.is_empty() {
+            pages.push(RotationPage { index: 0, apartment: None, camera_ids: pinned });
+        }
+
+        // Остаёмся на странице с той же квартирой и первой камерой, если она сохранилась
+        self.current = previous
+            .as_ref()
+            .and_then(|previous| {
+                pages.iter().position(|page| {
+                    page.apartment == previous.apartment
+                        && page.camera_ids.iter().any(|id| previous.camera_ids.contains(id) && !self.pinned.contains(id))
+                })
+            })
+            .unwrap_or_else(|| self.current.min(pages.len().saturating_sub(1)));
+        self.pages = pages;
+
+        if self.pages.get(self.current) != previous.as_ref() {
+            self.emit();
+        }
+    }
+}
+
+/// Общая ротация для всех окон приложения
+pub static ROTATION: Lazy<Mutex<RotationScheduler>> = Lazy::new(|| {
+    Mutex::new(RotationScheduler::new(&Settings::default()))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::UserRole;
+    use crate::reconnect::ManualClock;
+    use chrono::TimeZone;
+
+    type Events = Arc<Mutex<Vec<RotationState>>>;
+
+    fn scheduler(grid_size: u32) -> (Arc<ManualClock>, RotationScheduler, Events) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()));
+        let settings = Settings { rotation_interval: 15, grid_size, ..Settings::default() };
+        let mut scheduler = RotationScheduler::with_clock(&settings, clock.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = events.clone();
+        scheduler.set_sink(Box::new(move |state| log.lock().unwrap().push(state.clone())));
+        (clock, scheduler, events)
+    }
+
+    fn group(apartment: Option<&str>, camera_ids: &[u32]) -> RotationGroup {
+        RotationGroup { apartment: apartment.map(str::to_string), camera_ids: camera_ids.to_vec() }
+    }
+
+    fn page_ids(scheduler: &RotationScheduler) -> Vec<u32> {
+        scheduler.state().page.unwrap().camera_ids
+    }
+
+    #[test]
+    fn test_timer_pause_and_manual_steps() {
+        let (clock, mut scheduler, events) = scheduler(4);
+        scheduler.set_groups(vec![group(None, &[1, 2, 3, 4, 5, 6, 7, 8, 9])]);
+        assert_eq!(scheduler.state().page_count, 3);
+        assert_eq!(page_ids(&scheduler), vec![1, 2, 3, 4]);
+
+        clock.advance(Duration::from_secs(14));
+        assert!(!scheduler.tick());
+        clock.advance(Duration::from_secs(1));
+        assert!(scheduler.tick());
+        assert_eq!(page_ids(&scheduler), vec![5, 6, 7, 8]);
+
+        scheduler.set_paused(true);
+        clock.advance(Duration::from_secs(60));
+        assert!(!scheduler.tick());
+        assert!(scheduler.state().next_switch_at.is_none());
+
+        // Вручную можно листать и на паузе, с переходом через край
+        scheduler.step(2);
+        assert_eq!(page_ids(&scheduler), vec![1, 2, 3, 4]);
+        scheduler.step(-1);
+        assert_eq!(page_ids(&scheduler), vec![9]);
+
+        scheduler.set_paused(false);
+        clock.advance(Duration::from_secs(15));
+        assert!(scheduler.tick());
+        assert_eq!(scheduler.state().page.unwrap().index, 0);
+
+        let indexes: Vec<Option<usize>> = events.lock().unwrap().iter().map(|s| s.page.as_ref().map(|p| p.index)).collect();
+        assert_eq!(indexes, vec![Some(0), Some(1), Some(1), Some(0), Some(2), Some(2), Some(0)]);
+    }
+
+    #[test]
+    fn test_pin_and_skip_offline() {
+        let (_, mut scheduler, _) = scheduler(4);
+        scheduler.set_groups(vec![group(Some("Офис"), &[1, 2, 3]), group(Some("Склад"), &[4, 5, 6, 7, 8])]);
+        assert_eq!(scheduler.state().page_count, 3);
+
+        // Закреплённая камера занимает место на каждой странице
+        scheduler.set_pinned(5, true);
+        assert_eq!(page_ids(&scheduler), vec![5, 1, 2, 3]);
+        scheduler.step(1);
+        let page = scheduler.state().page.unwrap();
+        assert_eq!((page.apartment.as_deref(), page.camera_ids), (Some("Склад"), vec![5, 4, 6, 7]));
+
+        // Недоступные камеры пропускаются, страница остаётся той же
+        scheduler.set_unavailable([6, 7].into_iter().collect());
+        assert_eq!(page_ids(&scheduler), vec![5, 4, 8]);
+        assert_eq!(scheduler.state().page_count, 2);
+
+        scheduler.set_pinned(5, false);
+        scheduler.set_unavailable(BTreeSet::new());
+        assert_eq!(page_ids(&scheduler), vec![4, 5, 6, 7]);
+        assert_eq!(scheduler.state().page_count, 3);
+    }
+
+    #[test]
+    fn test_groups_for_user() {
+        let mut config = Config::new_test();
+        let mut user = User::new("viewer", "hash".to_string(), UserRole::Operator);
+        let all = rotation_groups(&config, &user, false);
+        let enabled: Vec<u32> = config.cameras.iter().filter(|c| c.enabled).map(|c| c.id).collect();
+        assert_eq!(all, vec![group(None, &enabled)]);
+
+        let apartment = config.apartments[0].apartment_name.clone();
+        user.allowed_apartments = Some(vec![apartment.clone()]);
+        config.cameras[0].apartment_name = apartment.clone();
+        let grouped = rotation_groups(&config, &user, true);
+        assert_eq!(grouped.len(), 1);
+        assert_eq!(grouped[0].apartment.as_deref(), Some(apartment.as_str()));
+        assert!(grouped[0].camera_ids.iter().all(|id| config.cameras.iter().any(|c| c.id == *id && c.apartment_name == apartment)));
+    }
+}
@@ -0,0 +1,1160 @@
+// rtsp.rs - Клиент RTSP 1.0 (RFC 2326) без внешнего FFmpeg
+
+use md5::{Digest, Md5};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use url::Url;
+use crate::config::Camera;
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+use crate::sdp::{MediaDescription, SessionDescription};
+
+const USER_AGENT: &str = "surveillance-system";
+
+/// Порт RTSP по умолчанию
+const DEFAULT_PORT: u16 = 554;
+
+/// Таймаут сессии, если камера его не сообщила (RFC 2326, 12.37)
+const DEFAULT_SESSION_TIMEOUT: u64 = 60;
+
+/// Предел размера заголовков ответа
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// Предел размера тела ответа (SDP и параметры занимают единицы килобайт)
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Способ доставки RTP
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RtspTransport {
+    /// RTP внутри соединения RTSP (`$` + канал + длина)
+    Tcp,
+    /// RTP и RTCP на паре UDP-портов клиента
+    Udp,
+}
+
+/// Ответ сервера RTSP
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtspResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RtspResponse {
+    /// Значение заголовка (без учёта регистра имени)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn headers_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn cseq(&self) -> Option<u32> {
+        self.header("CSeq")?.trim().parse().ok()
+    }
+
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Сообщение из соединения: ответ или кадр RTP/RTCP внутри TCP
+#[derive(Debug)]
+enum Message {
+    Response(RtspResponse),
+    Data { channel: u8, payload: Vec<u8> },
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Разбор одного сообщения из начала буфера; `None` — данных пока мало
+fn parse_message(buffer: &[u8]) -> Result<Option<(Message, usize)>> {
+    if buffer.first() == Some(&b'$') {
+        if buffer.len() < 4 {
+            return Ok(None);
+        }
+        let size = u16::from_be_bytes([buffer[2], buffer[3]]) as usize;
+        if buffer.len() < 4 + size {
+            return Ok(None);
+        }
+        let payload = buffer[4..4 + size].to_vec();
+        return Ok(Some((Message::Data { channel: buffer[1], payload }, 4 + size)));
+    }
+
+    let Some(head_end) = find(buffer, b"\r\n\r\n") else {
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Err(SurveillanceError::rtsp_error("rtsp_response_too_long"));
+        }
+        return Ok(None);
+    };
+    let head = std::str::from_utf8(&buffer[..head_end])
+        .map_err(|_| SurveillanceError::rtsp_error("rtsp_response_invalid"))?;
+
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let mut status_parts = status_line.splitn(3, ' ');
+    let (Some(version), Some(status)) = (status_parts.next(), status_parts.next()) else {
+        return Err(SurveillanceError::rtsp_error(Detail::new("rtsp_status_line_invalid").with("line", status_line)));
+    };
+    if !version.starts_with("RTSP/") {
+        return Err(SurveillanceError::rtsp_error(Detail::new("rtsp_status_line_invalid").with("line", status_line)));
+    }
+    let status = status
+        .parse()
+        .map_err(|_| SurveillanceError::rtsp_error(Detail::new("rtsp_status_line_invalid").with("line", status_line)))?;
+    let reason = status_parts.next().unwrap_or_default().to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = match headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("Content-Length")) {
+        Some((_, value)) => value
+            .parse::<usize>()
+            .ok()
+            .filter(|length| *length <= MAX_BODY_SIZE)
+            .ok_or_else(|| SurveillanceError::rtsp_error(Detail::new("rtsp_content_length_invalid").with("length", value.as_str())))?,
+        None => 0,
+    };
+    let body_start = head_end + 4;
+    let body_end = body_start
+        .checked_add(content_length)
+        .ok_or_else(|| SurveillanceError::rtsp_error(Detail::new("rtsp_content_length_invalid").with("length", content_length)))?;
+    if buffer.len() < body_end {
+        return Ok(None);
+    }
+
+    let response = RtspResponse {
+        status,
+        reason,
+        headers,
+        body: buffer[body_start..body_end].to_vec(),
+    };
+    Ok(Some((Message::Response(response), body_end)))
+}
+
+/// Ожидание с таймаутом соединения
+async fn with_timeout<T>(timeout: Duration, future: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| SurveillanceError::ConnectionTimeout)?
+}
+
+/// Параметры заголовка `WWW-Authenticate: схема ключ="значение", ...`
+fn parse_auth_params(text: &str) -> BTreeMap<String, String> {
+    let mut params = BTreeMap::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let after = after.trim_start();
+        let (value, remainder) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match after.find(',') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            },
+        };
+        params.insert(key, value.trim().to_string());
+        rest = remainder.trim_start().trim_start_matches(',').trim_start();
+    }
+    params
+}
+
+/// Схема аутентификации, предложенная камерой
+#[derive(Debug, Clone, PartialEq)]
+enum Challenge {
+    Basic,
+    Digest {
+        realm: String,
+        nonce: String,
+        opaque: Option<String>,
+        qop_auth: bool,
+        nonce_count: u32,
+    },
+}
+
+impl Challenge {
+    /// Выбор схемы: Digest предпочтительнее Basic
+    fn select<'a>(headers: impl Iterator<Item = &'a str>) -> Option<Self> {
+        let mut basic = None;
+        for header in headers {
+            let (scheme, params) = header.trim().split_once(' ').unwrap_or((header.trim(), ""));
+            if scheme.eq_ignore_ascii_case("Digest") {
+                let params = parse_auth_params(params);
+                let algorithm_supported = params
+                    .get("algorithm")
+                    .is_none_or(|algorithm| algorithm.eq_ignore_ascii_case("MD5"));
+                if let (Some(realm), Some(nonce), true) = (params.get("realm"), params.get("nonce"), algorithm_supported) {
+                    return Some(Challenge::Digest {
+                        realm: realm.clone(),
+                        nonce: nonce.clone(),
+                        opaque: params.get("opaque").cloned(),
+                        qop_auth: params
+                            .get("qop")
+                            .is_some_and(|qop| qop.split(',').any(|value| value.trim() == "auth")),
+                        nonce_count: 0,
+                    });
+                }
+            } else if scheme.eq_ignore_ascii_case("Basic") {
+                basic = Some(Challenge::Basic);
+            }
+        }
+        basic
+    }
+}
+
+fn md5_hex(text: &str) -> String {
+    Md5::digest(text.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Ответ Digest (RFC 2617); `qop` — пара (nc, cnonce) для qop=auth
+fn digest_response(
+    credentials: (&str, &str),
+    realm: &str,
+    nonce: &str,
+    method: &str,
+    uri: &str,
+    qop: Option<(&str, &str)>,
+) -> String {
+    let ha1 = md5_hex(&format!("{}:{}:{}", credentials.0, realm, credentials.1));
+    let ha2 = md5_hex(&format!("{}:{}", method, uri));
+    match qop {
+        Some((nonce_count, cnonce)) => {
+            md5_hex(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nonce_count, cnonce, ha2))
+        }
+        None => md5_hex(&format!("{}:{}:{}", ha1, nonce, ha2)),
+    }
+}
+
+/// Ответ сервера на SETUP
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportReply {
+    pub interleaved: Option<(u8, u8)>,
+    pub server_port: Option<(u16, u16)>,
+    pub ssrc: Option<u32>,
+}
+
+impl TransportReply {
+    fn parse(header: &str) -> Self {
+        let mut reply = TransportReply { interleaved: None, server_port: None, ssrc: None };
+        for parameter in header.split(';') {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            match name.trim() {
+                "interleaved" => reply.interleaved = parse_pair(value),
+                "server_port" => reply.server_port = parse_pair(value),
+                "ssrc" => reply.ssrc = u32::from_str_radix(value.trim(), 16).ok(),
+                _ => {}
+            }
+        }
+        reply
+    }
+}
+
+/// Пара `a-b`; без второго числа — следующее за первым, если оно помещается в тип
+fn parse_pair<T>(value: &str) -> Option<(T, T)>
+where
+    T: std::str::FromStr + Copy + TryFrom<u32>,
+    u32: From<T>,
+{
+    let (first, second) = value.split_once('-').unwrap_or((value, ""));
+    let first: T = first.trim().parse().ok()?;
+    let second = match second.trim().parse() {
+        Ok(second) => second,
+        Err(_) => T::try_from(u32::from(first).checked_add(1)?).ok()?,
+    };
+    Some((first, second))
+}
+
+/// Клиент RTSP: одно управляющее соединение с камерой
+pub struct RtspClient {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    url: Url,
+    credentials: Option<(String, String)>,
+    challenge: Option<Challenge>,
+    cseq: u32,
+    session: Option<String>,
+    session_timeout: Duration,
+    timeout: Duration,
+    public_methods: Vec<String>,
+    base_url: Option<String>,
+    /// Кадры RTP, пришедшие во время ожидания ответа
+    pending: VecDeque<(u8, Vec<u8>)>,
+}
+
+impl RtspClient {
+    /// Подключение по ссылке вида `rtsp://логин:пароль@адрес:порт/путь`
+    pub async fn connect(link: &str, timeout: Duration) -> Result<Self> {
+        let mut url = Url::parse(link)
+            .map_err(|e| SurveillanceError::rtsp_error(Detail::new("rtsp_url_invalid").with("error", e.to_string())))?;
+        if url.scheme() != "rtsp" {
+            return Err(SurveillanceError::rtsp_error(Detail::new("rtsp_scheme_unsupported").with("scheme", url.scheme())));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| SurveillanceError::rtsp_error("rtsp_url_no_host"))?
+            .to_string();
+        let port = url.port().unwrap_or(DEFAULT_PORT);
+
+        // Учётные данные передаются только в заголовке Authorization
+        let credentials = (!url.username().is_empty()).then(|| {
+            (
+                decode_userinfo(url.username()),
+                decode_userinfo(url.password().unwrap_or_default()),
+            )
+        });
+        url.set_username("").ok();
+        url.set_password(None).ok();
+
+        let stream = with_timeout(timeout, async {
+            TcpStream::connect((host.as_str(), port))
+                .await
+                .map_err(|e| SurveillanceError::network_error(Detail::new("connect_failed").with("address", format!("{}:{}", host, port)).with("error", e.to_string())))
+        })
+        .await?;
+        stream.set_nodelay(true).ok();
+
+        Ok(Self {
+            stream,
+            buffer: Vec::new(),
+            url,
+            credentials,
+            challenge: None,
+            cseq: 0,
+            session: None,
+            session_timeout: Duration::from_secs(DEFAULT_SESSION_TIMEOUT),
+            timeout,
+            public_methods: Vec::new(),
+            base_url: None,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Адрес камеры без учётных данных
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Таймаут сессии, объявленный камерой
+    pub fn session_timeout(&self) -> Duration {
+        self.session_timeout
+    }
+
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.stream.peer_addr()?)
+    }
+
+    /// Чтение следующего сообщения (безопасно при отмене: состояние в буфере)
+    async fn read_message(&mut self) -> Result<Message> {
+        loop {
+            if let Some((message, used)) = parse_message(&self.buffer)? {
+                self.buffer.drain(..used);
+                return Ok(message);
+            }
+            let mut chunk = [0u8; 8192];
+            let read = self.stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Err(SurveillanceError::network_error("rtsp_connection_closed"));
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    fn authorization(&mut self, method: &str, uri: &str) -> Option<String> {
+        let (login, password) = self.credentials.as_ref()?;
+        match self.challenge.as_mut()? {
+            Challenge::Basic => {
+                let token = data_encoding::BASE64.encode(format!("{}:{}", login, password).as_bytes());
+                Some(format!("Basic {}", token))
+            }
+            Challenge::Digest { realm, nonce, opaque, qop_auth, nonce_count } => {
+                let mut header = format!(
+                    "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\"",
+                    login, realm, nonce, uri
+                );
+                if *qop_auth {
+                    *nonce_count += 1;
+                    let nc = format!("{:08x}", nonce_count);
+                    let mut cnonce = [0u8; 8];
+                    rand::thread_rng().fill_bytes(&mut cnonce);
+                    let cnonce: String = cnonce.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    let response = digest_response(
+                        (login, password), realm, nonce, method, uri, Some((&nc, &cnonce)),
+                    );
+                    header.push_str(&format!(
+                        ", qop=auth, nc={}, cnonce=\"{}\", response=\"{}\"",
+                        nc, cnonce, response
+                    ));
+                } else {
+                    let response = digest_response((login, password), realm, nonce, method, uri, None);
+                    header.push_str(&format!(", response=\"{}\"", response));
+                }
+                if let Some(opaque) = opaque {
+                    header.push_str(&format!(", opaque=\"{}\"", opaque));
+                }
+                Some(header)
+            }
+        }
+    }
+
+    async fn send(&mut self, method: &str, uri: &str, headers: &[(&str, String)]) -> Result<u32> {
+        self.cseq += 1;
+        let mut request = format!("{} {} RTSP/1.0\r\nCSeq: {}\r\nUser-Agent: {}\r\n", method, uri, self.cseq, USER_AGENT);
+        if let Some(session) = &self.session {
+            request.push_str(&format!("Session: {}\r\n", session));
+        }
+        if let Some(authorization) = self.authorization(method, uri) {
+            request.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+
+        self.stream.write_all(request.as_bytes()).await?;
+        Ok(self.cseq)
+    }
+
+    /// Запрос с ожиданием ответа; на 401 — один повтор с аутентификацией
+    pub async fn request(&mut self, method: &str, uri: &str, headers: &[(&str, String)]) -> Result<RtspResponse> {
+        let mut retried = false;
+        loop {
+            let timeout = self.timeout;
+            let response = with_timeout(timeout, async {
+                let cseq = self.send(method, uri, headers).await?;
+                loop {
+                    match self.read_message().await? {
+                        Message::Data { channel, payload } => self.pending.push_back((channel, payload)),
+                        Message::Response(response) if response.cseq() == Some(cseq) => return Ok(response),
+                        Message::Response(response) => {
+                            log::debug!("Пропущен ответ RTSP без запроса: {} {}", response.status, response.reason);
+                        }
+                    }
+                }
+            })
+            .await?;
+
+            if response.status == 401 && !retried && self.credentials.is_some() {
+                if let Some(challenge) = Challenge::select(response.headers_named("WWW-Authenticate")) {
+                    self.challenge = Some(challenge);
+                    retried = true;
+                    continue;
+                }
+            }
+
+            if response.is_success() {
+                return Ok(response);
+            }
+            return match response.status {
+                401 | 403 => Err(SurveillanceError::CameraAuthFailed),
+                404 => Err(SurveillanceError::rtsp_error("camera_stream_not_found")),
+                status => Err(SurveillanceError::rtsp_error(
+                    Detail::new("rtsp_request_failed")
+                        .with("method", method)
+                        .with("uri", uri)
+                        .with("status", status)
+                        .with("reason", response.reason.as_str()),
+                )),
+            };
+        }
+    }
+
+    /// OPTIONS: список поддерживаемых методов
+    pub async fn options(&mut self) -> Result<Vec<String>> {
+        let uri = self.url.to_string();
+        let response = self.request("OPTIONS", &uri, &[]).await?;
+        self.public_methods = response
+            .header("Public")
+            .unwrap_or_default()
+            .split(',')
+            .map(|method| method.trim().to_uppercase())
+            .filter(|method| !method.is_empty())
+            .collect();
+        Ok(self.public_methods.clone())
+    }
+
+    /// DESCRIBE: описание потоков камеры
+    pub async fn describe(&mut self) -> Result<SessionDescription> {
+        let uri = self.url.to_string();
+        let response = self
+            .request("DESCRIBE", &uri, &[("Accept", "application/sdp".to_string())])
+            .await?;
+
+        let base = response
+            .header("Content-Base")
+            .or_else(|| response.header("Content-Location"))
+            .map(str::to_string)
+            .unwrap_or(uri);
+        let text = String::from_utf8_lossy(&response.body);
+        let description = SessionDescription::parse(&text)?;
+        self.base_url = Some(description.session_url(&base));
+        Ok(description)
+    }
+
+    /// Базовый URL после DESCRIBE
+    fn base_url(&self) -> String {
+        self.base_url.clone().unwrap_or_else(|| self.url.to_string())
+    }
+
+    /// SETUP одного медиапотока с заданным заголовком Transport
+    pub async fn setup(&mut self, media: &MediaDescription, transport: &str) -> Result<TransportReply> {
+        let uri = media.control_url(&self.base_url());
+        let response = self.request("SETUP", &uri, &[("Transport", transport.to_string())]).await?;
+
+        if let Some(session) = response.header("Session") {
+            let mut parts = session.split(';');
+            self.session = parts.next().map(|id| id.trim().to_string());
+            if let Some(timeout) = parts
+                .filter_map(|part| part.trim().strip_prefix("timeout="))
+                .find_map(|timeout| timeout.trim().parse::<u64>().ok())
+            {
+                self.session_timeout = Duration::from_secs(timeout.max(1));
+            }
+        }
+        if self.session.is_none() {
+            return Err(SurveillanceError::rtsp_error("rtsp_no_session"));
+        }
+
+        let reply = response
+            .header("Transport")
+            .map(TransportReply::parse)
+            .ok_or_else(|| SurveillanceError::rtsp_error("rtsp_no_transport"))?;
+        Ok(reply)
+    }
+
+    /// PLAY: начало передачи с текущего момента
+    pub async fn play(&mut self) -> Result<()> {
+        let uri = self.base_url();
+        self.request("PLAY", &uri, &[("Range", "npt=0.000-".to_string())]).await?;
+        Ok(())
+    }
+
+    /// Поддержание сессии: GET_PARAMETER, если камера его поддерживает, иначе OPTIONS
+    pub async fn keepalive(&mut self) -> Result<()> {
+        let method = if self.public_methods.iter().any(|method| method == "GET_PARAMETER") {
+            "GET_PARAMETER"
+        } else {
+            "OPTIONS"
+        };
+        let uri = self.base_url();
+        self.request(method, &uri, &[]).await?;
+        Ok(())
+    }
+
+    /// TEARDOWN: завершение сессии
+    pub async fn teardown(&mut self) -> Result<()> {
+        if self.session.is_none() {
+            return Ok(());
+        }
+        let uri = self.base_url();
+        let result = self.request("TEARDOWN", &uri, &[]).await;
+        self.session = None;
+        result.map(|_| ())
+    }
+
+    /// Следующий кадр RTP/RTCP, переданный внутри соединения
+    async fn read_interleaved(&mut self) -> Result<(u8, Vec<u8>)> {
+        if let Some(frame) = self.pending.pop_front() {
+            return Ok(frame);
+        }
+        loop {
+            match self.read_message().await? {
+                Message::Data { channel, payload } => return Ok((channel, payload)),
+                Message::Response(response) => {
+                    log::debug!("Пропущен ответ RTSP без запроса: {} {}", response.status, response.reason);
+                }
+            }
+        }
+    }
+}
+
+/// Декодирование %XX в логине и пароле из ссылки
+fn decode_userinfo(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%' && index + 2 < bytes.len())
+            .then(|| std::str::from_utf8(&bytes[index + 1..index + 3]).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Медиапоток сессии после SETUP
+#[derive(Debug, Clone)]
+pub struct RtspTrack {
+    pub media: MediaDescription,
+    pub control_url: String,
+    pub transport: TransportReply,
+}
+
+/// Пакет RTP или RTCP одного из потоков сессии
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPacket {
+    pub track: usize,
+    pub rtcp: bool,
+    pub payload: Vec<u8>,
+}
+
+/// Сессия просмотра камеры: DESCRIBE, SETUP всех потоков, PLAY и поддержание
+pub struct RtspSession {
+    client: RtspClient,
+    tracks: Vec<RtspTrack>,
+    transport: RtspTransport,
+    keepalive_interval: Duration,
+    next_keepalive: Instant,
+    udp_packets: Option<mpsc::Receiver<MediaPacket>>,
+    udp_readers: Vec<JoinHandle<()>>,
+}
+
+impl RtspSession {
+    /// Подключение к камере по `Camera::rtsp_link`
+    pub async fn open(camera: &Camera, transport: RtspTransport, timeout: Duration) -> Result<Self> {
+        if !camera.enabled {
+            return Err(SurveillanceError::CameraUnavailable { camera_id: camera.id });
+        }
+        Self::open_url(&camera.rtsp_link, transport, timeout).await
+    }
+
+    pub async fn open_url(link: &str, transport: RtspTransport, timeout: Duration) -> Result<Self> {
+        let mut client = RtspClient::connect(link, timeout).await?;
+        client.options().await?;
+        let description = client.describe().await?;
+
+        let mut tracks = Vec::new();
+        let mut udp_sockets = Vec::new();
+        let media: Vec<MediaDescription> = description
+            .media
+            .into_iter()
+            .filter(|media| media.kind == "video" || media.kind == "audio")
+            .collect();
+
+        for (index, media) in media.into_iter().enumerate() {
+            let header = match transport {
+                RtspTransport::Tcp => {
+                    // Каналы RTP и RTCP дорожки — чётный и следующий за ним
+                    let channel = index
+                        .checked_mul(2)
+                        .and_then(|channel| u8::try_from(channel).ok())
+                        .filter(|channel| *channel < u8::MAX)
+                        .ok_or_else(|| SurveillanceError::rtsp_error("rtsp_too_many_tracks"))?;
+                    format!("RTP/AVP/TCP;unicast;interleaved={}-{}", channel, channel + 1)
+                }
+                RtspTransport::Udp => {
+                    let (rtp, rtcp) = bind_udp_pair().await?;
+                    let header = format!(
+                        "RTP/AVP;unicast;client_port={}-{}",
+                        rtp.local_addr()?.port(),
+                        rtcp.local_addr()?.port()
+                    );
+                    udp_sockets.push((rtp, rtcp));
+                    header
+                }
+            };
+            let reply = client.setup(&media, &header).await?;
+            let control_url = media.control_url(&client.base_url());
+            tracks.push(RtspTrack { media, control_url, transport: reply });
+        }
+        if tracks.is_empty() {
+            return Err(SurveillanceError::rtsp_error("camera_no_media"));
+        }
+
+        let (udp_packets, udp_readers) = if transport == RtspTransport::Udp {
+            let camera_ip = client.peer_addr()?.ip();
+            let (sender, receiver) = mpsc::channel(1024);
+            let mut readers = Vec::new();
+            for (track, (rtp, rtcp)) in udp_sockets.into_iter().enumerate() {
+                readers.push(spawn_udp_reader(rtp, track, false, camera_ip, sender.clone()));
+                readers.push(spawn_udp_reader(rtcp, track, true, camera_ip, sender.clone()));
+            }
+            (Some(receiver), readers)
+        } else {
+            (None, Vec::new())
+        };
+
+        client.play().await?;
+
+        // Запас в половину таймаута сессии
+        let keepalive_interval = client.session_timeout() / 2;
+        Ok(Self {
+            client,
+            tracks,
+            transport,
+            keepalive_interval,
+            next_keepalive: Instant::now() + keepalive_interval,
+            udp_packets,
+            udp_readers,
+        })
+    }
+
+    pub fn tracks(&self) -> &[RtspTrack] {
+        &self.tracks
+    }
+
+    pub fn transport(&self) -> RtspTransport {
+        self.transport
+    }
+
+    /// Адрес камеры без учётных данных
+    pub fn url(&self) -> &str {
+        self.client.url()
+    }
+
+    /// Номер потока по каналу interleaved
+    fn track_for_channel(&self, channel: u8) -> Option<(usize, bool)> {
+        self.tracks.iter().enumerate().find_map(|(index, track)| {
+            let (rtp, rtcp) = track.transport.interleaved?;
+            match channel {
+                c if c == rtp => Some((index, false)),
+                c if c == rtcp => Some((index, true)),
+                _ => None,
+            }
+        })
+    }
+
+    /// Следующий пакет; между пакетами сессия поддерживается запросами keepalive
+    pub async fn next_packet(&mut self) -> Result<MediaPacket> {
+        loop {
+            if Instant::now() >= self.next_keepalive {
+                self.client.keepalive().await?;
+                self.next_keepalive = Instant::now() + self.keepalive_interval;
+            }
+            let keepalive_due = tokio::time::sleep_until(self.next_keepalive);
+
+            match self.udp_packets.as_mut() {
+                Some(packets) => tokio::select! {
+                    packet = packets.recv() => {
+                        return packet.ok_or_else(|| SurveillanceError::network_error("udp_receive_stopped"));
+                    }
+                    _ = keepalive_due => continue,
+                },
+                None => {
+                    let frame = tokio::select! {
+                        frame = self.client.read_interleaved() => frame?,
+                        _ = keepalive_due => continue,
+                    };
+                    let (channel, payload) = frame;
+                    match self.track_for_channel(channel) {
+                        Some((track, rtcp)) => return Ok(MediaPacket { track, rtcp, payload }),
+                        None => log::debug!("Пакет на неизвестном канале {}", channel),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Завершение сессии (TEARDOWN)
+    pub async fn close(mut self) -> Result<()> {
+        for reader in self.udp_readers.drain(..) {
+            reader.abort();
+        }
+        self.client.teardown().await
+    }
+}
+
+impl Drop for RtspSession {
+    fn drop(&mut self) {
+        for reader in &self.udp_readers {
+            reader.abort();
+        }
+    }
+}
+
+/// Пара UDP-портов: чётный для RTP, следующий за ним для RTCP
+async fn bind_udp_pair() -> Result<(UdpSocket, UdpSocket)> {
+    for _ in 0..16 {
+        let rtp = UdpSocket::bind("0.0.0.0:0").await?;
+        let port = rtp.local_addr()?.port();
+        if port % 2 != 0 || port == u16::MAX {
+            continue;
+        }
+        if let Ok(rtcp) = UdpSocket::bind(("0.0.0.0", port + 1)).await {
+            return Ok((rtp, rtcp));
+        }
+    }
+    Err(SurveillanceError::network_error("udp_ports_unavailable"))
+}
+
+fn spawn_udp_reader(
+    socket: UdpSocket,
+    track: usize,
+    rtcp: bool,
+    camera_ip: std::net::IpAddr,
+    sender: mpsc::Sender<MediaPacket>,
+) -> JoinHandle<()> {
+    let socket = Arc::new(socket);
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; 65536];
+        loop {
+            let (size, from) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    log::warn!("Ошибка приёма UDP: {}", e);
+                    return;
+                }
+            };
+            // Пакеты не от камеры отбрасываются
+            if from.ip() != camera_ip {
+                continue;
+            }
+            let packet = MediaPacket { track, rtcp, payload: buffer[..size].to_vec() };
+            if sender.send(packet).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+
+    const REALM: &str = "IP Camera";
+    const NONCE: &str = "5c2f8a1e9b";
+
+    /// Испытательный сервер RTSP в процессе теста: Digest-аутентификация,
+    /// два потока (H.264 и G.711), доставка по TCP и UDP
+    pub(crate) struct TestServer {
+        pub url: String,
+        pub requests: Arc<Mutex<Vec<String>>>,
+        task: JoinHandle<()>,
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            self.task.abort();
+        }
+    }
+
+    struct ServerRequest {
+        method: String,
+        uri: String,
+        headers: Vec<(String, String)>,
+    }
+
+    impl ServerRequest {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    fn take_request(buffer: &mut Vec<u8>) -> Option<ServerRequest> {
+        let end = find(buffer, b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&buffer[..end]).to_string();
+        buffer.drain(..end + 4);
+
+        let mut lines = head.split("\r\n");
+        let mut start = lines.next()?.split(' ');
+        Some(ServerRequest {
+            method: start.next()?.to_string(),
+            uri: start.next()?.to_string(),
+            headers: lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect(),
+        })
+    }
+
+    /// Проверка Digest без qop по значениям из запроса
+    fn authorized(request: &ServerRequest, login: &str, password: &str) -> bool {
+        let Some(header) = request.header("Authorization").and_then(|h| h.strip_prefix("Digest ")) else {
+            return false;
+        };
+        let params = parse_auth_params(header);
+        let expected = digest_response((login, password), REALM, NONCE, &request.method, &request.uri, None);
+        params.get("username").map(String::as_str) == Some(login)
+            && params.get("response") == Some(&expected)
+    }
+
+    fn rtp_packet(payload_type: u8, sequence: u16) -> Vec<u8> {
+        let mut packet = vec![0x80, payload_type];
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&(u32::from(sequence) * 3600).to_be_bytes());
+        packet.extend_from_slice(&0x1234_5678u32.to_be_bytes());
+        packet.extend_from_slice(&[0x65, 0x88, 0x84, 0x00]);
+        packet
+    }
+
+    pub(crate) const TEST_SDP: &str = "v=0\r\n\
+        o=- 1 1 IN IP4 127.0.0.1\r\n\
+        s=Test Camera\r\n\
+        t=0 0\r\n\
+        m=video 0 RTP/AVP 96\r\n\
+        b=AS:1024\r\n\
+        a=rtpmap:96 H264/90000\r\n\
+        a=fmtp:96 packetization-mode=1;sprop-parameter-sets=Z0LAKNoBQBbk,aM48gA==\r\n\
+        a=framerate:25\r\n\
+        a=control:trackID=1\r\n\
+        m=audio 0 RTP/AVP 8\r\n\
+        a=control:trackID=2\r\n";
+
+    impl TestServer {
+        pub(crate) async fn start(login: &'static str, password: &'static str) -> Self {
+            Self::start_with_sdp(login, password, TEST_SDP).await
+        }
+
+        /// Сервер с собственным описанием SDP (другие кодеки)
+        pub(crate) async fn start_with_sdp(login: &'static str, password: &'static str, sdp: &'static str) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let url = format!("rtsp://{}:{}@{}/stream", login, password, address);
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let log = requests.clone();
+
+            let task = tokio::spawn(async move {
+                while let Ok((socket, peer)) = listener.accept().await {
+                    let log = log.clone();
+                    tokio::spawn(serve_connection(socket, peer, address, (login, password), sdp, log));
+                }
+            });
+            Self { url, requests, task }
+        }
+
+        pub(crate) fn methods(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn serve_connection(
+        mut socket: TcpStream,
+        peer: SocketAddr,
+        address: SocketAddr,
+        (login, password): (&'static str, &'static str),
+        sdp: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    ) {
+        let base = format!("rtsp://{}/stream/", address);
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut buffer = Vec::new();
+        let mut udp_targets: Vec<SocketAddr> = Vec::new();
+        let mut playing = false;
+        let mut sequence = 0u16;
+        let mut ticker = tokio::time::interval(Duration::from_millis(20));
+
+        loop {
+            while let Some(request) = take_request(&mut buffer) {
+                let cseq = request.header("CSeq").unwrap_or("0").to_string();
+                let mut reply = format!("RTSP/1.0 200 OK\r\nCSeq: {}\r\n", cseq);
+                let mut body = String::new();
+
+                if request.method != "OPTIONS" && !authorized(&request, login, password) {
+                    log.lock().unwrap().push(format!("{} 401", request.method));
+                    let challenge = format!(
+                        "RTSP/1.0 401 Unauthorized\r\nCSeq: {}\r\n\
+                         WWW-Authenticate: Basic realm=\"{}\"\r\n\
+                         WWW-Authenticate: Digest realm=\"{}\", nonce=\"{}\"\r\n\r\n",
+                        cseq, REALM, REALM, NONCE
+                    );
+                    socket.write_all(challenge.as_bytes()).await.unwrap();
+                    continue;
+                }
+                log.lock().unwrap().push(request.method.clone());
+
+                match request.method.as_str() {
+                    "OPTIONS" => reply.push_str("Public: OPTIONS, DESCRIBE, SETUP, PLAY, GET_PARAMETER, TEARDOWN\r\n"),
+                    "DESCRIBE" => {
+                        reply.push_str(&format!("Content-Base: {}\r\nContent-Type: application/sdp\r\n", base));
+                        body = sdp.to_string();
+                    }
+                    "SETUP" => {
+                        let transport = request.header("Transport").unwrap_or_default().to_string();
+                        reply.push_str("Session: 4F2A9C;timeout=1\r\n");
+                        match transport.split(';').find_map(|p| p.strip_prefix("client_port=")) {
+                            Some(ports) => {
+                                let (rtp, _) = parse_pair::<u16>(ports).unwrap();
+                                udp_targets.push(SocketAddr::new(peer.ip(), rtp));
+                                let server_port = udp.local_addr().unwrap().port();
+                                reply.push_str(&format!(
+                                    "Transport: {};server_port={}-{}\r\n",
+                                    transport, server_port, server_port + 1
+                                ));
+                            }
+                            None => reply.push_str(&format!("Transport: {}\r\n", transport)),
+                        }
+                    }
+                    "PLAY" => playing = true,
+                    "TEARDOWN" => playing = false,
+                    _ => {}
+                }
+                if !body.is_empty() {
+                    reply.push_str(&format!("Content-Length: {}\r\n", body.len()));
+                }
+                reply.push_str("\r\n");
+                reply.push_str(&body);
+                socket.write_all(reply.as_bytes()).await.unwrap();
+            }
+
+            let mut chunk = [0u8; 4096];
+            tokio::select! {
+                read = socket.read(&mut chunk) => match read {
+                    Ok(0) | Err(_) => return,
+                    Ok(size) => buffer.extend_from_slice(&chunk[..size]),
+                },
+                _ = ticker.tick(), if playing => {
+                    sequence = sequence.wrapping_add(1);
+                    let video = rtp_packet(96, sequence);
+                    let audio = rtp_packet(8, sequence);
+                    if udp_targets.is_empty() {
+                        for (channel, packet) in [(0u8, video), (2u8, audio)] {
+                            let mut frame = vec![b'$', channel];
+                            frame.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+                            frame.extend_from_slice(&packet);
+                            socket.write_all(&frame).await.unwrap();
+                        }
+                    } else {
+                        for (target, packet) in udp_targets.iter().zip([video, audio]) {
+                            udp.send_to(&packet, target).await.ok();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_digest_and_challenge_selection() {
+        // Пример из RFC 2617, раздел 3.5
+        let response = digest_response(
+            ("Mufasa", "Circle Of Life"),
+            "testrealm@host.com",
+            "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+            "GET",
+            "/dir/index.html",
+            Some(("00000001", "0a4f113b")),
+        );
+        assert_eq!(response, "6629fae49393a05397450978507c4ef1");
+
+        let headers = ["Basic realm=\"cam\"", "Digest realm=\"cam\", nonce=\"n,1\", qop=\"auth,auth-int\""];
+        match Challenge::select(headers.into_iter()) {
+            Some(Challenge::Digest { nonce, qop_auth, .. }) => {
+                assert_eq!(nonce, "n,1");
+                assert!(qop_auth);
+            }
+            other => panic!("ожидался Digest: {:?}", other),
+        }
+        assert_eq!(Challenge::select(["Basic realm=\"cam\""].into_iter()), Some(Challenge::Basic));
+
+        assert_eq!(decode_userinfo("p%40ss%3A1"), "p@ss:1");
+        let reply = TransportReply::parse("RTP/AVP;unicast;client_port=5000-5001;server_port=6970-6971;ssrc=1A2B3C4D");
+        assert_eq!(reply.server_port, Some((6970, 6971)));
+        assert_eq!(reply.ssrc, Some(0x1A2B_3C4D));
+
+        // Второй канал за пределами типа не вычисляется
+        assert_eq!(parse_pair::<u8>("4"), Some((4, 5)));
+        assert_eq!(parse_pair::<u8>("255"), None);
+        assert_eq!(parse_pair::<u16>("65535-0"), Some((65535, 0)));
+    }
+
+    #[test]
+    fn test_message_limits() {
+        let response = |length: &str| format!("RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: {}\r\n\r\nv=0", length);
+
+        match parse_message(response("3").as_bytes()).unwrap() {
+            Some((Message::Response(response), consumed)) => {
+                assert_eq!(response.body, b"v=0");
+                assert_eq!(consumed, 50);
+            }
+            _ => panic!("ожидался ответ"),
+        }
+        assert!(parse_message(response("10").as_bytes()).unwrap().is_none());
+
+        // Огромная или переполняющая длина отклоняется, а не ждёт данных вечно
+        assert!(parse_message(response("65537").as_bytes()).is_err());
+        assert!(parse_message(response(&usize::MAX.to_string()).as_bytes()).is_err());
+        assert!(parse_message(response("-1").as_bytes()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_session_with_digest_and_keepalive() {
+        let server = TestServer::start("admin", "secret").await;
+        let mut session = RtspSession::open_url(&server.url, RtspTransport::Tcp, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(session.tracks().len(), 2);
+        assert_eq!(session.tracks()[0].media.encoding().as_deref(), Some("H264"));
+        assert_eq!(session.tracks()[1].control_url, format!("{}/trackID=2", server.url.replace("admin:secret@", "")));
+        assert_eq!(session.tracks()[1].transport.interleaved, Some((2, 3)));
+
+        // Пакеты обоих потоков; за это время истекает половина таймаута сессии
+        let started = Instant::now();
+        let mut seen = [0usize; 2];
+        while started.elapsed() < Duration::from_millis(700) {
+            let packet = session.next_packet().await.unwrap();
+            assert!(!packet.rtcp);
+            seen[packet.track] += 1;
+        }
+        assert!(seen[0] > 0 && seen[1] > 0);
+        session.close().await.unwrap();
+
+        let methods = server.methods();
+        assert_eq!(methods[..3], ["OPTIONS", "DESCRIBE 401", "DESCRIBE"]);
+        assert!(methods.contains(&"GET_PARAMETER".to_string()));
+        assert_eq!(methods.last().map(String::as_str), Some("TEARDOWN"));
+    }
+
+    #[tokio::test]
+    async fn test_udp_session_and_wrong_password() {
+        let server = TestServer::start("admin", "secret").await;
+        let mut session = RtspSession::open_url(&server.url, RtspTransport::Udp, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(session.tracks()[0].transport.server_port.is_some());
+
+        let packet = session.next_packet().await.unwrap();
+        assert_eq!(packet.payload[0], 0x80);
+        session.close().await.unwrap();
+
+        let wrong = server.url.replace("secret", "guess");
+        match RtspSession::open_url(&wrong, RtspTransport::Tcp, Duration::from_secs(5)).await {
+            Err(SurveillanceError::CameraAuthFailed) => {}
+            Err(e) => panic!("неожиданная ошибка: {}", e),
+            Ok(_) => panic!("вход с неверным паролем"),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// sdp.rs - Разбор описания сессии SDP из ответа RTSP DESCRIBE
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+
+/// Описание кодека из `a=rtpmap`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RtpMap {
+    pub payload_type: u8,
+    pub encoding: String,
+    pub clock_rate: u32,
+    pub channels: Option<u16>,
+}
+
+/// Медиапоток (`m=`) с атрибутами
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MediaDescription {
+    pub kind: String, // video, audio, application
+    pub port: u16,
+    pub protocol: String,
+    pub payload_types: Vec<u8>,
+    pub control: Option<String>,
+    pub rtpmap: Option<RtpMap>,
+    pub fmtp: BTreeMap<String, String>,
+    pub bandwidth_kbps: Option<u32>,
+    pub framerate: Option<f32>,
+}
+
+impl MediaDescription {
+    fn new(kind: &str, port: u16, protocol: &str, payload_types: Vec<u8>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            port,
+            protocol: protocol.to_string(),
+            payload_types,
+            control: None,
+            rtpmap: None,
+            fmtp: BTreeMap::new(),
+            bandwidth_kbps: None,
+            framerate: None,
+        }
+    }
+
+    /// Основной тип нагрузки (первый в строке `m=`)
+    pub fn payload_type(&self) -> Option<u8> {
+        self.payload_types.first().copied()
+    }
+
+    /// Название кодека; для статических типов RTP берётся из RFC 3551
+    pub fn encoding(&self) -> Option<String> {
+        if let Some(rtpmap) = &self.rtpmap {
+            return Some(rtpmap.encoding.to_uppercase());
+        }
+        match self.payload_type()? {
+            0 => Some("PCMU".to_string()),
+            8 => Some("PCMA".to_string()),
+            14 => Some("MPA".to_string()),
+            26 => Some("JPEG".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Частота тактов RTP
+    pub fn clock_rate(&self) -> Option<u32> {
+        match (&self.rtpmap, self.payload_type()) {
+            (Some(rtpmap), _) => Some(rtpmap.clock_rate),
+            (None, Some(0 | 8)) => Some(8000),
+            (None, Some(14 | 26)) => Some(90000),
+            _ => None,
+        }
+    }
+
+    /// URL для SETUP: абсолютный `a=control` или относительно базового URL
+    pub fn control_url(&self, base: &str) -> String {
+        resolve_control(base, self.control.as_deref())
+    }
+}
+
+/// Описание сессии
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionDescription {
+    pub session_name: String,
+    pub control: Option<String>,
+    pub media: Vec<MediaDescription>,
+}
+
+impl SessionDescription {
+    /// Разбор текста SDP (строки `тип=значение`)
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut session = SessionDescription {
+            session_name: String::new(),
+            control: None,
+            media: Vec::new(),
+        };
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (kind, value) = line
+                .split_once('=')
+                .ok_or_else(|| SurveillanceError::rtsp_error(Detail::new("sdp_line_invalid").with("line", line)))?;
+
+            match kind {
+                "s" => session.session_name = value.to_string(),
+                "m" => session.media.push(parse_media_line(value)?),
+                "b" => {
+                    if let (Some(media), Some(("AS", kbps))) = (session.media.last_mut(), value.split_once(':')) {
+                        media.bandwidth_kbps = kbps.trim().parse().ok();
+                    }
+                }
+                "a" => {
+                    let (name, attribute) = value.split_once(':').unwrap_or((value, ""));
+                    match session.media.last_mut() {
+                        Some(media) => apply_media_attribute(media, name, attribute),
+                        None if name == "control" => session.control = Some(attribute.to_string()),
+                        None => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if session.media.is_empty() {
+            return Err(SurveillanceError::rtsp_error("sdp_no_media"));
+        }
+        Ok(session)
+    }
+
+    /// Базовый URL для SETUP и PLAY с учётом `a=control` уровня сессии
+    pub fn session_url(&self, base: &str) -> String {
+        resolve_control(base, self.control.as_deref())
+    }
+}
+
+fn parse_media_line(value: &str) -> Result<MediaDescription> {
+    let invalid = || SurveillanceError::rtsp_error(Detail::new("sdp_line_invalid").with("line", format!("m={}", value)));
+    let mut fields = value.split_whitespace();
+    let kind = fields.next().ok_or_else(invalid)?;
+    // Порт может быть указан с числом портов: 5004/2
+    let port = fields
+        .next()
+        .and_then(|port| port.split('/').next())
+        .and_then(|port| port.parse().ok())
+        .ok_or_else(invalid)?;
+    let protocol = fields.next().ok_or_else(invalid)?;
+    let payload_types = fields.filter_map(|format| format.parse().ok()).collect();
+    Ok(MediaDescription::new(kind, port, protocol, payload_types))
+}
+
+fn apply_media_attribute(media: &mut MediaDescription, name: &str, value: &str) {
+    match name {
+        "control" => media.control = Some(value.to_string()),
+        "framerate" => media.framerate = value.trim().parse().ok(),
+        "rtpmap" => {
+            let Some((payload_type, codec)) = value.split_once(' ') else {
+                return;
+            };
+            let Ok(payload_type) = payload_type.parse::<u8>() else {
+                return;
+            };
+            // Атрибуты относятся к основному типу нагрузки
+            if media.payload_type() != Some(payload_type) {
+                return;
+            }
+            let mut parts = codec.trim().split('/');
+            let encoding = parts.next().unwrap_or_default().to_string();
+            let clock_rate = parts.next().and_then(|rate| rate.parse().ok()).unwrap_or(90000);
+            let channels = parts.next().and_then(|channels| channels.parse().ok());
+            media.rtpmap = Some(RtpMap { payload_type, encoding, clock_rate, channels });
+        }
+        "fmtp" => {
+            let Some((payload_type, parameters)) = value.split_once(' ') else {
+                return;
+            };
+            if payload_type.parse::<u8>().ok() != media.payload_type() {
+                return;
+            }
+            for parameter in parameters.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+                let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+                media.fmtp.insert(key.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Разрешение `a=control` относительно базового URL (RFC 2326, C.1.1)
+fn resolve_control(base: &str, control: Option<&str>) -> String {
+    match control {
+        None | Some("") | Some("*") => base.to_string(),
+        Some(control) if control.to_lowercase().starts_with("rtsp://") => control.to_string(),
+        Some(control) => format!("{}/{}", base.trim_end_matches('/'), control.trim_start_matches('/')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA_SDP: &str = "v=0\r\n\
+        o=- 1 1 IN IP4 192.168.1.10\r\n\
+        s=Media Presentation\r\n\
+        t=0 0\r\n\
+        a=control:*\r\n\
+        m=video 0 RTP/AVP 96\r\n\
+        b=AS:2048\r\n\
+        a=control:trackID=1\r\n\
+        a=rtpmap:96 H264/90000\r\n\
+        a=fmtp:96 packetization-mode=1; profile-level-id=640028; sprop-parameter-sets=Z2QAKKwbGoB4AiflwFuAgICgAAB9AAAOpgCA,aO48sA==\r\n\
+        a=framerate:25\r\n\
+        m=audio 0 RTP/AVP 8\r\n\
+        a=control:rtsp://192.168.1.10/stream/trackID=2\r\n";
+
+    #[test]
+    fn test_parse_camera_sdp() {
+        let sdp = SessionDescription::parse(CAMERA_SDP).unwrap();
+        assert_eq!(sdp.session_name, "Media Presentation");
+        assert_eq!(sdp.media.len(), 2);
+
+        let video = &sdp.media[0];
+        assert_eq!(video.kind, "video");
+        assert_eq!(video.encoding().as_deref(), Some("H264"));
+        assert_eq!(video.clock_rate(), Some(90000));
+        assert_eq!(video.bandwidth_kbps, Some(2048));
+        assert_eq!(video.framerate, Some(25.0));
+        assert_eq!(video.fmtp.get("packetization-mode").map(String::as_str), Some("1"));
+
+        // Статический тип 8 без rtpmap — G.711 A-law
+        let audio = &sdp.media[1];
+        assert_eq!(audio.encoding().as_deref(), Some("PCMA"));
+        assert_eq!(audio.clock_rate(), Some(8000));
+    }
+
+    #[test]
+    fn test_control_urls() {
+        let sdp = SessionDescription::parse(CAMERA_SDP).unwrap();
+        let base = "rtsp://192.168.1.10/stream/";
+        assert_eq!(sdp.session_url(base), base);
+        assert_eq!(sdp.media[0].control_url(base), "rtsp://192.168.1.10/stream/trackID=1");
+        assert_eq!(sdp.media[1].control_url(base), "rtsp://192.168.1.10/stream/trackID=2");
+
+        assert!(SessionDescription::parse("v=0\r\ns=empty\r\n").is_err());
+    }
+}
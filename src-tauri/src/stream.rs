@@ -0,0 +1,868 @@
+// stream.rs - Контроль процессов FFmpeg, преобразующих RTSP камер в HLS двух качеств
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use crate::camera_status::{self, CameraObservation};
+use crate::config::{Camera, Settings};
+use crate::error::{CommandError, SurveillanceError, Result};
+use crate::i18n::Detail;
+use crate::reconnect::{CircuitState, ReconnectPolicy, ReconnectStatus, Reconnector};
+
+pub mod hls;
+pub mod probe;
+pub mod rtp;
+pub mod sps;
+pub mod ts;
+
+/// Сколько ждать выхода FFmpeg после команды `q`, прежде чем завершить принудительно
+const GRACEFUL_STOP: Duration = Duration::from_secs(3);
+
+/// Качество потока: сетка или полный экран
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamQuality {
+    Low,
+    High,
+}
+
+impl std::fmt::Display for StreamQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl StreamQuality {
+    pub const ALL: [Self; 2] = [Self::Low, Self::High];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::High => "high",
+        }
+    }
+}
+
+/// Параметры запуска FFmpeg
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    pub ffmpeg_path: PathBuf,
+    pub output_dir: PathBuf,
+    pub connection_timeout: Duration, // Время до первого кадра
+    pub reconnect: ReconnectPolicy,
+    pub low_quality_resolution: String,
+    pub graceful_stop: Duration,
+}
+
+impl StreamOptions {
+    pub fn from_settings(settings: &Settings, output_dir: &Path) -> Self {
+        Self {
+            ffmpeg_path: PathBuf::from(&settings.ffmpeg_path),
+            output_dir: output_dir.to_path_buf(),
+            connection_timeout: Duration::from_secs(settings.connection_timeout.into()),
+            reconnect: ReconnectPolicy::from_settings(settings),
+            low_quality_resolution: settings.low_quality_resolution.clone(),
+            graceful_stop: GRACEFUL_STOP,
+        }
+    }
+
+    /// Плейлист HLS потока
+    pub fn playlist_path(&self, camera_id: u32, quality: StreamQuality) -> PathBuf {
+        self.output_dir.join(format!("{}.m3u8", stream_name(camera_id, quality)))
+    }
+}
+
+fn stream_name(camera_id: u32, quality: StreamQuality) -> String {
+    format!("{}_{}", camera_id, quality.as_str())
+}
+
+/// Источник и кодек видео выхода: сетка перекодируется в малое разрешение,
+/// полный экран копирует основной поток камеры без перекодирования
+fn video_args(quality: StreamQuality) -> &'static [&'static str] {
+    match quality {
+        StreamQuality::Low => &[
+            "-map", "[low]",
+            "-c:v", "libx264",
+            "-preset", "ultrafast",
+            "-tune", "zerolatency",
+            "-r", "15",
+            "-b:v", "1M",
+            "-maxrate", "1M",
+            "-bufsize", "2M",
+        ],
+        StreamQuality::High => &["-map", "0:v:0", "-c:v", "copy"],
+    }
+}
+
+/// Звук камеры (если есть) идёт в оба выхода: громкость и выключение применяет
+/// webview по состоянию `AudioState`, поэтому поток не перезапускается
+const AUDIO_ARGS: &[&str] = &["-map", "0:a:0?", "-c:a", "aac", "-b:a", "64k"];
+
+/// Аргументы FFmpeg: одно подключение к камере, два выхода HLS.
+///
+/// Кодируется только поток сетки (параметры те же, что у менеджера потоков
+/// на Node); поток полного экрана — копия видео камеры, поэтому сетка из
+/// многих камер не кодирует каждую из них в 1080p.
+pub fn build_ffmpeg_args(camera: &Camera, options: &StreamOptions) -> Vec<String> {
+    let filter = format!("[0:v]scale=s={}[low]", options.low_quality_resolution);
+
+    let mut args: Vec<String> = [
+        "-hide_banner",
+        "-rtsp_transport", "tcp",
+        "-timeout", &options.connection_timeout.as_micros().to_string(),
+        "-i", &camera.rtsp_link,
+        "-filter_complex", &filter,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+
+    for quality in StreamQuality::ALL {
+        let segments = options
+            .output_dir
+            .join(format!("{}_%03d.ts", stream_name(camera.id, quality)));
+        args.extend(video_args(quality).iter().chain(AUDIO_ARGS).map(|arg| arg.to_string()));
+        args.extend(
+            [
+                "-fflags", "+genpts",
+                "-avoid_negative_ts", "make_zero",
+                "-f", "hls",
+                "-hls_time", "2",
+                "-hls_list_size", "3",
+                "-hls_flags", "delete_segments+independent_segments",
+                "-hls_segment_filename", &segments.to_string_lossy(),
+                &options.playlist_path(camera.id, quality).to_string_lossy(),
+            ]
+            .iter()
+            .map(|arg| arg.to_string()),
+        );
+    }
+    args
+}
+
+/// Состояние потока
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamState {
+    Connecting,
+    Streaming,
+    Retrying,
+    Failed, // Попытки исчерпаны, камера проверяется с медленным интервалом
+    Stopped,
+}
+
+/// Показатели из строки прогресса FFmpeg
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct StreamProgress {
+    pub frame: u64,
+    pub fps: f32,
+    pub bitrate_kbps: Option<f32>,
+}
+
+/// Состояние потока для интерфейса
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamStatus {
+    pub camera_id: u32,
+    /// Качество, которое показывает интерфейс
+    pub quality: StreamQuality,
+    pub state: StreamState,
+    pub attempt: u32,
+    pub pid: Option<u32>,
+    /// Плейлист выбранного качества
+    pub playlist: PathBuf,
+    /// Плейлисты всех качеств одного процесса
+    pub renditions: BTreeMap<StreamQuality, PathBuf>,
+    pub started_at: DateTime<Utc>,
+    pub streaming_since: Option<DateTime<Utc>>,
+    pub progress: Option<StreamProgress>,
+    /// Последняя ошибка с кодом и параметрами каталога
+    pub last_error: Option<CommandError>,
+    pub reconnect: ReconnectStatus,
+}
+
+/// Переподключение потока для команды состояния
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StreamReconnectStatus {
+    pub camera_id: u32,
+    pub quality: StreamQuality,
+    pub state: StreamState,
+    #[serde(flatten)]
+    pub reconnect: ReconnectStatus,
+}
+
+/// Разбор строки прогресса: `frame=  120 fps= 25 ... bitrate= 812.3kbits/s`
+pub fn parse_progress(line: &str) -> Option<StreamProgress> {
+    let value = |key: &str| {
+        let start = line.find(key)? + key.len();
+        line[start..].split_whitespace().next()
+    };
+
+    let frame = value("frame=")?.parse().ok()?;
+    let fps = value("fps=").and_then(|fps| fps.parse().ok()).unwrap_or(0.0);
+    let bitrate_kbps = value("bitrate=").and_then(|bitrate| bitrate.trim_end_matches("kbits/s").parse().ok());
+    Some(StreamProgress { frame, fps, bitrate_kbps })
+}
+
+/// Сообщения FFmpeg, по которым понятна причина сбоя
+pub fn classify_stderr(line: &str) -> Option<SurveillanceError> {
+    if line.contains("Connection refused") {
+        Some(SurveillanceError::network_error("camera_connection_refused"))
+    } else if line.contains("No route to host") {
+        Some(SurveillanceError::network_error("camera_no_route"))
+    } else if line.contains("Connection timed out") {
+        Some(SurveillanceError::ConnectionTimeout)
+    } else if line.contains("401 Unauthorized") {
+        Some(SurveillanceError::CameraAuthFailed)
+    } else if line.contains("404 Not Found") {
+        Some(SurveillanceError::rtsp_error("camera_stream_not_found"))
+    } else {
+        None
+    }
+}
+
+/// Чтение stderr FFmpeg по строкам: прогресс завершается `\r`, сообщения — `\n`
+struct StderrLines<R> {
+    reader: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> StderrLines<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, buffer: Vec::new() }
+    }
+
+    async fn next_line(&mut self) -> std::io::Result<Option<String>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\r' || *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line[..end]).trim().to_string();
+                if !line.is_empty() {
+                    return Ok(Some(line));
+                }
+                continue;
+            }
+
+            let mut chunk = [0u8; 1024];
+            let read = self.reader.read(&mut chunk).await?;
+            if read == 0 {
+                let rest = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).trim().to_string();
+                return Ok((!rest.is_empty()).then_some(rest));
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+/// Итог одного запуска FFmpeg
+enum RunOutcome {
+    Stopped,
+    Failed { error: SurveillanceError },
+}
+
+struct StreamHandle {
+    status: Arc<Mutex<StreamStatus>>,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+/// Запущенные потоки по камерам; задача потока смотрит сюда при завершении
+type StreamRegistry = Arc<Mutex<HashMap<u32, StreamHandle>>>;
+
+/// Контроль процессов FFmpeg: по процессу на камеру, оба качества из одного подключения
+pub struct StreamSupervisor {
+    options: Mutex<StreamOptions>,
+    streams: StreamRegistry,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>> {
+    mutex.lock().map_err(|e| SurveillanceError::internal_error(Detail::new("lock_poisoned").with("error", e.to_string())))
+}
+
+fn update(status: &Mutex<StreamStatus>, change: impl FnOnce(&mut StreamStatus)) {
+    if let Ok(mut status) = status.lock() {
+        change(&mut status);
+    }
+}
+
+/// Выбор показываемого качества: оба плейлиста уже пишутся одним процессом
+fn select_quality(status: &mut StreamStatus, quality: StreamQuality) {
+    if let Some(playlist) = status.renditions.get(&quality) {
+        status.quality = quality;
+        status.playlist = playlist.clone();
+    }
+}
+
+fn snapshot(status: &Mutex<StreamStatus>) -> Result<StreamStatus> {
+    Ok(lock(status)?.clone())
+}
+
+impl StreamSupervisor {
+    pub fn new(options: StreamOptions) -> Self {
+        Self {
+            options: Mutex::new(options),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Новые таймауты и повторы применяются к следующим запускам
+    pub fn apply_settings(&self, settings: &Settings) -> Result<()> {
+        let mut options = lock(&self.options)?;
+        let output_dir = options.output_dir.clone();
+        let graceful_stop = options.graceful_stop;
+        *options = StreamOptions { graceful_stop, ..StreamOptions::from_settings(settings, &output_dir) };
+        Ok(())
+    }
+
+    /// Запуск потока; у работающего потока только переключается качество
+    pub fn start(&self, camera: &Camera, quality: StreamQuality) -> Result<StreamStatus> {
+        if !camera.enabled {
+            return Err(SurveillanceError::CameraUnavailable { camera_id: camera.id });
+        }
+
+        let mut streams = lock(&self.streams)?;
+        if let Some(handle) = streams.get(&camera.id) {
+            if !handle.task.is_finished() {
+                update(&handle.status, |status| select_quality(status, quality));
+                return snapshot(&handle.status);
+            }
+        }
+
+        let options = lock(&self.options)?.clone();
+        std::fs::create_dir_all(&options.output_dir)?;
+        let reconnector = Reconnector::new(options.reconnect.clone());
+
+        let status = Arc::new(Mutex::new(StreamStatus {
+            camera_id: camera.id,
+            quality,
+            state: StreamState::Connecting,
+            attempt: 0,
+            pid: None,
+            playlist: options.playlist_path(camera.id, quality),
+            renditions: StreamQuality::ALL
+                .into_iter()
+                .map(|quality| (quality, options.playlist_path(camera.id, quality)))
+                .collect(),
+            started_at: Utc::now(),
+            streaming_since: None,
+            progress: None,
+            last_error: None,
+            reconnect: reconnector.status().clone(),
+        }));
+        let (stop, stop_signal) = watch::channel(false);
+        let task = tokio::spawn(supervise(
+            camera.clone(),
+            options,
+            reconnector,
+            status.clone(),
+            stop_signal,
+            self.streams.clone(),
+        ));
+
+        log::info!("Запуск потока {} ({})", camera.camera_name, quality);
+        let current = snapshot(&status)?;
+        streams.insert(camera.id, StreamHandle { status, stop, task });
+        Ok(current)
+    }
+
+    /// Переключение показываемого качества без переподключения к камере
+    pub fn set_quality(&self, camera_id: u32, quality: StreamQuality) -> Result<StreamStatus> {
+        let streams = lock(&self.streams)?;
+        let handle = streams
+            .get(&camera_id)
+            .filter(|handle| !handle.task.is_finished())
+            .ok_or(SurveillanceError::StreamNotRunning { camera_id })?;
+        update(&handle.status, |status| select_quality(status, quality));
+        snapshot(&handle.status)
+    }
+
+    /// Остановка потока; возвращает последнее состояние, если поток был запущен
+    pub async fn stop(&self, camera_id: u32) -> Result<Option<StreamStatus>> {
+        let handle = lock(&self.streams)?.remove(&camera_id);
+        let Some(handle) = handle else {
+            return Ok(None);
+        };
+
+        handle.stop.send(true).ok();
+        if let Err(e) = handle.task.await {
+            // Файлы обычно удаляет сама задача, аварийно завершённая — не успела
+            log::error!("Задача потока {} завершилась аварийно: {}", camera_id, e);
+            remove_output(&lock(&self.options)?.output_dir, camera_id)?;
+        }
+
+        Ok(Some(snapshot(&handle.status)?))
+    }
+
+    /// Остановка всех потоков (при выходе из приложения)
+    pub async fn stop_all(&self) -> Result<()> {
+        let camera_ids: Vec<u32> = lock(&self.streams)?.keys().copied().collect();
+        for camera_id in camera_ids {
+            self.stop(camera_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Сигнал остановки всем потокам без ожидания FFmpeg (выход пользователя);
+    /// задачи завершаются и удаляют файлы в фоне
+    pub fn signal_stop_all(&self) -> Result<()> {
+        for (camera_id, handle) in lock(&self.streams)?.drain() {
+            log::info!("Остановка потока камеры {}", camera_id);
+            handle.stop.send(true).ok();
+        }
+        Ok(())
+    }
+
+    /// Остановка потоков камер, удалённых из конфигурации или отключённых
+    pub async fn retain_cameras(&self, cameras: &[Camera]) -> Result<()> {
+        let camera_ids: Vec<u32> = lock(&self.streams)?
+            .keys()
+            .copied()
+            .filter(|camera_id| !cameras.iter().any(|camera| camera.id == *camera_id && camera.enabled))
+            .collect();
+        for camera_id in camera_ids {
+            log::info!("Поток камеры {} остановлен: камера удалена или отключена", camera_id);
+            self.stop(camera_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Состояние всех потоков
+    pub fn statuses(&self) -> Result<Vec<StreamStatus>> {
+        let streams = lock(&self.streams)?;
+        let mut statuses = streams
+            .values()
+            .map(|handle| snapshot(&handle.status))
+            .collect::<Result<Vec<_>>>()?;
+        statuses.sort_by_key(|status| status.camera_id);
+        Ok(statuses)
+    }
+
+    /// Состояние переподключения всех потоков
+    pub fn reconnect_statuses(&self) -> Result<Vec<StreamReconnectStatus>> {
+        Ok(self
+            .statuses()?
+            .into_iter()
+            .map(|status| StreamReconnectStatus {
+                camera_id: status.camera_id,
+                quality: status.quality,
+                state: status.state,
+                reconnect: status.reconnect,
+            })
+            .collect())
+    }
+
+}
+
+/// Удаление плейлистов и сегментов обоих качеств остановленного потока
+fn remove_output(output_dir: &Path, camera_id: u32) -> Result<()> {
+    let entries = match std::fs::read_dir(output_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let belongs = StreamQuality::ALL.into_iter().any(|quality| {
+            let prefix = stream_name(camera_id, quality);
+            name == format!("{}.m3u8", prefix) || name.starts_with(&format!("{}_", prefix))
+        });
+        if belongs {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                log::warn!("Не удалось удалить файл потока {}: {}", name, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Поток до остановки, затем удаление его файлов
+async fn supervise(
+    camera: Camera,
+    options: StreamOptions,
+    reconnector: Reconnector,
+    status: Arc<Mutex<StreamStatus>>,
+    stop: watch::Receiver<bool>,
+    streams: StreamRegistry,
+) {
+    run_until_stopped(&camera, &options, reconnector, &status, stop).await;
+
+    // Пока FFmpeg завершался, камеру могли запустить снова: файлы уже принадлежат новому процессу
+    let Ok(streams) = streams.lock() else {
+        return;
+    };
+    if !streams.contains_key(&camera.id) {
+        if let Err(e) = remove_output(&options.output_dir, camera.id) {
+            log::warn!("Не удалось удалить файлы потока {}: {}", camera.camera_name, e);
+        }
+    }
+}
+
+/// Перезапуски FFmpeg до остановки: растущая задержка, затем медленные повторы
+async fn run_until_stopped(
+    camera: &Camera,
+    options: &StreamOptions,
+    mut reconnector: Reconnector,
+    status: &Mutex<StreamStatus>,
+    mut stop: watch::Receiver<bool>,
+) {
+    loop {
+        reconnector.try_attempt();
+        update(status, |status| {
+            status.state = StreamState::Connecting;
+            status.attempt = reconnector.status().consecutive_failures + 1;
+            status.reconnect = reconnector.status().clone();
+        });
+        camera_status::report(camera.id, CameraObservation::Connecting);
+
+        let error = match run_once(camera, options, &mut reconnector, status, &mut stop).await {
+            RunOutcome::Stopped => {
+                update(status, |status| {
+                    status.state = StreamState::Stopped;
+                    status.pid = None;
+                });
+                log::info!("Поток {} остановлен", camera.camera_name);
+                camera_status::report(camera.id, CameraObservation::Stopped);
+                return;
+            }
+            RunOutcome::Failed { error } => error,
+        };
+
+        let attempt = reconnector.status().consecutive_failures + 1;
+        log::warn!("Поток {} прерван (попытка {}): {}", camera.camera_name, attempt, error);
+        crate::error_journal::report(&error, "stream", None);
+        camera_status::report(camera.id, CameraObservation::Failed { error: error.clone() });
+
+        let was_closed = reconnector.status().circuit == CircuitState::Closed;
+        let delay = reconnector.record_failure(&error);
+        let circuit_open = reconnector.status().circuit == CircuitState::Open;
+        update(status, |status| {
+            status.state = if circuit_open { StreamState::Failed } else { StreamState::Retrying };
+            status.pid = None;
+            status.streaming_since = None;
+            status.last_error = Some(CommandError::new(&error, crate::session_locale()));
+            status.reconnect = reconnector.status().clone();
+        });
+
+        // Оператор узнаёт о камере один раз, пробные попытки не повторяют уведомление
+        if circuit_open && was_closed {
+            log::error!(
+                "Поток {} не восстановлен после {} попыток, следующая проверка через {:?}",
+                camera.camera_name, attempt, delay
+            );
+            crate::notifications::report_camera(camera.id, &error, "stream");
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stop.changed() => {
+                update(status, |status| status.state = StreamState::Stopped);
+                camera_status::report(camera.id, CameraObservation::Stopped);
+                return;
+            }
+        }
+    }
+}
+
+/// Один запуск FFmpeg: ожидание первого кадра, разбор stderr, остановка по сигналу
+async fn run_once(
+    camera: &Camera,
+    options: &StreamOptions,
+    reconnector: &mut Reconnector,
+    status: &Mutex<StreamStatus>,
+    stop: &mut watch::Receiver<bool>,
+) -> RunOutcome {
+    let spawned = Command::new(&options.ffmpeg_path)
+        .args(build_ffmpeg_args(camera, options))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            let error = SurveillanceError::internal_error(Detail::new("ffmpeg_start_failed").with("error", e.to_string()));
+            return RunOutcome::Failed { error };
+        }
+    };
+    update(status, |status| status.pid = child.id());
+
+    let Some(stderr) = child.stderr.take() else {
+        let error = SurveillanceError::internal_error("ffmpeg_output_unavailable");
+        return RunOutcome::Failed { error };
+    };
+    let mut lines = StderrLines::new(stderr);
+    let first_frame = tokio::time::sleep(options.connection_timeout);
+    tokio::pin!(first_frame);
+
+    let mut streamed = false;
+    let mut last_error = None;
+    loop {
+        tokio::select! {
+            _ = stop.changed() => {
+                stop_child(&mut child, options.graceful_stop).await;
+                return RunOutcome::Stopped;
+            }
+            _ = &mut first_frame, if !streamed => {
+                child.kill().await.ok();
+                let error = last_error.unwrap_or(SurveillanceError::ConnectionTimeout);
+                return RunOutcome::Failed { error };
+            }
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    if let Some(progress) = parse_progress(&line) {
+                        if !streamed {
+                            streamed = true;
+                            // После успешного показа отсчёт попыток начинается заново
+                            reconnector.record_success();
+                            log::info!("Поток {} подключен", camera.camera_name);
+                        }
+                        update(status, |status| {
+                            if status.state != StreamState::Streaming {
+                                status.reconnect = reconnector.status().clone();
+                                status.state = StreamState::Streaming;
+                                status.streaming_since = Some(Utc::now());
+                                status.last_error = None;
+                            }
+                            status.progress = Some(progress);
+                        });
+                        camera_status::report(camera.id, CameraObservation::Frame {
+                            fps: progress.fps,
+                            bitrate_kbps: progress.bitrate_kbps,
+                        });
+                    } else if let Some(error) = classify_stderr(&line) {
+                        last_error = Some(error);
+                    }
+                }
+                // stderr закрыт: процесс завершается
+                Ok(None) | Err(_) => break,
+            }
+        }
+    }
+
+    let error = match child.wait().await {
+        Ok(exit) => last_error.unwrap_or_else(|| {
+            SurveillanceError::rtsp_error(Detail::new("ffmpeg_exited").with("status", exit.to_string()))
+        }),
+        Err(e) => SurveillanceError::internal_error(Detail::new("ffmpeg_wait_failed").with("error", e.to_string())),
+    };
+    RunOutcome::Failed { error }
+}
+
+/// Мягкая остановка: команда `q` в stdin, затем принудительное завершение
+async fn stop_child(child: &mut Child, grace: Duration) {
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(b"q").await.ok();
+    }
+    if tokio::time::timeout(grace, child.wait()).await.is_err() {
+        log::warn!("FFmpeg не завершился за {:?}, процесс будет убит", grace);
+        child.kill().await.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(id: u32) -> Camera {
+        Camera {
+            id,
+            camera_name: format!("Камера {}", id),
+            apartment_name: "Тест".to_string(),
+            rtsp_link: format!("rtsp://192.168.1.{}/stream", id),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_single_input_two_renditions() {
+        let options = StreamOptions {
+            ffmpeg_path: PathBuf::from("ffmpeg"),
+            output_dir: PathBuf::from("/tmp/hls"),
+            connection_timeout: Duration::from_secs(5),
+            reconnect: ReconnectPolicy::from_settings(&Settings::default()),
+            low_quality_resolution: "640x480".to_string(),
+            graceful_stop: Duration::from_secs(2),
+        };
+        let args = build_ffmpeg_args(&camera(4), &options);
+
+        assert_eq!(args.iter().filter(|arg| *arg == "-i").count(), 1);
+        let filter = args.iter().position(|arg| arg == "-filter_complex").unwrap() + 1;
+        assert_eq!(args[filter], "[0:v]scale=s=640x480[low]");
+        assert!(args.windows(2).any(|pair| pair == ["-map", "[low]"]));
+
+        // Кодируется только сетка: полный экран копирует видео камеры
+        assert_eq!(args.iter().filter(|arg| *arg == "libx264").count(), 1);
+        let high = args.iter().position(|arg| arg == "0:v:0").unwrap();
+        assert_eq!(args[high + 1..high + 3], ["-c:v", "copy"]);
+
+        // Звук камеры передаётся в оба выхода
+        assert!(!args.contains(&"-an".to_string()));
+        assert_eq!(args.windows(2).filter(|pair| pair == &["-map", "0:a:0?"]).count(), 2);
+        assert_eq!(args.windows(2).filter(|pair| pair == &["-c:a", "aac"]).count(), 2);
+        assert!(args.contains(&"/tmp/hls/4_low.m3u8".to_string()));
+        assert!(args.contains(&"/tmp/hls/4_high.m3u8".to_string()));
+    }
+
+    #[test]
+    fn test_parse_stderr() {
+        let progress = parse_progress("frame=  120 fps= 25 q=28.0 size=     512kB time=00:00:04.80 bitrate= 873.8kbits/s speed=1.0x").unwrap();
+        assert_eq!(progress.frame, 120);
+        assert_eq!(progress.fps, 25.0);
+        assert_eq!(progress.bitrate_kbps, Some(873.8));
+        assert!(parse_progress("Input #0, rtsp, from 'rtsp://camera':").is_none());
+
+        assert!(matches!(
+            classify_stderr("[tcp @ 0x55] Connection to tcp://10.0.0.5:554 failed: Connection timed out"),
+            Some(SurveillanceError::ConnectionTimeout)
+        ));
+        assert!(matches!(
+            classify_stderr("[rtsp @ 0x55] method DESCRIBE failed: 401 Unauthorized"),
+            Some(SurveillanceError::CameraAuthFailed)
+        ));
+        assert!(classify_stderr("Stream mapping:").is_none());
+    }
+
+    /// Поддельный ffmpeg: shell-скрипт, который пишет в stderr то же, что настоящий
+    #[cfg(unix)]
+    fn fake_ffmpeg(dir: &Path, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    async fn wait_for(supervisor: &StreamSupervisor, state: StreamState) -> StreamStatus {
+        for _ in 0..200 {
+            let status = supervisor.statuses().unwrap().remove(0);
+            if status.state == state {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("поток не перешёл в состояние {:?}", state);
+    }
+
+    // Оба сценария в одном тесте: скрипты записываются до запуска процессов
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fake_ffmpeg_supervision() {
+        let dir = std::env::temp_dir().join(format!("streams-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stopped_marker = dir.join("stopped");
+        let attempts_log = dir.join("attempts");
+
+        let healthy = fake_ffmpeg(&dir, "ffmpeg-ok", &format!(
+            "printf 'Input #0, rtsp\\nframe=   25 fps= 25 q=28.0 size=  128kB bitrate= 512.0kbits/s\\r' >&2\n\
+             read command\n\
+             echo \"$command\" > '{}'",
+            stopped_marker.display()
+        ));
+        let refused = fake_ffmpeg(&dir, "ffmpeg-refused", &format!(
+            "echo attempt >> '{}'\n\
+             echo \"[tcp @ 0x1] Connection to tcp://camera:554 failed: Connection refused\" >&2\n\
+             exit 1",
+            attempts_log.display()
+        ));
+
+        let options = StreamOptions {
+            ffmpeg_path: healthy,
+            output_dir: dir.join("hls"),
+            connection_timeout: Duration::from_secs(5),
+            reconnect: ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(20),
+                multiplier: 2.0,
+                jitter: 0.0,
+                max_attempts: 3,
+                slow_retry_interval: Duration::from_secs(3600),
+            },
+            low_quality_resolution: "640x480".to_string(),
+            graceful_stop: Duration::from_secs(2),
+        };
+
+        // Исправный поток: первый кадр, затем мягкая остановка командой q
+        let supervisor = StreamSupervisor::new(options.clone());
+        supervisor.start(&camera(1), StreamQuality::Low).unwrap();
+        let streaming = wait_for(&supervisor, StreamState::Streaming).await;
+        assert_eq!(streaming.progress.unwrap().frame, 25);
+        assert!(streaming.pid.is_some());
+
+        // Переход в полноэкранный режим не перезапускает FFmpeg
+        let pid = streaming.pid;
+        let high = supervisor.set_quality(1, StreamQuality::High).unwrap();
+        assert_eq!((high.quality, high.pid), (StreamQuality::High, pid));
+        assert_eq!(high.playlist, options.playlist_path(1, StreamQuality::High));
+        let low = supervisor.start(&camera(1), StreamQuality::Low).unwrap();
+        assert_eq!((low.quality, low.pid), (StreamQuality::Low, pid));
+        assert_eq!(supervisor.statuses().unwrap().len(), 1);
+
+        let stopped = supervisor.stop(1).await.unwrap().unwrap();
+        assert_eq!(stopped.state, StreamState::Stopped);
+        assert_eq!(std::fs::read_to_string(&stopped_marker).unwrap().trim(), "q");
+        assert!(supervisor.statuses().unwrap().is_empty());
+
+        // Выход пользователя: сигнал без ожидания, файлы удаляет завершившаяся задача
+        std::fs::remove_file(&stopped_marker).unwrap();
+        supervisor.start(&camera(1), StreamQuality::Low).unwrap();
+        wait_for(&supervisor, StreamState::Streaming).await;
+        let playlist = options.playlist_path(1, StreamQuality::Low);
+        std::fs::write(&playlist, "#EXTM3U").unwrap();
+        supervisor.signal_stop_all().unwrap();
+        assert!(supervisor.statuses().unwrap().is_empty());
+        for _ in 0..100 {
+            if !playlist.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!playlist.exists());
+        assert_eq!(std::fs::read_to_string(&stopped_marker).unwrap().trim(), "q");
+
+        // Камера отклоняет соединение: max_attempts запусков, затем медленные повторы
+        let supervisor = StreamSupervisor::new(StreamOptions { ffmpeg_path: refused, ..options });
+        supervisor.start(&camera(2), StreamQuality::High).unwrap();
+        let failed = wait_for(&supervisor, StreamState::Failed).await;
+        assert_eq!(failed.attempt, 3);
+        let last_error = failed.last_error.unwrap();
+        assert_eq!(last_error.params["detail"], "camera_connection_refused");
+        assert!(last_error.message.contains("Соединение отклонено"));
+        assert_eq!(failed.reconnect.circuit, CircuitState::Open);
+        assert!(failed.reconnect.next_attempt_at.unwrap() > Utc::now() + chrono::Duration::minutes(59));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(std::fs::read_to_string(&attempts_log).unwrap().lines().count(), 3);
+        let reconnect = supervisor.reconnect_statuses().unwrap();
+        assert_eq!((reconnect[0].camera_id, reconnect[0].state), (2, StreamState::Failed));
+
+        // Камера удалена из конфигурации: её поток останавливается
+        supervisor.retain_cameras(&[camera(2)]).await.unwrap();
+        assert_eq!(supervisor.statuses().unwrap().len(), 1);
+        supervisor.retain_cameras(&[camera(1)]).await.unwrap();
+        assert!(supervisor.statuses().unwrap().is_empty());
+
+        assert!(matches!(
+            supervisor.set_quality(3, StreamQuality::High),
+            Err(SurveillanceError::StreamNotRunning { camera_id: 3 })
+        ));
+
+        let mut disabled = camera(3);
+        disabled.enabled = false;
+        assert!(matches!(
+            supervisor.start(&disabled, StreamQuality::Low),
+            Err(SurveillanceError::CameraUnavailable { camera_id: 3 })
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,846 @@
+// hls.rs - Встроенный упаковщик HLS/LL-HLS: сегменты MPEG-TS в памяти и раздача по протоколу приложения
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use crate::config::{Camera, Settings};
+use crate::error::{SurveillanceError, Result};
+use crate::reconnect::{ReconnectPolicy, Reconnector};
+use crate::rtsp::{RtspSession, RtspTrack, RtspTransport};
+use super::lock;
+use super::probe::nal_units;
+use super::rtp::{Depacketizer, Frame, RtpCodec, RtpPacket};
+use super::ts::{AacConfig, TsMuxer, PACKET_SIZE};
+
+/// Схема протокола приложения, по которой интерфейс получает плейлисты и сегменты
+pub const HLS_SCHEME: &str = "hls";
+
+const CLOCK_RATE: i64 = 90_000;
+
+/// Разделитель access unit; Apple требует его перед каждым кадром H.264 в MPEG-TS
+const ACCESS_UNIT_DELIMITER: [u8; 6] = [0, 0, 0, 1, 0x09, 0xF0];
+
+/// Настройки упаковщика
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HlsSettings {
+    pub segment_duration_ms: u32, // Длительность сегмента (как hls_time 2 у FFmpeg)
+    pub playlist_size: usize,     // Сегментов в плейлисте (как hls_list_size 3)
+    pub low_latency: bool,        // Части сегментов LL-HLS
+    pub part_duration_ms: u32,    // Длительность части LL-HLS
+}
+
+impl Default for HlsSettings {
+    fn default() -> Self {
+        Self {
+            segment_duration_ms: 2000,
+            playlist_size: 3,
+            low_latency: false,
+            part_duration_ms: 500,
+        }
+    }
+}
+
+impl HlsSettings {
+    fn segment_ticks(&self) -> i64 {
+        i64::from(self.segment_duration_ms.max(100)) * CLOCK_RATE / 1000
+    }
+
+    fn part_ticks(&self) -> i64 {
+        i64::from(self.part_duration_ms.clamp(50, self.segment_duration_ms.max(100))) * CLOCK_RATE / 1000
+    }
+}
+
+/// Часть сегмента LL-HLS: диапазон байт сегмента
+#[derive(Debug, Clone, PartialEq)]
+struct Part {
+    duration: f64,
+    independent: bool,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    sequence: u64,
+    duration: f64,
+    discontinuity: bool,
+    data: Vec<u8>,
+    parts: Vec<Part>,
+}
+
+/// Сегмент, в который сейчас пишутся кадры
+#[derive(Debug)]
+struct OpenSegment {
+    segment: Segment,
+    start_pts: i64,
+    part_start: usize,
+    part_start_pts: i64,
+    part_independent: bool,
+}
+
+/// Упаковщик одного потока: кадры H.264 и AAC → сегменты MPEG-TS и плейлист
+pub struct HlsPackager {
+    settings: HlsSettings,
+    muxer: TsMuxer,
+    audio_rate: Option<i64>,
+    /// SPS и PPS из SDP в Annex B; добавляются к ключевым кадрам без них
+    parameter_sets: Vec<u8>,
+    segments: VecDeque<Segment>,
+    current: Option<OpenSegment>,
+    next_sequence: u64,
+    /// Разрывов, ушедших из памяти, для EXT-X-DISCONTINUITY-SEQUENCE
+    removed_discontinuities: u64,
+    pending_discontinuity: bool,
+    waiting_keyframe: bool,
+    last_video_pts: Option<i64>,
+    frame_interval: i64,
+}
+
+impl HlsPackager {
+    pub fn new(settings: HlsSettings) -> Self {
+        Self {
+            settings,
+            muxer: TsMuxer::new(None),
+            audio_rate: None,
+            parameter_sets: Vec::new(),
+            segments: VecDeque::new(),
+            current: None,
+            next_sequence: 0,
+            removed_discontinuities: 0,
+            pending_discontinuity: false,
+            waiting_keyframe: true,
+            last_video_pts: None,
+            frame_interval: 0,
+        }
+    }
+
+    /// Начало новой сессии камеры; после переподключения метки времени начинаются заново,
+    /// поэтому следующий сегмент отмечается разрывом
+    pub fn begin_session(&mut self, audio: Option<AacConfig>, parameter_sets: Vec<u8>) {
+        if let Some(end) = self.last_video_pts.map(|pts| pts + self.frame_interval) {
+            self.close_segment(end);
+        }
+        self.pending_discontinuity = !self.segments.is_empty();
+        self.muxer = TsMuxer::new(audio);
+        self.audio_rate = audio.map(|config| i64::from(config.sample_rate()));
+        self.parameter_sets = parameter_sets;
+        self.waiting_keyframe = true;
+        self.last_video_pts = None;
+        self.frame_interval = 0;
+    }
+
+    /// Access unit H.264 (метки 90 кГц); сегменты начинаются с ключевого кадра
+    pub fn push_video(&mut self, frame: &Frame) {
+        // После потерь декодеру нужен ключевой кадр
+        if frame.discontinuity && !frame.keyframe {
+            self.waiting_keyframe = true;
+        }
+        if self.waiting_keyframe && !frame.keyframe {
+            return;
+        }
+        self.waiting_keyframe = false;
+
+        let pts = frame.timestamp;
+        if let Some(last) = self.last_video_pts.filter(|&last| pts > last) {
+            self.frame_interval = pts - last;
+        }
+        self.last_video_pts = Some(pts);
+
+        let segment_ticks = self.settings.segment_ticks();
+        if frame.keyframe && self.current.as_ref().is_some_and(|open| pts - open.start_pts >= segment_ticks) {
+            self.close_segment(pts);
+        }
+        if self.current.is_none() {
+            self.open_segment(pts);
+        }
+
+        // Часть закрывается до того, как следующий кадр превысит её длительность
+        let part_ticks = self.settings.part_ticks();
+        let Some(open) = self.current.as_mut() else {
+            return;
+        };
+        if self.settings.low_latency
+            && open.segment.data.len() > open.part_start
+            && pts - open.part_start_pts + self.frame_interval > part_ticks
+        {
+            open.segment.parts.push(Part {
+                duration: (pts - open.part_start_pts) as f64 / CLOCK_RATE as f64,
+                independent: open.part_independent,
+                start: open.part_start,
+                end: open.segment.data.len(),
+            });
+            open.part_start = open.segment.data.len();
+            open.part_start_pts = pts;
+            open.part_independent = frame.keyframe;
+            self.muxer.write_tables(&mut open.segment.data);
+        }
+
+        let mut access_unit = ACCESS_UNIT_DELIMITER.to_vec();
+        let has_sps = nal_units(&frame.data).any(|nal| nal.first().is_some_and(|header| header & 0x1F == 7));
+        if frame.keyframe && !has_sps {
+            access_unit.extend_from_slice(&self.parameter_sets);
+        }
+        access_unit.extend_from_slice(&frame.data);
+        self.muxer.write_video(&mut open.segment.data, pts, frame.keyframe, &access_unit);
+    }
+
+    /// Кадр AAC (метки в тактах частоты дискретизации); до первого сегмента видео отбрасывается
+    pub fn push_audio(&mut self, frame: &Frame) {
+        let (Some(rate), Some(open)) = (self.audio_rate, self.current.as_mut()) else {
+            return;
+        };
+        let pts = frame.timestamp * CLOCK_RATE / rate;
+        self.muxer.write_audio(&mut open.segment.data, pts, &frame.data);
+    }
+
+    /// Плейлист; без готовых сегментов (и частей LL-HLS) плеер получать нечего
+    pub fn playlist(&self) -> Option<String> {
+        let window = self.segments.len().saturating_sub(self.settings.playlist_size.max(1));
+        let listed: Vec<&Segment> = self.segments.iter().skip(window).collect();
+        let open_parts = self
+            .current
+            .as_ref()
+            .filter(|_| self.settings.low_latency)
+            .map(|open| open.segment.parts.as_slice())
+            .unwrap_or_default();
+        if listed.is_empty() && open_parts.is_empty() {
+            return None;
+        }
+
+        let longest = listed.iter().map(|segment| segment.duration).fold(0.0, f64::max);
+        let target = longest.max(self.settings.segment_duration_ms as f64 / 1000.0).ceil() as u64;
+        let first_sequence = listed
+            .first()
+            .map(|segment| segment.sequence)
+            .or_else(|| self.current.as_ref().map(|open| open.segment.sequence))
+            .unwrap_or_default();
+        let hidden_discontinuities = self.segments.iter().take(window).filter(|segment| segment.discontinuity).count();
+
+        let mut playlist = String::from("#EXTM3U\n");
+        let part_target = self.settings.part_ticks() as f64 / CLOCK_RATE as f64;
+        if self.settings.low_latency {
+            // Блокирующая перезагрузка не поддерживается: обработчик протокола синхронный
+            playlist.push_str("#EXT-X-VERSION:9\n");
+            let _ = writeln!(playlist, "#EXT-X-TARGETDURATION:{}", target);
+            let _ = writeln!(playlist, "#EXT-X-SERVER-CONTROL:PART-HOLD-BACK={:.3}", part_target * 3.0);
+            let _ = writeln!(playlist, "#EXT-X-PART-INF:PART-TARGET={:.3}", part_target);
+        } else {
+            playlist.push_str("#EXT-X-VERSION:3\n");
+            let _ = writeln!(playlist, "#EXT-X-TARGETDURATION:{}", target);
+        }
+        let _ = writeln!(playlist, "#EXT-X-MEDIA-SEQUENCE:{}", first_sequence);
+        let _ = writeln!(
+            playlist,
+            "#EXT-X-DISCONTINUITY-SEQUENCE:{}",
+            self.removed_discontinuities + hidden_discontinuities as u64
+        );
+        playlist.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+
+        for segment in &listed {
+            if segment.discontinuity {
+                playlist.push_str("#EXT-X-DISCONTINUITY\n");
+            }
+            if self.settings.low_latency {
+                write_parts(&mut playlist, segment.sequence, &segment.parts);
+            }
+            let _ = writeln!(playlist, "#EXTINF:{:.3},\n{}.ts", segment.duration, segment.sequence);
+        }
+        if let Some(open) = self.current.as_ref().filter(|_| !open_parts.is_empty()) {
+            if open.segment.discontinuity {
+                playlist.push_str("#EXT-X-DISCONTINUITY\n");
+            }
+            write_parts(&mut playlist, open.segment.sequence, open_parts);
+        }
+        Some(playlist)
+    }
+
+    /// Готовый сегмент (в памяти остаётся на один больше, чем в плейлисте)
+    pub fn segment(&self, sequence: u64) -> Option<Vec<u8>> {
+        self.segments
+            .iter()
+            .find(|segment| segment.sequence == sequence)
+            .map(|segment| segment.data.clone())
+    }
+
+    /// Часть LL-HLS готового или текущего сегмента
+    pub fn part(&self, sequence: u64, index: usize) -> Option<Vec<u8>> {
+        let segment = self
+            .segments
+            .iter()
+            .chain(self.current.as_ref().map(|open| &open.segment))
+            .find(|segment| segment.sequence == sequence)?;
+        let part = segment.parts.get(index)?;
+        Some(segment.data[part.start..part.end].to_vec())
+    }
+
+    fn open_segment(&mut self, pts: i64) {
+        let mut segment = Segment {
+            sequence: self.next_sequence,
+            duration: 0.0,
+            discontinuity: std::mem::take(&mut self.pending_discontinuity),
+            data: Vec::new(),
+            parts: Vec::new(),
+        };
+        self.next_sequence += 1;
+        self.muxer.write_tables(&mut segment.data);
+        self.current = Some(OpenSegment {
+            segment,
+            start_pts: pts,
+            part_start: 0,
+            part_start_pts: pts,
+            part_independent: true,
+        });
+    }
+
+    fn close_segment(&mut self, end_pts: i64) {
+        let Some(mut open) = self.current.take() else {
+            return;
+        };
+        if self.settings.low_latency && open.segment.data.len() > open.part_start {
+            open.segment.parts.push(Part {
+                duration: (end_pts - open.part_start_pts) as f64 / CLOCK_RATE as f64,
+                independent: open.part_independent,
+                start: open.part_start,
+                end: open.segment.data.len(),
+            });
+        }
+        open.segment.duration = (end_pts - open.start_pts) as f64 / CLOCK_RATE as f64;
+        debug_assert_eq!(open.segment.data.len() % PACKET_SIZE, 0);
+        self.segments.push_back(open.segment);
+
+        while self.segments.len() > self.settings.playlist_size.max(1) + 1 {
+            if let Some(removed) = self.segments.pop_front() {
+                self.removed_discontinuities += u64::from(removed.discontinuity);
+            }
+        }
+    }
+}
+
+fn write_parts(playlist: &mut String, sequence: u64, parts: &[Part]) {
+    for (index, part) in parts.iter().enumerate() {
+        let _ = writeln!(
+            playlist,
+            "#EXT-X-PART:DURATION={:.3},URI=\"{}.{}.ts\"{}",
+            part.duration,
+            sequence,
+            index,
+            if part.independent { ",INDEPENDENT=YES" } else { "" }
+        );
+    }
+}
+
+/// Ответ протокола приложения
+#[derive(Debug, Clone, PartialEq)]
+pub struct HlsResource {
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+type SharedPackager = Arc<Mutex<HlsPackager>>;
+type Packagers = Arc<Mutex<HashMap<u32, SharedPackager>>>;
+
+/// Опубликованные потоки камер
+#[derive(Clone, Default)]
+pub struct HlsRegistry {
+    streams: Packagers,
+}
+
+impl HlsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Публикация потока; данные удаляются, когда публикация уничтожается
+    pub fn publish(&self, camera_id: u32, packager: HlsPackager) -> Result<HlsPublication> {
+        let packager = Arc::new(Mutex::new(packager));
+        lock(&self.streams)?.insert(camera_id, packager.clone());
+        Ok(HlsPublication { camera_id, packager, streams: self.streams.clone() })
+    }
+
+    pub fn is_published(&self, camera_id: u32) -> bool {
+        lock(&self.streams).is_ok_and(|streams| streams.contains_key(&camera_id))
+    }
+
+    /// Ресурс по пути запроса: `/{camera_id}/index.m3u8`, `/{camera_id}/{sequence}.ts`
+    /// или часть LL-HLS `/{camera_id}/{sequence}.{part}.ts`
+    pub fn respond(&self, path: &str) -> Option<HlsResource> {
+        let (camera_id, file) = path.trim_start_matches('/').split_once('/')?;
+        let packager = lock(&self.streams).ok()?.get(&camera_id.parse::<u32>().ok()?)?.clone();
+        let packager = lock(&packager).ok()?;
+
+        if file == "index.m3u8" {
+            return packager.playlist().map(|playlist| HlsResource {
+                content_type: "application/vnd.apple.mpegurl",
+                body: playlist.into_bytes(),
+            });
+        }
+        let name = file.strip_suffix(".ts")?;
+        let body = match name.split_once('.') {
+            Some((sequence, part)) => packager.part(sequence.parse().ok()?, part.parse().ok()?)?,
+            None => packager.segment(name.parse().ok()?)?,
+        };
+        Some(HlsResource { content_type: "video/mp2t", body })
+    }
+}
+
+/// Поток, опубликованный в реестре, пока жива публикация
+pub struct HlsPublication {
+    camera_id: u32,
+    packager: SharedPackager,
+    streams: Packagers,
+}
+
+impl HlsPublication {
+    pub fn update<T>(&self, change: impl FnOnce(&mut HlsPackager) -> T) -> Result<T> {
+        Ok(change(&mut *lock(&self.packager)?))
+    }
+}
+
+impl Drop for HlsPublication {
+    fn drop(&mut self) {
+        if let Ok(mut streams) = self.streams.lock() {
+            // Поток мог быть опубликован заново другой публикацией
+            if streams.get(&self.camera_id).is_some_and(|current| Arc::ptr_eq(current, &self.packager)) {
+                streams.remove(&self.camera_id);
+                log::info!("Поток HLS камеры {} снят с публикации", self.camera_id);
+            }
+        }
+    }
+}
+
+/// Путь ресурса из адреса запроса без схемы, хоста и параметров
+pub fn request_path(uri: &str) -> &str {
+    let without_scheme = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    let path = without_scheme.find('/').map_or("/", |index| &without_scheme[index..]);
+    path.split(['?', '#']).next().unwrap_or(path)
+}
+
+/// Адрес плейлиста камеры для webview (Windows открывает свои протоколы через https)
+pub fn playlist_url(camera_id: u32) -> String {
+    if cfg!(windows) {
+        format!("https://{}.localhost/{}/index.m3u8", HLS_SCHEME, camera_id)
+    } else {
+        format!("{}://localhost/{}/index.m3u8", HLS_SCHEME, camera_id)
+    }
+}
+
+/// Параметры публикации
+#[derive(Debug, Clone)]
+pub struct HlsOptions {
+    pub settings: HlsSettings,
+    /// Предел на подключение и на паузу между пакетами
+    pub timeout: Duration,
+    pub transport: RtspTransport,
+    pub reconnect: ReconnectPolicy,
+}
+
+impl HlsOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            settings: settings.hls.clone(),
+            timeout: Duration::from_secs(settings.connection_timeout.max(1).into()),
+            transport: RtspTransport::Tcp,
+            reconnect: ReconnectPolicy::from_settings(settings),
+        }
+    }
+}
+
+struct PublisherHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+/// Публикация камер без FFmpeg: RTSP → кадры → сегменты в памяти
+pub struct HlsService {
+    registry: HlsRegistry,
+    options: Mutex<HlsOptions>,
+    publishers: Mutex<HashMap<u32, PublisherHandle>>,
+}
+
+impl HlsService {
+    pub fn new(options: HlsOptions) -> Self {
+        Self {
+            registry: HlsRegistry::new(),
+            options: Mutex::new(options),
+            publishers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn registry(&self) -> &HlsRegistry {
+        &self.registry
+    }
+
+    /// Новые параметры действуют для следующих запусков
+    pub fn apply_settings(&self, settings: &Settings) -> Result<()> {
+        *lock(&self.options)? = HlsOptions::from_settings(settings);
+        Ok(())
+    }
+
+    /// Запуск публикации (работающая не перезапускается); возвращает адрес плейлиста
+    pub fn start(&self, camera: &Camera) -> Result<String> {
+        if !camera.enabled {
+            return Err(SurveillanceError::CameraUnavailable { camera_id: camera.id });
+        }
+        let mut publishers = lock(&self.publishers)?;
+        if publishers.get(&camera.id).is_some_and(|handle| !handle.task.is_finished()) {
+            return Ok(playlist_url(camera.id));
+        }
+
+        let options = lock(&self.options)?.clone();
+        let publication = self.registry.publish(camera.id, HlsPackager::new(options.settings.clone()))?;
+        let (stop, stop_signal) = watch::channel(false);
+        let task = tokio::spawn(publish(camera.clone(), options, publication, stop_signal));
+        publishers.insert(camera.id, PublisherHandle { stop, task });
+        log::info!("Публикация HLS камеры {}", camera.camera_name);
+        Ok(playlist_url(camera.id))
+    }
+
+    /// Остановка публикации; сегменты удаляются вместе с задачей
+    pub async fn stop(&self, camera_id: u32) -> Result<bool> {
+        let handle = lock(&self.publishers)?.remove(&camera_id);
+        let Some(handle) = handle else {
+            return Ok(false);
+        };
+        handle.stop.send(true).ok();
+        if let Err(e) = handle.task.await {
+            log::warn!("Задача публикации HLS камеры {} завершилась с ошибкой: {}", camera_id, e);
+        }
+        Ok(true)
+    }
+
+    /// Остановка всех публикаций (выход пользователя или приложения)
+    pub async fn stop_all(&self) -> Result<()> {
+        let camera_ids: Vec<u32> = lock(&self.publishers)?.keys().copied().collect();
+        for camera_id in camera_ids {
+            self.stop(camera_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Сигнал остановки всем публикациям без ожидания TEARDOWN; задачи завершаются
+    /// в фоне, а запущенные после вызова публикации не затрагиваются
+    pub fn signal_stop_all(&self) -> Result<()> {
+        for (camera_id, handle) in lock(&self.publishers)?.drain() {
+            log::info!("Остановка публикации HLS камеры {}", camera_id);
+            handle.stop.send(true).ok();
+        }
+        Ok(())
+    }
+
+    /// Остановка публикаций камер, удалённых из конфигурации или отключённых
+    pub async fn retain_cameras(&self, cameras: &[Camera]) -> Result<()> {
+        let camera_ids: Vec<u32> = lock(&self.publishers)?
+            .keys()
+            .copied()
+            .filter(|camera_id| !cameras.iter().any(|camera| camera.id == *camera_id && camera.enabled))
+            .collect();
+        for camera_id in camera_ids {
+            log::info!("Публикация HLS камеры {} остановлена: камера удалена или отключена", camera_id);
+            self.stop(camera_id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Сессии камеры с переподключением до остановки; публикация живёт всё это время
+async fn publish(camera: Camera, options: HlsOptions, publication: HlsPublication, mut stop: watch::Receiver<bool>) {
+    let mut reconnector = Reconnector::new(options.reconnect.clone());
+    loop {
+        reconnector.try_attempt();
+        let error = match run_session(&camera, &options, &publication, &mut reconnector, &mut stop).await {
+            Ok(()) => return,
+            Err(error) => error,
+        };
+        log::warn!("Публикация HLS камеры {} прервана: {}", camera.camera_name, error);
+        crate::error_journal::report(&error, "hls", None);
+
+        let delay = reconnector.record_failure(&error);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stop.changed() => return,
+        }
+    }
+}
+
+/// Одна сессия RTSP; `Ok` — остановка по сигналу
+async fn run_session(
+    camera: &Camera,
+    options: &HlsOptions,
+    publication: &HlsPublication,
+    reconnector: &mut Reconnector,
+    stop: &mut watch::Receiver<bool>,
+) -> Result<()> {
+    let mut session = tokio::select! {
+        session = tokio::time::timeout(options.timeout, RtspSession::open(camera, options.transport, options.timeout)) => {
+            session.map_err(|_| SurveillanceError::ConnectionTimeout)??
+        }
+        _ = stop.changed() => return Ok(()),
+    };
+    let result = pump(&mut session, options, publication, reconnector, stop).await;
+    if let Err(e) = session.close().await {
+        log::debug!("TEARDOWN после публикации HLS: {}", e);
+    }
+    result
+}
+
+async fn pump(
+    session: &mut RtspSession,
+    options: &HlsOptions,
+    publication: &HlsPublication,
+    reconnector: &mut Reconnector,
+    stop: &mut watch::Receiver<bool>,
+) -> Result<()> {
+    let tracks = session.tracks();
+    let video_track = tracks
+        .iter()
+        .position(|track| track.media.kind == "video")
+        .ok_or_else(|| SurveillanceError::rtsp_error("camera_no_video"))?;
+    if RtpCodec::from_media(&tracks[video_track].media) != Some(RtpCodec::H264) {
+        return Err(SurveillanceError::UnsupportedCodec {
+            codec: tracks[video_track].media.encoding().unwrap_or_else(|| "unknown".to_string()),
+        });
+    }
+    // Звук, кроме AAC, в MPEG-TS для HLS не передаётся
+    let audio = select_audio(tracks);
+    let parameter_sets = sdp_parameter_sets(&tracks[video_track]);
+
+    let mut video = Depacketizer::new(RtpCodec::H264);
+    let mut audio_depacketizer = audio.map(|(track, codec, config)| (track, Depacketizer::new(codec), config));
+    publication.update(|packager| packager.begin_session(audio.map(|(_, _, config)| config), parameter_sets))?;
+    let mut streaming = false;
+
+    loop {
+        let packet = tokio::select! {
+            packet = tokio::time::timeout(options.timeout, session.next_packet()) => {
+                packet.map_err(|_| SurveillanceError::ConnectionTimeout)??
+            }
+            _ = stop.changed() => return Ok(()),
+        };
+        if packet.rtcp {
+            continue;
+        }
+        let Ok(rtp) = RtpPacket::parse(&packet.payload) else {
+            continue;
+        };
+
+        if packet.track == video_track {
+            let frames = video.push(&rtp);
+            if !frames.is_empty() && !streaming {
+                streaming = true;
+                reconnector.record_success();
+            }
+            publication.update(|packager| frames.iter().for_each(|frame| packager.push_video(frame)))?;
+        } else if let Some((_, depacketizer, _)) = audio_depacketizer.as_mut().filter(|(track, _, _)| *track == packet.track) {
+            let frames = depacketizer.push(&rtp);
+            publication.update(|packager| frames.iter().for_each(|frame| packager.push_audio(frame)))?;
+        }
+    }
+}
+
+fn select_audio(tracks: &[RtspTrack]) -> Option<(usize, RtpCodec, AacConfig)> {
+    tracks.iter().enumerate().find_map(|(index, track)| {
+        let codec = RtpCodec::from_media(&track.media)?;
+        if !matches!(codec, RtpCodec::Aac { .. }) {
+            return None;
+        }
+        match AacConfig::from_media(&track.media) {
+            Ok(config) => Some((index, codec, config)),
+            Err(e) => {
+                log::warn!("Звук AAC не будет передан: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// SPS и PPS из `sprop-parameter-sets` (RFC 6184) в формате Annex B
+fn sdp_parameter_sets(track: &RtspTrack) -> Vec<u8> {
+    let mut annex_b = Vec::new();
+    let Some(sets) = track.media.fmtp.get("sprop-parameter-sets") else {
+        return annex_b;
+    };
+    for nal in sets.split(',').filter_map(|set| STANDARD.decode(set.trim()).ok()) {
+        annex_b.extend_from_slice(&[0, 0, 0, 1]);
+        annex_b.extend_from_slice(&nal);
+    }
+    annex_b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::tests::TestServer;
+
+    const SPS: [u8; 8] = [0, 0, 0, 1, 0x67, 0x42, 0xC0, 0x28];
+
+    fn frame(index: i64, keyframe: bool) -> Frame {
+        Frame {
+            timestamp: index * 3600,
+            keyframe,
+            discontinuity: false,
+            data: vec![0, 0, 0, 1, if keyframe { 0x65 } else { 0x41 }, 0x88, index as u8],
+        }
+    }
+
+    fn packager(settings: HlsSettings) -> HlsPackager {
+        let mut packager = HlsPackager::new(settings);
+        packager.begin_session(None, SPS.to_vec());
+        packager
+    }
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_segments_and_playlist() {
+        // Сегмент 1 с, ключевой кадр каждые 25 кадров по 40 мс
+        let settings = HlsSettings { segment_duration_ms: 1000, playlist_size: 2, ..HlsSettings::default() };
+        let mut packager = packager(settings);
+        assert!(packager.playlist().is_none());
+
+        packager.push_video(&frame(-1, false));
+        for index in 0..126 {
+            packager.push_video(&frame(index, index % 25 == 0));
+        }
+        let playlist = packager.playlist().unwrap();
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:3\n"), "{}", playlist);
+        assert!(playlist.contains("#EXTINF:1.000,\n3.ts\n#EXTINF:1.000,\n4.ts\n"), "{}", playlist);
+        assert!(!playlist.contains("2.ts"));
+
+        // Один сегмент за пределами плейлиста ещё отдаётся, более старые удалены
+        assert!(packager.segment(2).is_some());
+        assert!(packager.segment(1).is_none());
+        let segment = packager.segment(4).unwrap();
+        assert_eq!(segment.len() % PACKET_SIZE, 0);
+        assert_eq!((segment[0], segment[1] & 0x1F, segment[2]), (0x47, 0x00, 0x00));
+        assert!(contains(&segment, &ACCESS_UNIT_DELIMITER));
+        assert!(contains(&segment, &SPS));
+        assert!(packager.segment(5).is_none());
+    }
+
+    #[test]
+    fn test_low_latency_parts_and_discontinuity() {
+        let settings = HlsSettings {
+            segment_duration_ms: 1000,
+            playlist_size: 3,
+            low_latency: true,
+            part_duration_ms: 200,
+        };
+        let mut packager = packager(settings);
+        for index in 0..31 {
+            packager.push_video(&frame(index, index % 25 == 0));
+        }
+        let playlist = packager.playlist().unwrap();
+        assert!(playlist.contains("#EXT-X-PART-INF:PART-TARGET=0.200\n"), "{}", playlist);
+        assert!(playlist.contains("#EXT-X-PART:DURATION=0.200,URI=\"0.0.ts\",INDEPENDENT=YES\n"), "{}", playlist);
+        assert!(playlist.contains("#EXT-X-PART:DURATION=0.200,URI=\"0.1.ts\"\n"), "{}", playlist);
+        assert!(playlist.contains("#EXTINF:1.000,\n0.ts\n#EXT-X-PART:DURATION=0.200,URI=\"1.0.ts\",INDEPENDENT=YES\n"));
+
+        // Части сегмента вместе дают сам сегмент
+        let joined: Vec<u8> = (0..5).flat_map(|index| packager.part(0, index).unwrap()).collect();
+        assert_eq!(joined, packager.segment(0).unwrap());
+        assert!(packager.part(1, 0).is_some());
+        assert!(packager.part(0, 5).is_none());
+
+        // После переподключения метки начинаются заново
+        packager.begin_session(None, SPS.to_vec());
+        for index in 0..6 {
+            packager.push_video(&frame(index, index == 0));
+        }
+        let playlist = packager.playlist().unwrap();
+        assert!(playlist.contains("#EXTINF:0.240,\n1.ts\n#EXT-X-DISCONTINUITY\n#EXT-X-PART"), "{}", playlist);
+    }
+
+    #[test]
+    fn test_registry_paths_and_cleanup() {
+        assert_eq!(request_path("hls://localhost/3/index.m3u8?_HLS_msn=4"), "/3/index.m3u8");
+        assert_eq!(request_path("https://hls.localhost/3/7.ts"), "/3/7.ts");
+        assert_eq!(request_path("hls://localhost"), "/");
+
+        let registry = HlsRegistry::new();
+        let mut packager = packager(HlsSettings { segment_duration_ms: 1000, ..HlsSettings::default() });
+        for index in 0..26 {
+            packager.push_video(&frame(index, index % 25 == 0));
+        }
+        let publication = registry.publish(3, packager).unwrap();
+
+        let playlist = registry.respond("/3/index.m3u8").unwrap();
+        assert_eq!(playlist.content_type, "application/vnd.apple.mpegurl");
+        let segment = registry.respond("/3/0.ts").unwrap();
+        assert_eq!((segment.content_type, segment.body.len() % PACKET_SIZE), ("video/mp2t", 0));
+        for missing in ["/3/9.ts", "/3/0.0.ts", "/4/index.m3u8", "/3/playlist.m3u8", "/x/0.ts", "/3"] {
+            assert!(registry.respond(missing).is_none(), "{}", missing);
+        }
+
+        // Новая публикация той же камеры не снимается старой
+        let replacement = registry.publish(3, HlsPackager::new(HlsSettings::default())).unwrap();
+        drop(publication);
+        assert!(registry.is_published(3));
+        drop(replacement);
+        assert!(!registry.is_published(3));
+    }
+
+    #[tokio::test]
+    async fn test_publish_from_camera() {
+        let server = TestServer::start("admin", "secret").await;
+        let mut settings = Settings::default();
+        settings.hls.segment_duration_ms = 400;
+        let service = HlsService::new(HlsOptions::from_settings(&settings));
+        let camera = Camera {
+            id: 5,
+            camera_name: "Камера 5".to_string(),
+            apartment_name: "Тест".to_string(),
+            rtsp_link: server.url.clone(),
+            enabled: true,
+        };
+
+        let url = service.start(&camera).unwrap();
+        assert!(url.ends_with("/5/index.m3u8"));
+        let mut playlist = None;
+        for _ in 0..100 {
+            playlist = service.registry().respond("/5/index.m3u8");
+            if playlist.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let playlist = String::from_utf8(playlist.unwrap().body).unwrap();
+        assert!(playlist.contains("#EXTINF:0.400,\n0.ts"), "{}", playlist);
+        let segment = service.registry().respond("/5/0.ts").unwrap().body;
+        // SPS из SDP добавлен к ключевым кадрам
+        assert!(contains(&segment, &STANDARD.decode("Z0LAKNoBQBbk").unwrap()));
+
+        assert!(service.stop(5).await.unwrap());
+        assert!(!service.registry().is_published(5));
+        assert!(server.methods().contains(&"TEARDOWN".to_string()));
+        assert!(!service.stop(5).await.unwrap());
+
+        // Сигнал остановки снимает публикацию, когда задача завершится
+        service.start(&camera).unwrap();
+        service.signal_stop_all().unwrap();
+        for _ in 0..100 {
+            if !service.registry().is_published(5) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!service.registry().is_published(5));
+        assert!(!service.stop(5).await.unwrap());
+
+        // Отключение камеры в конфигурации останавливает её публикацию
+        service.start(&camera).unwrap();
+        service.retain_cameras(std::slice::from_ref(&camera)).await.unwrap();
+        assert!(service.registry().is_published(5));
+        let disabled = Camera { enabled: false, ..camera };
+        service.retain_cameras(&[disabled]).await.unwrap();
+        assert!(!service.registry().is_published(5));
+        assert!(!service.stop(5).await.unwrap());
+    }
+}
@@ -0,0 +1,330 @@
+// probe.rs - Проверка камеры: кодеки, разрешение, частота кадров, битрейт и задержка
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::config::{Camera, Settings};
+use crate::error::{SurveillanceError, Result};
+use crate::rtsp::{RtspSession, RtspTrack, RtspTransport};
+use crate::sdp::MediaDescription;
+use super::rtp::{Depacketizer, RtpCodec, RtpPacket};
+use super::sps::{parse_h264_sps, parse_h265_sps, SpsInfo};
+
+/// Сколько собирать пакеты после первого кадра для оценки частоты и битрейта
+const SAMPLE_DURATION: Duration = Duration::from_secs(2);
+
+/// Параметры проверки
+#[derive(Debug, Clone)]
+pub struct ProbeOptions {
+    /// Предел на подключение и на ожидание первого кадра
+    pub timeout: Duration,
+    pub sample_duration: Duration,
+    pub transport: RtspTransport,
+}
+
+impl ProbeOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            timeout: Duration::from_secs(settings.connection_timeout.into()),
+            sample_duration: SAMPLE_DURATION,
+            transport: RtspTransport::Tcp,
+        }
+    }
+}
+
+/// Видеопоток камеры
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VideoProbe {
+    pub codec: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub profile_idc: Option<u8>,
+    pub level_idc: Option<u8>,
+    /// Измеренная частота кадров; без кадров — из `a=framerate`
+    pub fps: Option<f32>,
+}
+
+/// Звуковой поток камеры
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AudioProbe {
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    /// Кодек поддерживается депакетизатором
+    pub supported: bool,
+}
+
+/// Результат проверки камеры
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProbeReport {
+    /// Адрес без учётных данных
+    pub url: String,
+    pub transport: RtspTransport,
+    pub video: VideoProbe,
+    pub audio: Option<AudioProbe>,
+    /// Оценка по принятым пакетам; без пакетов — из `b=AS`
+    pub bitrate_kbps: Option<f32>,
+    /// От начала подключения до ответа на PLAY
+    pub connect_ms: u64,
+    /// От начала подключения до первого собранного видеокадра
+    pub latency_ms: u64,
+    pub frames: u64,
+    pub lost_packets: u64,
+}
+
+/// Проверка камеры из конфигурации
+pub async fn probe_camera(camera: &Camera, options: &ProbeOptions) -> Result<ProbeReport> {
+    if !camera.enabled {
+        return Err(SurveillanceError::CameraUnavailable { camera_id: camera.id });
+    }
+    probe_url(&camera.rtsp_link, options).await
+}
+
+/// Проверка по ссылке RTSP: подключение, разбор SDP и короткий приём потока
+pub async fn probe_url(link: &str, options: &ProbeOptions) -> Result<ProbeReport> {
+    let started = Instant::now();
+    let deadline = started + options.timeout;
+
+    let mut session = tokio::time::timeout_at(deadline, RtspSession::open_url(link, options.transport, options.timeout))
+        .await
+        .map_err(|_| SurveillanceError::ConnectionTimeout)??;
+    let connect_ms = started.elapsed().as_millis() as u64;
+
+    let result = sample(&mut session, options, started, deadline).await;
+    // Ошибка TEARDOWN не влияет на результат проверки
+    if let Err(e) = session.close().await {
+        log::debug!("TEARDOWN после проверки камеры: {}", e);
+    }
+    let sample = result?;
+
+    Ok(ProbeReport {
+        url: sample.url,
+        transport: options.transport,
+        video: sample.video,
+        audio: sample.audio,
+        bitrate_kbps: sample.bitrate_kbps,
+        connect_ms,
+        latency_ms: sample.latency_ms,
+        frames: sample.frames,
+        lost_packets: sample.lost_packets,
+    })
+}
+
+struct Sample {
+    url: String,
+    video: VideoProbe,
+    audio: Option<AudioProbe>,
+    bitrate_kbps: Option<f32>,
+    latency_ms: u64,
+    frames: u64,
+    lost_packets: u64,
+}
+
+async fn sample(session: &mut RtspSession, options: &ProbeOptions, started: Instant, deadline: Instant) -> Result<Sample> {
+    let (video_track, codec) = select_video(session.tracks())?;
+    let video_media = session.tracks()[video_track].media.clone();
+    let audio = session
+        .tracks()
+        .iter()
+        .find(|track| track.media.kind == "audio")
+        .map(|track| describe_audio(&track.media));
+    let url = session.url().to_string();
+
+    let mut parameters = sdp_sps(&video_media, codec);
+    let mut depacketizer = Depacketizer::new(codec);
+    let mut first_frame: Option<(Instant, i64)> = None;
+    let mut last_timestamp = 0;
+    let mut frames = 0u64;
+    let mut bytes = 0usize;
+    let mut first_packet: Option<Instant> = None;
+    let mut sample_end = deadline;
+
+    loop {
+        let packet = match tokio::time::timeout_at(sample_end, session.next_packet()).await {
+            Ok(packet) => packet?,
+            // До первого кадра истечение срока — таймаут камеры, после — конец выборки
+            Err(_) if first_frame.is_none() => return Err(SurveillanceError::ConnectionTimeout),
+            Err(_) => break,
+        };
+        if packet.rtcp {
+            continue;
+        }
+        let now = Instant::now();
+        first_packet.get_or_insert(now);
+        bytes += packet.payload.len();
+        if packet.track != video_track {
+            continue;
+        }
+        let Ok(rtp) = RtpPacket::parse(&packet.payload) else {
+            continue;
+        };
+
+        for frame in depacketizer.push(&rtp) {
+            if parameters.is_none() {
+                parameters = nal_units(&frame.data).find_map(|nal| parse_sps(codec, nal));
+            }
+            if first_frame.is_none() {
+                first_frame = Some((now, frame.timestamp));
+                sample_end = now + options.sample_duration;
+            }
+            last_timestamp = frame.timestamp;
+            frames += 1;
+        }
+    }
+
+    let (first_frame_at, first_timestamp) = first_frame.unwrap_or((started, 0));
+    let clock_rate = video_media.clock_rate().unwrap_or(90000) as f32;
+    let measured_fps = (frames > 1 && last_timestamp > first_timestamp)
+        .then(|| (frames - 1) as f32 * clock_rate / (last_timestamp - first_timestamp) as f32);
+    let elapsed = first_packet.map(|at| at.elapsed().as_secs_f32()).unwrap_or_default();
+    let measured_bitrate = (bytes > 0 && elapsed > 0.0).then(|| bytes as f32 * 8.0 / elapsed / 1000.0);
+
+    Ok(Sample {
+        url,
+        video: VideoProbe {
+            codec: video_media.encoding().unwrap_or_default(),
+            width: parameters.map(|sps| sps.width),
+            height: parameters.map(|sps| sps.height),
+            profile_idc: parameters.map(|sps| sps.profile_idc),
+            level_idc: parameters.map(|sps| sps.level_idc),
+            fps: measured_fps.or(video_media.framerate),
+        },
+        audio,
+        bitrate_kbps: measured_bitrate.or(video_media.bandwidth_kbps.map(|kbps| kbps as f32)),
+        latency_ms: first_frame_at.duration_since(started).as_millis() as u64,
+        frames,
+        lost_packets: depacketizer.stats().lost,
+    })
+}
+
+/// Видеопоток для проверки; кодек должен поддерживаться депакетизатором
+fn select_video(tracks: &[RtspTrack]) -> Result<(usize, RtpCodec)> {
+    let (index, track) = tracks
+        .iter()
+        .enumerate()
+        .find(|(_, track)| track.media.kind == "video")
+        .ok_or_else(|| SurveillanceError::rtsp_error("camera_no_video"))?;
+    match RtpCodec::from_media(&track.media) {
+        Some(codec) if codec.is_video() => Ok((index, codec)),
+        _ => Err(SurveillanceError::UnsupportedCodec {
+            codec: track.media.encoding().unwrap_or_else(|| "unknown".to_string()),
+        }),
+    }
+}
+
+fn describe_audio(media: &MediaDescription) -> AudioProbe {
+    AudioProbe {
+        codec: media.encoding().unwrap_or_else(|| "unknown".to_string()),
+        sample_rate: media.clock_rate(),
+        channels: media.rtpmap.as_ref().and_then(|rtpmap| rtpmap.channels).or(Some(1)),
+        supported: RtpCodec::from_media(media).is_some_and(|codec| !codec.is_video()),
+    }
+}
+
+/// SPS из `sprop-parameter-sets` (RFC 6184) или `sprop-sps` (RFC 7798)
+fn sdp_sps(media: &MediaDescription, codec: RtpCodec) -> Option<SpsInfo> {
+    let encoded = match codec {
+        RtpCodec::H264 => media.fmtp.get("sprop-parameter-sets")?.split(',').next()?,
+        RtpCodec::H265 => media.fmtp.get("sprop-sps")?.as_str(),
+        _ => return None,
+    };
+    parse_sps(codec, &STANDARD.decode(encoded.trim()).ok()?)
+}
+
+fn parse_sps(codec: RtpCodec, nal: &[u8]) -> Option<SpsInfo> {
+    match codec {
+        RtpCodec::H264 => parse_h264_sps(nal),
+        RtpCodec::H265 => parse_h265_sps(nal),
+        _ => None,
+    }
+}
+
+/// Блоки NAL кадра в формате Annex B
+pub(super) fn nal_units(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut starts = Vec::new();
+    let mut index = 0;
+    while index + 3 <= data.len() {
+        if data[index..index + 3] == [0, 0, 1] {
+            starts.push(index + 3);
+            index += 3;
+        } else {
+            index += 1;
+        }
+    }
+    let ends: Vec<usize> = starts
+        .iter()
+        .skip(1)
+        .map(|&start| {
+            let end = start - 3;
+            if end > 0 && data[end - 1] == 0 { end - 1 } else { end }
+        })
+        .chain(std::iter::once(data.len()))
+        .collect();
+    starts.into_iter().zip(ends).map(move |(start, end)| &data[start..end.max(start)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::tests::TestServer;
+
+    const MJPEG_SDP: &str = "v=0\r\n\
+        s=MJPEG Camera\r\n\
+        m=video 0 RTP/AVP 26\r\n\
+        a=control:trackID=1\r\n";
+
+    fn options(timeout: Duration) -> ProbeOptions {
+        ProbeOptions {
+            timeout,
+            sample_duration: Duration::from_millis(400),
+            transport: RtspTransport::Tcp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_stream_parameters() {
+        let server = TestServer::start("admin", "secret").await;
+        let report = probe_url(&server.url, &options(Duration::from_secs(5))).await.unwrap();
+
+        assert!(!report.url.contains("secret"));
+        assert_eq!(report.video.codec, "H264");
+        assert_eq!((report.video.width, report.video.height), (Some(1280), Some(720)));
+        assert_eq!(report.video.profile_idc, Some(66));
+        // Метки времени идут с шагом 3600 тактов — 25 кадров в секунду
+        let fps = report.video.fps.unwrap();
+        assert!((fps - 25.0).abs() < 0.01, "fps {}", fps);
+        assert!(report.bitrate_kbps.unwrap() > 0.0);
+        assert!(report.frames > 1);
+        assert!(report.latency_ms >= report.connect_ms);
+
+        let audio = report.audio.unwrap();
+        assert_eq!((audio.codec.as_str(), audio.sample_rate, audio.supported), ("PCMA", Some(8000), true));
+        assert!(server.methods().contains(&"TEARDOWN".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_probe_failures_are_specific() {
+        let server = TestServer::start("admin", "secret").await;
+        let wrong = server.url.replace("secret", "guess");
+        assert!(matches!(
+            probe_url(&wrong, &options(Duration::from_secs(5))).await,
+            Err(SurveillanceError::CameraAuthFailed)
+        ));
+
+        let mjpeg = TestServer::start_with_sdp("admin", "secret", MJPEG_SDP).await;
+        match probe_url(&mjpeg.url, &options(Duration::from_secs(5))).await {
+            Err(SurveillanceError::UnsupportedCodec { codec }) => assert_eq!(codec, "JPEG"),
+            other => panic!("ожидалась ошибка кодека: {:?}", other.map(|report| report.video)),
+        }
+
+        // Камера принимает соединение, но не отвечает
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("rtsp://{}/stream", silent.local_addr().unwrap());
+        let _connection = tokio::spawn(async move { silent.accept().await });
+        assert!(matches!(
+            probe_url(&url, &options(Duration::from_millis(300))).await,
+            Err(SurveillanceError::ConnectionTimeout)
+        ));
+    }
+}
@@ -0,0 +1,799 @@
+// rtp.rs - Сборка кадров из пакетов RTP: H.264, H.265, AAC и G.711
+
+use serde::{Deserialize, Serialize};
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+use crate::sdp::MediaDescription;
+
+/// Число отсчётов в кадре AAC
+const AAC_FRAME_SAMPLES: i64 = 1024;
+
+/// Стартовый код Annex B перед каждым NAL
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Пакет RTP (RFC 3550)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpPacket {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub payload: Vec<u8>,
+}
+
+impl RtpPacket {
+    /// Разбор заголовка с учётом CSRC, расширения и выравнивания
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let invalid = |reason: Detail| {
+            SurveillanceError::rtsp_error(Detail::new("rtp_packet_invalid").with_detail("reason", reason))
+        };
+        if data.len() < 12 {
+            return Err(invalid(Detail::new("rtp_short_header")));
+        }
+        if data[0] >> 6 != 2 {
+            return Err(invalid(Detail::new("rtp_bad_version")));
+        }
+
+        let padding = data[0] & 0x20 != 0;
+        let extension = data[0] & 0x10 != 0;
+        let csrc_count = (data[0] & 0x0F) as usize;
+        let mut start = 12 + csrc_count * 4;
+        if extension {
+            let header = data.get(start..start + 4).ok_or_else(|| invalid(Detail::new("rtp_truncated_extension")))?;
+            start += 4 + u16::from_be_bytes([header[2], header[3]]) as usize * 4;
+        }
+
+        let mut end = data.len();
+        if padding {
+            let padding_size = *data.last().unwrap_or(&0) as usize;
+            end = end.checked_sub(padding_size).ok_or_else(|| invalid(Detail::new("rtp_bad_padding")))?;
+        }
+        if start > end {
+            return Err(invalid(Detail::new("rtp_no_payload")));
+        }
+
+        Ok(Self {
+            marker: data[1] & 0x80 != 0,
+            payload_type: data[1] & 0x7F,
+            sequence: u16::from_be_bytes([data[2], data[3]]),
+            timestamp: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            ssrc: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+            payload: data[start..end].to_vec(),
+        })
+    }
+}
+
+/// Кодек потока и параметры упаковки
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RtpCodec {
+    H264,
+    H265,
+    /// AAC по RFC 3640 (режим AAC-hbr)
+    Aac { size_length: u8, index_length: u8, index_delta_length: u8 },
+    Pcma,
+    Pcmu,
+}
+
+impl RtpCodec {
+    /// Кодек по описанию SDP; AAC с нулевыми или больше 32 бит длинами полей не поддерживается
+    pub fn from_media(media: &MediaDescription) -> Option<Self> {
+        let parameter = |name: &str, default: u8| {
+            let length = match media.fmtp.get(name) {
+                Some(value) => value.parse().ok()?,
+                None => default,
+            };
+            (1..=32).contains(&length).then_some(length)
+        };
+        match media.encoding()?.as_str() {
+            "H264" => Some(Self::H264),
+            "H265" | "HEVC" => Some(Self::H265),
+            "MPEG4-GENERIC" => Some(Self::Aac {
+                size_length: parameter("sizelength", 13)?,
+                index_length: parameter("indexlength", 3)?,
+                index_delta_length: parameter("indexdeltalength", 3)?,
+            }),
+            "PCMA" => Some(Self::Pcma),
+            "PCMU" => Some(Self::Pcmu),
+            _ => None,
+        }
+    }
+
+    pub fn is_video(&self) -> bool {
+        matches!(self, Self::H264 | Self::H265)
+    }
+}
+
+/// Собранный кадр: access unit в Annex B, кадр AAC или отсчёты G.711
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Время в тактах RTP от первого пакета (без переполнения 32 бит)
+    pub timestamp: i64,
+    pub keyframe: bool,
+    /// Перед кадром были потери: декодеру может потребоваться ключевой кадр
+    pub discontinuity: bool,
+    pub data: Vec<u8>,
+}
+
+/// Расширение 32-битных меток времени RTP до 64 бит
+#[derive(Debug, Default)]
+pub struct TimestampUnwrapper {
+    last: Option<(u32, i64)>,
+}
+
+impl TimestampUnwrapper {
+    pub fn unwrap(&mut self, timestamp: u32) -> i64 {
+        let extended = match self.last {
+            None => i64::from(timestamp),
+            // Разница считается со знаком: переставленный пакет не даёт скачка на 2^32
+            Some((last, extended)) => extended + i64::from(timestamp.wrapping_sub(last) as i32),
+        };
+        self.last = Some((timestamp, extended));
+        extended
+    }
+}
+
+/// Результат проверки порядкового номера
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+    First,
+    InOrder,
+    /// Пропущено указанное число пакетов
+    Gap(u16),
+    /// Повтор или опоздавший пакет
+    Late,
+}
+
+/// Отслеживание порядковых номеров с учётом переполнения
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    last: Option<u16>,
+}
+
+impl SequenceTracker {
+    pub fn check(&mut self, sequence: u16) -> SequenceCheck {
+        let Some(last) = self.last else {
+            self.last = Some(sequence);
+            return SequenceCheck::First;
+        };
+        let delta = sequence.wrapping_sub(last) as i16;
+        if delta <= 0 {
+            return SequenceCheck::Late;
+        }
+        self.last = Some(sequence);
+        match delta {
+            1 => SequenceCheck::InOrder,
+            delta => SequenceCheck::Gap(delta as u16 - 1),
+        }
+    }
+}
+
+/// Счётчики сборки для диагностики
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DepacketizerStats {
+    pub packets: u64,
+    pub lost: u64,
+    pub late: u64,
+    pub malformed: u64,
+    pub frames: u64,
+    pub dropped_frames: u64,
+}
+
+/// Access unit видео в процессе сборки
+#[derive(Debug, Default)]
+struct AccessUnit {
+    timestamp: Option<i64>,
+    nals: Vec<Vec<u8>>,
+    keyframe: bool,
+    corrupt: bool,
+}
+
+/// Сборщик кадров одного потока
+pub struct Depacketizer {
+    codec: RtpCodec,
+    sequence: SequenceTracker,
+    timestamps: TimestampUnwrapper,
+    first_timestamp: Option<i64>,
+    access_unit: AccessUnit,
+    fragment: Option<Vec<u8>>,
+    aac_fragment: Option<(i64, usize, Vec<u8>)>,
+    loss_pending: bool,
+    discontinuity: bool,
+    stats: DepacketizerStats,
+}
+
+impl Depacketizer {
+    pub fn new(codec: RtpCodec) -> Self {
+        Self {
+            codec,
+            sequence: SequenceTracker::default(),
+            timestamps: TimestampUnwrapper::default(),
+            first_timestamp: None,
+            access_unit: AccessUnit::default(),
+            fragment: None,
+            aac_fragment: None,
+            loss_pending: false,
+            discontinuity: false,
+            stats: DepacketizerStats::default(),
+        }
+    }
+
+    pub fn codec(&self) -> RtpCodec {
+        self.codec
+    }
+
+    pub fn stats(&self) -> DepacketizerStats {
+        self.stats
+    }
+
+    /// Приём пакета; возвращает кадры, собранные к этому моменту
+    pub fn push(&mut self, packet: &RtpPacket) -> Vec<Frame> {
+        self.stats.packets += 1;
+        match self.sequence.check(packet.sequence) {
+            SequenceCheck::Late => {
+                self.stats.late += 1;
+                return Vec::new();
+            }
+            SequenceCheck::Gap(lost) => {
+                self.stats.lost += u64::from(lost);
+                self.on_loss();
+            }
+            SequenceCheck::First | SequenceCheck::InOrder => {}
+        }
+
+        let extended = self.timestamps.unwrap(packet.timestamp);
+        let timestamp = extended - *self.first_timestamp.get_or_insert(extended);
+
+        let mut frames = Vec::new();
+        let parsed = match self.codec {
+            RtpCodec::H264 | RtpCodec::H265 => self.push_video(packet, timestamp, &mut frames),
+            RtpCodec::Aac { .. } => self.push_aac(packet, timestamp, &mut frames),
+            RtpCodec::Pcma | RtpCodec::Pcmu => {
+                if !packet.payload.is_empty() {
+                    self.emit(&mut frames, timestamp, true, packet.payload.clone());
+                }
+                Some(())
+            }
+        };
+
+        if parsed.is_none() {
+            self.stats.malformed += 1;
+            self.on_loss();
+        }
+        frames
+    }
+
+    /// Выдача незавершённого кадра (конец потока)
+    pub fn flush(&mut self) -> Option<Frame> {
+        let mut frames = Vec::new();
+        self.finish_access_unit(&mut frames);
+        frames.pop()
+    }
+
+    /// Потеря пакета: текущие фрагменты и access unit недостоверны
+    fn on_loss(&mut self) {
+        self.fragment = None;
+        self.aac_fragment = None;
+        self.access_unit.corrupt = true;
+        self.loss_pending = true;
+        self.discontinuity = true;
+    }
+
+    fn emit(&mut self, frames: &mut Vec<Frame>, timestamp: i64, keyframe: bool, data: Vec<u8>) {
+        self.stats.frames += 1;
+        frames.push(Frame {
+            timestamp,
+            keyframe,
+            discontinuity: std::mem::take(&mut self.discontinuity),
+            data,
+        });
+    }
+
+    fn finish_access_unit(&mut self, frames: &mut Vec<Frame>) {
+        let unit = std::mem::take(&mut self.access_unit);
+        let Some(timestamp) = unit.timestamp else {
+            return;
+        };
+        if unit.corrupt || unit.nals.is_empty() {
+            if !unit.nals.is_empty() {
+                self.stats.dropped_frames += 1;
+            }
+            return;
+        }
+
+        let mut data = Vec::with_capacity(unit.nals.iter().map(|nal| nal.len() + 4).sum());
+        for nal in &unit.nals {
+            data.extend_from_slice(&START_CODE);
+            data.extend_from_slice(nal);
+        }
+        self.emit(frames, timestamp, unit.keyframe, data);
+    }
+
+    fn push_video(&mut self, packet: &RtpPacket, timestamp: i64, frames: &mut Vec<Frame>) -> Option<()> {
+        if self.access_unit.timestamp != Some(timestamp) {
+            // Новая метка времени завершает предыдущий access unit
+            self.finish_access_unit(frames);
+            self.access_unit.timestamp = Some(timestamp);
+            self.access_unit.corrupt = self.loss_pending;
+        }
+        self.loss_pending = false;
+
+        let parsed = match self.codec {
+            RtpCodec::H265 => self.push_h265(&packet.payload),
+            _ => self.push_h264(&packet.payload),
+        };
+        if parsed.is_some() && packet.marker {
+            self.finish_access_unit(frames);
+        }
+        parsed
+    }
+
+    fn add_nal(&mut self, nal: Vec<u8>) {
+        let keyframe = match self.codec {
+            RtpCodec::H265 => nal.first().is_some_and(|header| (16..=21).contains(&((header >> 1) & 0x3F))),
+            _ => nal.first().is_some_and(|header| header & 0x1F == 5),
+        };
+        self.access_unit.keyframe |= keyframe;
+        self.access_unit.nals.push(nal);
+    }
+
+    /// Пакет с NAL, разделёнными префиксом длины (STAP-A и AP)
+    fn add_aggregated(&mut self, mut rest: &[u8]) -> Option<()> {
+        while !rest.is_empty() {
+            let size = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+            let nal = rest.get(2..2 + size).filter(|nal| !nal.is_empty())?;
+            self.add_nal(nal.to_vec());
+            rest = &rest[2 + size..];
+        }
+        Some(())
+    }
+
+    /// Фрагмент (FU-A или FU): `header` — восстановленный заголовок NAL
+    fn add_fragment(&mut self, start: bool, end: bool, header: &[u8], data: &[u8]) {
+        if start {
+            if self.fragment.is_some() {
+                self.access_unit.corrupt = true;
+            }
+            let mut nal = header.to_vec();
+            nal.extend_from_slice(data);
+            self.fragment = Some(nal);
+        } else {
+            match self.fragment.as_mut() {
+                Some(nal) => nal.extend_from_slice(data),
+                // Начало фрагмента потеряно
+                None => {
+                    self.access_unit.corrupt = true;
+                    return;
+                }
+            }
+        }
+        if end {
+            if let Some(nal) = self.fragment.take() {
+                self.add_nal(nal);
+            }
+        }
+    }
+
+    /// H.264 (RFC 6184): одиночный NAL, STAP-A, FU-A
+    fn push_h264(&mut self, payload: &[u8]) -> Option<()> {
+        let header = *payload.first()?;
+        match header & 0x1F {
+            1..=23 => self.add_nal(payload.to_vec()),
+            24 => self.add_aggregated(&payload[1..])?,
+            28 => {
+                let fu = *payload.get(1)?;
+                let nal_header = [(header & 0xE0) | (fu & 0x1F)];
+                self.add_fragment(fu & 0x80 != 0, fu & 0x40 != 0, &nal_header, &payload[2..]);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// H.265 (RFC 7798): одиночный NAL, AP, FU (без DONL)
+    fn push_h265(&mut self, payload: &[u8]) -> Option<()> {
+        let header = payload.get(..2)?;
+        match (header[0] >> 1) & 0x3F {
+            0..=47 => self.add_nal(payload.to_vec()),
+            48 => self.add_aggregated(&payload[2..])?,
+            49 => {
+                let fu = *payload.get(2)?;
+                let nal_header = [(header[0] & 0x81) | ((fu & 0x3F) << 1), header[1]];
+                self.add_fragment(fu & 0x80 != 0, fu & 0x40 != 0, &nal_header, &payload[3..]);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// AAC (RFC 3640): заголовки AU, затем данные; крупный AU — во фрагментах
+    fn push_aac(&mut self, packet: &RtpPacket, timestamp: i64, frames: &mut Vec<Frame>) -> Option<()> {
+        let RtpCodec::Aac { size_length, index_length, index_delta_length } = self.codec else {
+            return None;
+        };
+        let payload = &packet.payload;
+        let header_bits = u16::from_be_bytes([*payload.first()?, *payload.get(1)?]) as usize;
+        let header_bytes = header_bits.div_ceil(8);
+        let mut reader = BitReader::new(payload.get(2..2 + header_bytes)?);
+        let mut data = payload.get(2 + header_bytes..)?;
+
+        let mut sizes = Vec::new();
+        let mut consumed = 0;
+        while consumed < header_bits {
+            let index_bits = if sizes.is_empty() { index_length } else { index_delta_length };
+            let pass_bits = usize::from(size_length) + usize::from(index_bits);
+            // Проход без битов не продвигается: заголовки AU не разобрать
+            if pass_bits == 0 {
+                return None;
+            }
+            sizes.push(reader.read(size_length)? as usize);
+            reader.read(index_bits)?;
+            consumed += pass_bits;
+        }
+        if sizes.is_empty() {
+            return None;
+        }
+
+        // Продолжение фрагментированного AU
+        if let Some((fragment_timestamp, size, mut buffer)) = self.aac_fragment.take() {
+            if fragment_timestamp == timestamp && sizes == [size] {
+                buffer.extend_from_slice(data);
+                return self.finish_aac_fragment(packet, timestamp, size, buffer, frames);
+            }
+            self.stats.dropped_frames += 1;
+        }
+
+        if sizes.len() == 1 && sizes[0] > data.len() {
+            return self.finish_aac_fragment(packet, timestamp, sizes[0], data.to_vec(), frames);
+        }
+
+        for (index, size) in sizes.into_iter().enumerate() {
+            let unit = data.get(..size)?;
+            data = &data[size..];
+            self.emit(frames, timestamp + index as i64 * AAC_FRAME_SAMPLES, true, unit.to_vec());
+        }
+        Some(())
+    }
+
+    fn finish_aac_fragment(
+        &mut self,
+        packet: &RtpPacket,
+        timestamp: i64,
+        size: usize,
+        buffer: Vec<u8>,
+        frames: &mut Vec<Frame>,
+    ) -> Option<()> {
+        match (buffer.len().cmp(&size), packet.marker) {
+            (std::cmp::Ordering::Equal, true) => self.emit(frames, timestamp, true, buffer),
+            (std::cmp::Ordering::Less, false) => self.aac_fragment = Some((timestamp, size, buffer)),
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// Чтение битовых полей (заголовки AU)
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read(&mut self, bits: u8) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..bits {
+            let byte = *self.data.get(self.position / 8)?;
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.position += 1;
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::sdp::SessionDescription;
+
+    const MTU: usize = 1200;
+
+    fn packet(sequence: u16, timestamp: u32, marker: bool, payload: Vec<u8>) -> RtpPacket {
+        RtpPacket { marker, payload_type: 96, sequence, timestamp, ssrc: 1, payload }
+    }
+
+    fn annex_b(nals: &[Vec<u8>]) -> Vec<u8> {
+        nals.iter().flat_map(|nal| START_CODE.iter().chain(nal).copied()).collect()
+    }
+
+    /// Упаковка access unit так, как это делает камера: мелкие NAL — агрегатом,
+    /// крупные — фрагментами. `header_len` — 1 для H.264, 2 для H.265.
+    fn packetize_video(nals: &[Vec<u8>], codec: RtpCodec) -> Vec<Vec<u8>> {
+        let header_len = if codec == RtpCodec::H265 { 2 } else { 1 };
+        let mut payloads = Vec::new();
+        let mut aggregate: Vec<&Vec<u8>> = Vec::new();
+
+        let flush = |aggregate: &mut Vec<&Vec<u8>>, payloads: &mut Vec<Vec<u8>>| {
+            match aggregate.len() {
+                0 => {}
+                1 => payloads.push(aggregate[0].clone()),
+                _ => {
+                    let mut payload = match codec {
+                        RtpCodec::H265 => vec![48 << 1, 1],
+                        _ => vec![24],
+                    };
+                    for nal in aggregate.iter() {
+                        payload.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+                        payload.extend_from_slice(nal);
+                    }
+                    payloads.push(payload);
+                }
+            }
+            aggregate.clear();
+        };
+
+        for nal in nals {
+            if nal.len() > MTU {
+                flush(&mut aggregate, &mut payloads);
+                let chunks: Vec<&[u8]> = nal[header_len..].chunks(MTU).collect();
+                for (index, chunk) in chunks.iter().enumerate() {
+                    let start = if index == 0 { 0x80 } else { 0 };
+                    let end = if index + 1 == chunks.len() { 0x40 } else { 0 };
+                    let mut payload = match codec {
+                        RtpCodec::H265 => vec![(nal[0] & 0x81) | (49 << 1), nal[1], start | end | ((nal[0] >> 1) & 0x3F)],
+                        _ => vec![(nal[0] & 0xE0) | 28, start | end | (nal[0] & 0x1F)],
+                    };
+                    payload.extend_from_slice(chunk);
+                    payloads.push(payload);
+                }
+            } else {
+                let size: usize = aggregate.iter().map(|nal| nal.len() + 2).sum();
+                if size + nal.len() + 2 > MTU {
+                    flush(&mut aggregate, &mut payloads);
+                }
+                aggregate.push(nal);
+            }
+        }
+        flush(&mut aggregate, &mut payloads);
+        payloads
+    }
+
+    /// Пакеты потока: access unit каждые 3600 тактов (25 кадров/с)
+    fn video_packets(units: &[Vec<Vec<u8>>], codec: RtpCodec, first_sequence: u16, first_timestamp: u32) -> Vec<RtpPacket> {
+        let mut packets = Vec::new();
+        let mut sequence = first_sequence;
+        for (index, unit) in units.iter().enumerate() {
+            let timestamp = first_timestamp.wrapping_add(index as u32 * 3600);
+            let payloads = packetize_video(unit, codec);
+            let last = payloads.len() - 1;
+            for (position, payload) in payloads.into_iter().enumerate() {
+                packets.push(packet(sequence, timestamp, position == last, payload));
+                sequence = sequence.wrapping_add(1);
+            }
+        }
+        packets
+    }
+
+    fn h264_nal() -> impl Strategy<Value = Vec<u8>> {
+        (prop::sample::select(vec![1u8, 5, 6, 7, 8]), prop::collection::vec(any::<u8>(), 1..3000))
+            .prop_map(|(kind, body)| [vec![0x60 | kind], body].concat())
+    }
+
+    fn h265_nal() -> impl Strategy<Value = Vec<u8>> {
+        (prop::sample::select(vec![1u8, 19, 21, 32, 33, 34]), prop::collection::vec(any::<u8>(), 1..3000))
+            .prop_map(|(kind, body)| [vec![kind << 1, 1], body].concat())
+    }
+
+    fn assemble(codec: RtpCodec, packets: &[RtpPacket]) -> (Vec<Frame>, DepacketizerStats) {
+        let mut depacketizer = Depacketizer::new(codec);
+        let frames = packets.iter().flat_map(|packet| depacketizer.push(packet)).collect();
+        (frames, depacketizer.stats())
+    }
+
+    #[test]
+    fn test_parse_packet_and_static_codecs() {
+        // Заголовок с одним CSRC, расширением и выравниванием
+        let mut data = vec![0xB1, 0x88, 0x12, 0x34, 0, 0, 0x0E, 0x10, 0, 0, 0, 7, 0, 0, 0, 9];
+        data.extend_from_slice(&[0xBE, 0xDE, 0, 1, 1, 2, 3, 4]);
+        data.extend_from_slice(&[0xD5, 0xD5, 0, 0, 2]);
+        let packet = RtpPacket::parse(&data).unwrap();
+        assert!(packet.marker);
+        assert_eq!(packet.payload_type, 8);
+        assert_eq!(packet.sequence, 0x1234);
+        assert_eq!(packet.timestamp, 3600);
+        assert_eq!(packet.payload, vec![0xD5, 0xD5, 0]);
+        assert!(RtpPacket::parse(&data[..10]).is_err());
+
+        let mut depacketizer = Depacketizer::new(RtpCodec::Pcma);
+        let frames = depacketizer.push(&packet);
+        assert_eq!(frames[0].data, vec![0xD5, 0xD5, 0]);
+
+        let mut sequence = SequenceTracker::default();
+        assert_eq!(sequence.check(65534), SequenceCheck::First);
+        assert_eq!(sequence.check(65535), SequenceCheck::InOrder);
+        assert_eq!(sequence.check(2), SequenceCheck::Gap(2));
+        assert_eq!(sequence.check(1), SequenceCheck::Late);
+    }
+
+    #[test]
+    fn test_aac_field_lengths_validated() {
+        let sdp = |fmtp: &str| {
+            let text = format!(
+                "v=0\r\ns=-\r\nm=audio 0 RTP/AVP 97\r\na=rtpmap:97 MPEG4-GENERIC/16000/1\r\na=fmtp:97 streamtype=5; mode=AAC-hbr; {}\r\n",
+                fmtp
+            );
+            let session = SessionDescription::parse(&text).unwrap();
+            RtpCodec::from_media(&session.media[0])
+        };
+        assert_eq!(
+            sdp("sizelength=13; indexlength=3; indexdeltalength=3"),
+            Some(RtpCodec::Aac { size_length: 13, index_length: 3, index_delta_length: 3 })
+        );
+        assert_eq!(sdp("sizelength=0; indexlength=0; indexdeltalength=0"), None);
+        assert_eq!(sdp("sizelength=13; indexlength=3; indexdeltalength=0"), None);
+        assert_eq!(sdp("sizelength=40; indexlength=3; indexdeltalength=3"), None);
+        assert_eq!(sdp("sizelength=x; indexlength=3; indexdeltalength=3"), None);
+
+        // Кодек с нулевыми длинами, собранный в обход SDP, не зацикливает разбор
+        let codec = RtpCodec::Aac { size_length: 0, index_length: 0, index_delta_length: 0 };
+        let (frames, stats) = assemble(codec, &[packet(0, 0, true, vec![0, 16, 0, 0, 1, 2, 3])]);
+        assert!(frames.is_empty());
+        assert_eq!(stats.malformed, 1);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_h264_round_trip(
+            units in prop::collection::vec(prop::collection::vec(h264_nal(), 1..6), 1..8),
+            first_sequence in any::<u16>(),
+            first_timestamp in any::<u32>(),
+        ) {
+            let packets = video_packets(&units, RtpCodec::H264, first_sequence, first_timestamp);
+            let (frames, stats) = assemble(RtpCodec::H264, &packets);
+
+            prop_assert_eq!(frames.len(), units.len());
+            for (index, (frame, unit)) in frames.iter().zip(&units).enumerate() {
+                prop_assert_eq!(frame.timestamp, index as i64 * 3600);
+                prop_assert_eq!(&frame.data, &annex_b(unit));
+                prop_assert_eq!(frame.keyframe, unit.iter().any(|nal| nal[0] & 0x1F == 5));
+            }
+            prop_assert_eq!(stats.lost + stats.malformed, 0);
+        }
+
+        #[test]
+        fn prop_h265_round_trip(
+            units in prop::collection::vec(prop::collection::vec(h265_nal(), 1..6), 1..8),
+            first_timestamp in any::<u32>(),
+        ) {
+            let packets = video_packets(&units, RtpCodec::H265, 0, first_timestamp);
+            let (frames, _) = assemble(RtpCodec::H265, &packets);
+
+            prop_assert_eq!(frames.len(), units.len());
+            for (frame, unit) in frames.iter().zip(&units) {
+                prop_assert_eq!(&frame.data, &annex_b(unit));
+                let irap = unit.iter().any(|nal| (16..=21).contains(&(nal[0] >> 1)));
+                prop_assert_eq!(frame.keyframe, irap);
+            }
+        }
+
+        #[test]
+        fn prop_lost_packet_never_yields_damaged_frame(
+            units in prop::collection::vec(prop::collection::vec(h264_nal(), 1..4), 2..8),
+            drop_seed in any::<usize>(),
+        ) {
+            let mut packets = video_packets(&units, RtpCodec::H264, 100, 0);
+            prop_assume!(packets.len() > 2);
+            // Первый и последний пакеты не удаляются: без них потерю не обнаружить
+            let dropped = packets.remove(1 + drop_seed % (packets.len() - 2));
+            let (frames, stats) = assemble(RtpCodec::H264, &packets);
+
+            prop_assert_eq!(stats.lost, 1);
+            let damaged = (dropped.timestamp / 3600) as usize;
+            for frame in &frames {
+                let index = (frame.timestamp / 3600) as usize;
+                prop_assert_ne!(index, damaged);
+                prop_assert_eq!(&frame.data, &annex_b(&units[index]));
+            }
+            // Первый кадр после потери помечен разрывом
+            if let Some(first_after) = frames.iter().find(|frame| frame.timestamp > i64::from(dropped.timestamp)) {
+                prop_assert!(first_after.discontinuity);
+            }
+        }
+
+        #[test]
+        fn prop_aac_round_trip(units in prop::collection::vec(prop::collection::vec(any::<u8>(), 1..3000), 1..20)) {
+            // Мелкие AU объединяются в пакет, крупные — фрагментируются
+            let mut packets = Vec::new();
+            let mut batch: Vec<&Vec<u8>> = Vec::new();
+            let emit = |batch: &mut Vec<&Vec<u8>>, timestamp: u32, packets: &mut Vec<RtpPacket>| {
+                if batch.is_empty() {
+                    return;
+                }
+                let mut payload = ((batch.len() * 16) as u16).to_be_bytes().to_vec();
+                for unit in batch.iter() {
+                    payload.extend_from_slice(&((unit.len() as u16) << 3).to_be_bytes());
+                }
+                for unit in batch.iter() {
+                    payload.extend_from_slice(unit);
+                }
+                packets.push(packet(packets.len() as u16, timestamp, true, payload));
+                batch.clear();
+            };
+
+            let mut timestamp = 0u32;
+            let mut batch_start = 0u32;
+            for unit in &units {
+                if unit.len() > MTU {
+                    emit(&mut batch, batch_start, &mut packets);
+                    let chunks: Vec<&[u8]> = unit.chunks(MTU).collect();
+                    for (index, chunk) in chunks.iter().enumerate() {
+                        let mut payload = vec![0, 16];
+                        payload.extend_from_slice(&((unit.len() as u16) << 3).to_be_bytes());
+                        payload.extend_from_slice(chunk);
+                        packets.push(packet(packets.len() as u16, timestamp, index + 1 == chunks.len(), payload));
+                    }
+                    batch_start = timestamp + 1024;
+                } else {
+                    if batch.is_empty() {
+                        batch_start = timestamp;
+                    }
+                    batch.push(unit);
+                    if batch.iter().map(|unit| unit.len()).sum::<usize>() > MTU {
+                        emit(&mut batch, batch_start, &mut packets);
+                    }
+                }
+                timestamp += 1024;
+            }
+            emit(&mut batch, batch_start, &mut packets);
+
+            let codec = RtpCodec::Aac { size_length: 13, index_length: 3, index_delta_length: 3 };
+            let (frames, stats) = assemble(codec, &packets);
+            prop_assert_eq!(stats.malformed, 0);
+            prop_assert_eq!(frames.len(), units.len());
+            for (index, (frame, unit)) in frames.iter().zip(&units).enumerate() {
+                prop_assert_eq!(frame.timestamp, index as i64 * 1024);
+                prop_assert_eq!(&frame.data, unit);
+            }
+        }
+
+        #[test]
+        fn prop_timestamp_unwrap(start in any::<u32>(), steps in prop::collection::vec(0u32..1_000_000, 1..200)) {
+            let mut unwrapper = TimestampUnwrapper::default();
+            let base = unwrapper.unwrap(start);
+            let mut expected = 0i64;
+            let mut raw = start;
+            for step in steps {
+                raw = raw.wrapping_add(step);
+                expected += i64::from(step);
+                prop_assert_eq!(unwrapper.unwrap(raw) - base, expected);
+            }
+        }
+
+        #[test]
+        fn prop_arbitrary_input_never_panics(packets in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..64), 0..40)) {
+            let codecs = [
+                RtpCodec::H264,
+                RtpCodec::H265,
+                RtpCodec::Aac { size_length: 13, index_length: 3, index_delta_length: 3 },
+                RtpCodec::Pcmu,
+            ];
+            for codec in codecs {
+                let mut depacketizer = Depacketizer::new(codec);
+                for (sequence, data) in packets.iter().enumerate() {
+                    if let Ok(packet) = RtpPacket::parse(data) {
+                        depacketizer.push(&packet);
+                    }
+                    let raw = packet(sequence as u16, 0, sequence % 3 == 0, data.clone());
+                    depacketizer.push(&raw);
+                }
+                depacketizer.flush();
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+// sps.rs - Разбор наборов параметров последовательности (SPS) H.264/H.265
+
+use serde::{Deserialize, Serialize};
+
+/// Параметры видео из SPS
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpsInfo {
+    pub width: u32,
+    pub height: u32,
+    pub profile_idc: u8,
+    pub level_idc: u8,
+}
+
+/// Профили H.264 с расширенным заголовком SPS (ITU-T H.264, 7.3.2.1.1)
+const H264_HIGH_PROFILES: &[u8] = &[100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// Разбор SPS H.264 вместе с однобайтовым заголовком NAL
+pub fn parse_h264_sps(nal: &[u8]) -> Option<SpsInfo> {
+    if nal.first()? & 0x1F != 7 {
+        return None;
+    }
+    let rbsp = remove_emulation_prevention(&nal[1..]);
+    let mut reader = BitReader::new(&rbsp);
+
+    let profile_idc = reader.bits(8)? as u8;
+    reader.skip(8)?; // constraint_set флаги
+    let level_idc = reader.bits(8)? as u8;
+    reader.ue()?; // seq_parameter_set_id
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    if H264_HIGH_PROFILES.contains(&profile_idc) {
+        chroma_format_idc = reader.ue()?;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = reader.flag()?;
+        }
+        reader.ue()?; // bit_depth_luma_minus8
+        reader.ue()?; // bit_depth_chroma_minus8
+        reader.skip(1)?; // qpprime_y_zero_transform_bypass_flag
+        if reader.flag()? {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for index in 0..lists {
+                if reader.flag()? {
+                    skip_scaling_list(&mut reader, if index < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+
+    reader.ue()?; // log2_max_frame_num_minus4
+    match reader.ue()? {
+        0 => {
+            reader.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            reader.skip(1)?; // delta_pic_order_always_zero_flag
+            reader.se()?;
+            reader.se()?;
+            for _ in 0..reader.ue()? {
+                reader.se()?;
+            }
+        }
+        _ => {}
+    }
+    reader.ue()?; // max_num_ref_frames
+    reader.skip(1)?; // gaps_in_frame_num_value_allowed_flag
+    let width_in_mbs = reader.ue()?.checked_add(1)?;
+    let height_in_map_units = reader.ue()?.checked_add(1)?;
+    let frame_mbs_only = reader.flag()?;
+    if !frame_mbs_only {
+        reader.skip(1)?; // mb_adaptive_frame_field_flag
+    }
+    reader.skip(1)?; // direct_8x8_inference_flag
+
+    let field_factor = if frame_mbs_only { 1 } else { 2 };
+    let mut width = width_in_mbs.checked_mul(16)?;
+    let mut height = height_in_map_units.checked_mul(16 * field_factor)?;
+
+    if reader.flag()? {
+        let (left, right, top, bottom) = (reader.ue()?, reader.ue()?, reader.ue()?, reader.ue()?);
+        // Единицы обрезки зависят от субдискретизации цветности (таблица 6-1)
+        let (crop_x, crop_y) = if chroma_format_idc == 0 || separate_colour_plane {
+            (1, field_factor)
+        } else {
+            let sub_width = if chroma_format_idc == 3 { 1 } else { 2 };
+            let sub_height = if chroma_format_idc == 1 { 2 } else { 1 };
+            (sub_width, sub_height * field_factor)
+        };
+        width = crop(width, left, right, crop_x)?;
+        height = crop(height, top, bottom, crop_y)?;
+    }
+
+    Some(SpsInfo { width, height, profile_idc, level_idc })
+}
+
+/// Размер после обрезки с двух сторон; `None` при переполнении или обрезке больше размера
+fn crop(size: u32, first: u32, second: u32, unit: u32) -> Option<u32> {
+    size.checked_sub(first.checked_add(second)?.checked_mul(unit)?)
+}
+
+/// Разбор SPS H.265 вместе с двухбайтовым заголовком NAL
+pub fn parse_h265_sps(nal: &[u8]) -> Option<SpsInfo> {
+    if (nal.first()? >> 1) & 0x3F != 33 || nal.len() < 2 {
+        return None;
+    }
+    let rbsp = remove_emulation_prevention(&nal[2..]);
+    let mut reader = BitReader::new(&rbsp);
+
+    reader.skip(4)?; // sps_video_parameter_set_id
+    let max_sub_layers_minus1 = reader.bits(3)?;
+    reader.skip(1)?; // sps_temporal_id_nesting_flag
+
+    // profile_tier_level (ITU-T H.265, 7.3.3)
+    reader.skip(3)?; // general_profile_space, general_tier_flag
+    let profile_idc = reader.bits(5)? as u8;
+    reader.skip(32 + 4 + 43 + 1)?;
+    let level_idc = reader.bits(8)? as u8;
+    let mut sub_layer_flags = Vec::new();
+    for _ in 0..max_sub_layers_minus1 {
+        sub_layer_flags.push((reader.flag()?, reader.flag()?));
+    }
+    if max_sub_layers_minus1 > 0 {
+        reader.skip(2 * (8 - max_sub_layers_minus1))?;
+    }
+    for (profile_present, level_present) in sub_layer_flags {
+        if profile_present {
+            reader.skip(88)?;
+        }
+        if level_present {
+            reader.skip(8)?;
+        }
+    }
+
+    reader.ue()?; // sps_seq_parameter_set_id
+    let chroma_format_idc = reader.ue()?;
+    let separate_colour_plane = chroma_format_idc == 3 && reader.flag()?;
+    let mut width = reader.ue()?;
+    let mut height = reader.ue()?;
+
+    if reader.flag()? {
+        let (left, right, top, bottom) = (reader.ue()?, reader.ue()?, reader.ue()?, reader.ue()?);
+        let (sub_width, sub_height) = match chroma_format_idc {
+            1 if !separate_colour_plane => (2, 2),
+            2 if !separate_colour_plane => (2, 1),
+            _ => (1, 1),
+        };
+        width = crop(width, left, right, sub_width)?;
+        height = crop(height, top, bottom, sub_height)?;
+    }
+
+    Some(SpsInfo { width, height, profile_idc, level_idc })
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Option<()> {
+    let mut last_scale = 8i64;
+    let mut next_scale = 8i64;
+    for _ in 0..size {
+        if next_scale != 0 {
+            next_scale = (last_scale + reader.se()? + 256) % 256;
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Some(())
+}
+
+/// Удаление байтов `0x03` после двух нулевых байтов
+fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &byte in data {
+        if zeros >= 2 && byte == 0x03 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    rbsp
+}
+
+/// Чтение битов старшим вперёд и кодов Exp-Golomb
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        let byte = self.data.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Some(bit == 1)
+    }
+
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        (0..count).try_fold(0u32, |value, _| Some((value << 1) | u32::from(self.flag()?)))
+    }
+
+    fn skip(&mut self, count: u32) -> Option<()> {
+        let position = self.position + count as usize;
+        if position > self.data.len() * 8 {
+            return None;
+        }
+        self.position = position;
+        Some(())
+    }
+
+    fn ue(&mut self) -> Option<u32> {
+        let mut leading_zeros = 0;
+        while !self.flag()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return None;
+            }
+        }
+        Some((1u32 << leading_zeros) - 1 + self.bits(leading_zeros)?)
+    }
+
+    fn se(&mut self) -> Option<i64> {
+        let code = i64::from(self.ue()?);
+        Some(if code % 2 == 1 { (code + 1) / 2 } else { -(code / 2) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    fn decode(text: &str) -> Vec<u8> {
+        STANDARD.decode(text).unwrap()
+    }
+
+    #[test]
+    fn test_h264_sps() {
+        // Baseline 1280x720 без обрезки
+        let sps = parse_h264_sps(&decode("Z0LAKNoBQBbk")).unwrap();
+        assert_eq!((sps.width, sps.height, sps.profile_idc, sps.level_idc), (1280, 720, 66, 40));
+
+        // High 1920x1088 с обрезкой до 1080
+        let sps = parse_h264_sps(&decode("Z2QAKKy0A8ARPyo=")).unwrap();
+        assert_eq!((sps.width, sps.height, sps.profile_idc), (1920, 1080, 100));
+
+        // SPS реальной камеры: VUI и байты защиты от эмуляции
+        let sps = parse_h264_sps(&decode("Z2QAKKwbGoB4AiflwFuAgICgAAB9AAAOpgCA")).unwrap();
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+
+        assert!(parse_h264_sps(&decode("aO48sA==")).is_none());
+        assert!(parse_h264_sps(&[0x67, 0x42]).is_none());
+    }
+
+    #[test]
+    fn test_h265_sps() {
+        let sps = parse_h265_sps(&decode("QgEBAWAAAAMAkAAAAwAAAwB4oAPAgBDlwA==")).unwrap();
+        assert_eq!((sps.width, sps.height, sps.profile_idc, sps.level_idc), (1920, 1080, 1, 120));
+        assert!(parse_h265_sps(&[0x40, 0x01, 0x0C]).is_none());
+    }
+
+    /// Запись битов SPS с защитой от эмуляции стартового кода
+    #[derive(Default)]
+    struct BitWriter {
+        bits: Vec<bool>,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u64, count: u32) -> &mut Self {
+            for shift in (0..count).rev() {
+                self.bits.push((value >> shift) & 1 == 1);
+            }
+            self
+        }
+
+        fn ue(&mut self, value: u32) -> &mut Self {
+            let code = u64::from(value) + 1;
+            let length = 64 - code.leading_zeros();
+            self.bits(0, length - 1).bits(code, length)
+        }
+
+        fn finish(&mut self, header: &[u8]) -> Vec<u8> {
+            self.bits(1, 1); // rbsp_stop_one_bit
+            while !self.bits.len().is_multiple_of(8) {
+                self.bits.push(false);
+            }
+            let mut nal = header.to_vec();
+            let mut zeros = 0;
+            for chunk in self.bits.chunks(8) {
+                let byte = chunk.iter().fold(0u8, |byte, bit| (byte << 1) | u8::from(*bit));
+                if zeros >= 2 && byte <= 3 {
+                    nal.push(3);
+                    zeros = 0;
+                }
+                zeros = if byte == 0 { zeros + 1 } else { 0 };
+                nal.push(byte);
+            }
+            nal
+        }
+    }
+
+    /// Baseline SPS с заданными размерами в макроблоках и обрезкой
+    fn h264_sps(width_in_mbs_minus1: u32, height_in_map_units_minus1: u32, frame_mbs_only: bool, crop: Option<[u32; 4]>) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        writer.bits(66, 8).bits(0, 8).bits(40, 8).ue(0);
+        writer.ue(0).ue(2).ue(1).bits(0, 1); // frame_num, pic_order_cnt_type 2, ref_frames, gaps
+        writer.ue(width_in_mbs_minus1).ue(height_in_map_units_minus1).bits(u64::from(frame_mbs_only), 1);
+        if !frame_mbs_only {
+            writer.bits(0, 1);
+        }
+        writer.bits(1, 1); // direct_8x8_inference_flag
+        match crop {
+            Some(offsets) => {
+                writer.bits(1, 1);
+                offsets.iter().for_each(|offset| {
+                    writer.ue(*offset);
+                });
+            }
+            None => {
+                writer.bits(0, 1);
+            }
+        }
+        writer.bits(0, 1).finish(&[0x67])
+    }
+
+    /// Main SPS 4:2:0 с заданными размерами и обрезкой
+    fn h265_sps(width: u32, height: u32, crop: [u32; 4]) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        writer.bits(0, 4).bits(0, 3).bits(1, 1).bits(0, 3).bits(1, 5);
+        writer.bits(0x6000_0000, 32).bits(0x90_0000_0000_0000, 48).bits(120, 8);
+        writer.ue(0).ue(1).ue(width).ue(height).bits(1, 1);
+        crop.iter().for_each(|offset| {
+            writer.ue(*offset);
+        });
+        writer.finish(&[0x42, 0x01])
+    }
+
+    #[test]
+    fn test_oversized_sps_rejected() {
+        // Собранные SPS разбираются так же, как записанные камерами
+        let sps = parse_h264_sps(&h264_sps(119, 33, false, Some([0, 0, 0, 2]))).unwrap();
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        let sps = parse_h265_sps(&h265_sps(1920, 1088, [0, 0, 0, 4])).unwrap();
+        assert_eq!((sps.width, sps.height, sps.profile_idc, sps.level_idc), (1920, 1080, 1, 120));
+
+        // Размеры и обрезка, переполняющие u32, дают None вместо паники
+        assert!(parse_h264_sps(&h264_sps(u32::MAX - 1, 0, true, None)).is_none());
+        assert!(parse_h264_sps(&h264_sps(1 << 28, 0, true, None)).is_none());
+        assert!(parse_h264_sps(&h264_sps(0, 1 << 27, false, None)).is_none());
+        assert!(parse_h264_sps(&h264_sps(119, 67, true, Some([u32::MAX - 1, u32::MAX - 1, 0, 0]))).is_none());
+        assert!(parse_h264_sps(&h264_sps(119, 67, true, Some([1 << 31, 0, 0, 0]))).is_none());
+        assert!(parse_h265_sps(&h265_sps(u32::MAX - 1, 1080, [1 << 31, 0, 0, 0])).is_none());
+        assert!(parse_h265_sps(&h265_sps(1920, 1080, [u32::MAX - 1, u32::MAX - 1, 0, 0])).is_none());
+    }
+}
@@ -0,0 +1,355 @@
+// ts.rs - Упаковка H.264 и AAC в транспортный поток MPEG-TS (ISO/IEC 13818-1)
+
+use crate::error::{SurveillanceError, Result};
+use crate::sdp::MediaDescription;
+
+pub const PACKET_SIZE: usize = 188;
+
+const PAT_PID: u16 = 0x0000;
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x0100;
+const AUDIO_PID: u16 = 0x0101;
+
+const STREAM_TYPE_H264: u8 = 0x1B;
+const STREAM_TYPE_AAC: u8 = 0x0F;
+
+/// Частоты дискретизации AAC по индексу (ISO/IEC 14496-3)
+const AAC_SAMPLE_RATES: [u32; 13] = [96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350];
+
+/// Параметры AAC из AudioSpecificConfig, нужные для заголовков ADTS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AacConfig {
+    pub object_type: u8,
+    pub sample_rate_index: u8,
+    pub channels: u8,
+}
+
+impl AacConfig {
+    /// Разбор AudioSpecificConfig; ADTS допускает только стандартные частоты
+    pub fn parse(config: &[u8]) -> Result<Self> {
+        if config.len() < 2 {
+            return Err(SurveillanceError::rtsp_error("aac_config_invalid"));
+        }
+        let object_type = config[0] >> 3;
+        let sample_rate_index = ((config[0] & 0x07) << 1) | (config[1] >> 7);
+        let channels = (config[1] >> 3) & 0x0F;
+        if !(1..=4).contains(&object_type) || usize::from(sample_rate_index) >= AAC_SAMPLE_RATES.len() || channels > 7 {
+            return Err(SurveillanceError::rtsp_error("aac_config_invalid"));
+        }
+        Ok(Self { object_type, sample_rate_index, channels })
+    }
+
+    /// Конфигурация из параметра `config` SDP (RFC 3640, шестнадцатеричная строка)
+    pub fn from_media(media: &MediaDescription) -> Result<Self> {
+        let hex = media
+            .fmtp
+            .get("config")
+            .ok_or_else(|| SurveillanceError::rtsp_error("aac_config_invalid"))?;
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|index| hex.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| SurveillanceError::rtsp_error("aac_config_invalid"))?;
+        Self::parse(&bytes)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        AAC_SAMPLE_RATES[usize::from(self.sample_rate_index)]
+    }
+
+    /// Заголовок ADTS без контрольной суммы для кадра из `payload_size` байт
+    pub fn adts_header(&self, payload_size: usize) -> [u8; 7] {
+        let length = payload_size + 7;
+        [
+            0xFF,
+            0xF1,
+            ((self.object_type - 1) << 6) | (self.sample_rate_index << 2) | (self.channels >> 2),
+            ((self.channels & 0x03) << 6) | ((length >> 11) as u8 & 0x03),
+            (length >> 3) as u8,
+            ((length as u8 & 0x07) << 5) | 0x1F,
+            0xFC,
+        ]
+    }
+}
+
+/// Мультиплексор одной программы: видео H.264 и необязательный AAC
+#[derive(Debug, Clone)]
+pub struct TsMuxer {
+    audio: Option<AacConfig>,
+    continuity: [u8; 4],
+}
+
+impl TsMuxer {
+    pub fn new(audio: Option<AacConfig>) -> Self {
+        Self { audio, continuity: [0; 4] }
+    }
+
+    pub fn audio(&self) -> Option<AacConfig> {
+        self.audio
+    }
+
+    /// Таблицы PAT и PMT; пишутся в начале каждого сегмента и части
+    pub fn write_tables(&mut self, out: &mut Vec<u8>) {
+        let mut pat = vec![0x00, 0x01, 0xE0 | (PMT_PID >> 8) as u8, PMT_PID as u8];
+        pat = section(0x00, 0x0001, &pat);
+        self.write_section(out, PAT_PID, &pat);
+
+        let mut pmt = vec![0xE0 | (VIDEO_PID >> 8) as u8, VIDEO_PID as u8, 0xF0, 0x00];
+        pmt.extend_from_slice(&elementary_stream(STREAM_TYPE_H264, VIDEO_PID));
+        if self.audio.is_some() {
+            pmt.extend_from_slice(&elementary_stream(STREAM_TYPE_AAC, AUDIO_PID));
+        }
+        let pmt = section(0x02, 0x0001, &pmt);
+        self.write_section(out, PMT_PID, &pmt);
+    }
+
+    /// Access unit H.264 в Annex B; `pts` в тактах 90 кГц
+    pub fn write_video(&mut self, out: &mut Vec<u8>, pts: i64, keyframe: bool, access_unit: &[u8]) {
+        let pes = pes_packet(0xE0, pts, access_unit, false);
+        // PCR идёт вместе с каждым кадром видео, точка входа отмечается флагом произвольного доступа
+        let mut adaptation = vec![if keyframe { 0x50 } else { 0x10 }];
+        adaptation.extend_from_slice(&pcr(pts));
+        self.write_pes(out, VIDEO_PID, &adaptation, &pes);
+    }
+
+    /// Кадр AAC без заголовка; `pts` в тактах 90 кГц
+    pub fn write_audio(&mut self, out: &mut Vec<u8>, pts: i64, frame: &[u8]) {
+        let Some(config) = self.audio else {
+            return;
+        };
+        let mut payload = config.adts_header(frame.len()).to_vec();
+        payload.extend_from_slice(frame);
+        let pes = pes_packet(0xC0, pts, &payload, true);
+        self.write_pes(out, AUDIO_PID, &[], &pes);
+    }
+
+    fn write_section(&mut self, out: &mut Vec<u8>, pid: u16, section: &[u8]) {
+        // pointer_field, секция и заполнение 0xFF до конца пакета
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(section);
+        payload.resize(PACKET_SIZE - 4, 0xFF);
+        self.write_packet(out, pid, true, &[], &payload);
+    }
+
+    fn write_pes(&mut self, out: &mut Vec<u8>, pid: u16, adaptation: &[u8], pes: &[u8]) {
+        let mut offset = self.write_packet(out, pid, true, adaptation, pes);
+        while offset < pes.len() {
+            offset += self.write_packet(out, pid, false, &[], &pes[offset..]);
+        }
+    }
+
+    /// Один пакет; `adaptation` — поле адаптации без байта длины. Возвращает число записанных байт данных
+    fn write_packet(&mut self, out: &mut Vec<u8>, pid: u16, start: bool, adaptation: &[u8], data: &[u8]) -> usize {
+        let mut space = PACKET_SIZE - 4;
+        if !adaptation.is_empty() {
+            space -= 1 + adaptation.len();
+        }
+        let size = data.len().min(space);
+        let stuffing = space - size;
+        let has_adaptation = !adaptation.is_empty() || stuffing > 0;
+
+        let counter = self.next_continuity(pid);
+        out.push(0x47);
+        out.push(((start as u8) << 6) | ((pid >> 8) as u8 & 0x1F));
+        out.push(pid as u8);
+        out.push(if has_adaptation { 0x30 } else { 0x10 } | counter);
+        if has_adaptation {
+            if adaptation.is_empty() {
+                // Поле адаптации только для заполнения: байт длины, флаги и 0xFF
+                out.push((stuffing - 1) as u8);
+                if stuffing > 1 {
+                    out.push(0x00);
+                    out.resize(out.len() + stuffing - 2, 0xFF);
+                }
+            } else {
+                out.push((adaptation.len() + stuffing) as u8);
+                out.extend_from_slice(adaptation);
+                out.resize(out.len() + stuffing, 0xFF);
+            }
+        }
+        out.extend_from_slice(&data[..size]);
+        size
+    }
+
+    fn next_continuity(&mut self, pid: u16) -> u8 {
+        let index = match pid {
+            PAT_PID => 0,
+            PMT_PID => 1,
+            VIDEO_PID => 2,
+            _ => 3,
+        };
+        let counter = self.continuity[index];
+        self.continuity[index] = (counter + 1) & 0x0F;
+        counter
+    }
+}
+
+fn elementary_stream(stream_type: u8, pid: u16) -> [u8; 5] {
+    [stream_type, 0xE0 | (pid >> 8) as u8, pid as u8, 0xF0, 0x00]
+}
+
+/// Секция PSI с заголовком и CRC
+fn section(table_id: u8, id: u16, body: &[u8]) -> Vec<u8> {
+    let length = 5 + body.len() + 4;
+    let mut section = vec![
+        table_id,
+        0xB0 | (length >> 8) as u8,
+        length as u8,
+        (id >> 8) as u8,
+        id as u8,
+        0xC1, // версия 0, текущая
+        0x00,
+        0x00,
+    ];
+    section.extend_from_slice(body);
+    let crc = crc32_mpeg(&section);
+    section.extend_from_slice(&crc.to_be_bytes());
+    section
+}
+
+/// Пакет PES с PTS; длина указывается только для звука, у видео она может не поместиться в 16 бит
+fn pes_packet(stream_id: u8, pts: i64, payload: &[u8], bounded: bool) -> Vec<u8> {
+    let length = if bounded { (payload.len() + 8).min(0xFFFF) } else { 0 };
+    let mut pes = vec![0x00, 0x00, 0x01, stream_id, (length >> 8) as u8, length as u8, 0x80, 0x80, 0x05];
+    pes.extend_from_slice(&timestamp(0x20, pts));
+    pes.extend_from_slice(payload);
+    pes
+}
+
+/// 33-битная метка времени PES с маркерными битами
+fn timestamp(prefix: u8, value: i64) -> [u8; 5] {
+    let value = value as u64 & 0x1_FFFF_FFFF;
+    [
+        prefix | ((value >> 29) as u8 & 0x0E) | 0x01,
+        (value >> 22) as u8,
+        ((value >> 14) as u8 & 0xFE) | 0x01,
+        (value >> 7) as u8,
+        ((value << 1) as u8 & 0xFE) | 0x01,
+    ]
+}
+
+/// PCR по метке 90 кГц (расширение 27 МГц равно нулю)
+fn pcr(value: i64) -> [u8; 6] {
+    let base = value as u64 & 0x1_FFFF_FFFF;
+    [
+        (base >> 25) as u8,
+        (base >> 17) as u8,
+        (base >> 9) as u8,
+        (base >> 1) as u8,
+        ((base << 7) as u8 & 0x80) | 0x7E,
+        0x00,
+    ]
+}
+
+/// CRC-32/MPEG-2 для секций PSI
+fn crc32_mpeg(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(data: &[u8]) -> Vec<&[u8]> {
+        assert_eq!(data.len() % PACKET_SIZE, 0);
+        data.chunks(PACKET_SIZE).collect()
+    }
+
+    fn pid(packet: &[u8]) -> u16 {
+        (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2])
+    }
+
+    fn payload(packet: &[u8]) -> &[u8] {
+        match packet[3] & 0x30 {
+            0x30 => &packet[5 + usize::from(packet[4])..],
+            _ => &packet[4..],
+        }
+    }
+
+    fn decode_timestamp(bytes: &[u8]) -> i64 {
+        (i64::from(bytes[0] & 0x0E) << 29)
+            | (i64::from(bytes[1]) << 22)
+            | (i64::from(bytes[2] & 0xFE) << 14)
+            | (i64::from(bytes[3]) << 7)
+            | (i64::from(bytes[4]) >> 1)
+    }
+
+    #[test]
+    fn test_tables_and_crc() {
+        // Контрольное значение CRC-32/MPEG-2
+        assert_eq!(crc32_mpeg(b"123456789"), 0x0376_E6E7);
+
+        let mut muxer = TsMuxer::new(Some(AacConfig::parse(&[0x14, 0x08]).unwrap()));
+        let mut out = Vec::new();
+        muxer.write_tables(&mut out);
+        let packets = packets(&out);
+        assert_eq!((pid(packets[0]), pid(packets[1])), (PAT_PID, PMT_PID));
+
+        // Секция, включая CRC, даёт нулевой остаток
+        for packet in &packets {
+            let data = payload(packet);
+            let length = ((usize::from(data[2]) & 0x0F) << 8) | usize::from(data[3]);
+            assert_eq!(crc32_mpeg(&data[1..4 + length]), 0);
+        }
+        let pmt = payload(packets[1]);
+        assert_eq!((pmt[13], pmt[18]), (STREAM_TYPE_H264, STREAM_TYPE_AAC));
+    }
+
+    #[test]
+    fn test_video_pes_split_and_stuffed() {
+        let mut muxer = TsMuxer::new(None);
+        let access_unit: Vec<u8> = [0, 0, 0, 1, 0x65].into_iter().chain((0..400).map(|i| i as u8)).collect();
+        let mut out = Vec::new();
+        muxer.write_video(&mut out, 123_456, true, &access_unit);
+        muxer.write_video(&mut out, 127_056, false, &access_unit);
+
+        let packets = packets(&out);
+        assert!(packets.iter().all(|packet| packet[0] == 0x47 && pid(packet) == VIDEO_PID));
+        let counters: Vec<u8> = packets.iter().map(|packet| packet[3] & 0x0F).collect();
+        assert_eq!(counters, (0..counters.len() as u8).collect::<Vec<_>>());
+
+        // Первый пакет: начало PES, флаг произвольного доступа, PCR и PTS
+        let first = packets[0];
+        assert_eq!(first[1] & 0x40, 0x40);
+        assert_eq!(first[5], 0x50);
+        let pes = payload(first);
+        assert_eq!(&pes[..4], &[0, 0, 1, 0xE0]);
+        assert_eq!(decode_timestamp(&pes[9..14]), 123_456);
+
+        // Данные собираются обратно без потерь
+        let mut collected = Vec::new();
+        for packet in &packets[..packets.len() / 2] {
+            collected.extend_from_slice(payload(packet));
+        }
+        assert_eq!(&collected[14..], access_unit.as_slice());
+        assert_eq!(payload(packets[packets.len() / 2])[0..4], [0, 0, 1, 0xE0]);
+        assert_eq!(packets[packets.len() / 2][5], 0x10);
+    }
+
+    #[test]
+    fn test_aac_config_and_adts() {
+        // AAC-LC, 44.1 кГц, стерео
+        let config = AacConfig::parse(&[0x12, 0x10]).unwrap();
+        assert_eq!(config, AacConfig { object_type: 2, sample_rate_index: 4, channels: 2 });
+        assert_eq!(config.sample_rate(), 44100);
+        assert_eq!(config.adts_header(100), [0xFF, 0xF1, 0x50, 0x80, 0x0D, 0x7F, 0xFC]);
+        assert!(AacConfig::parse(&[0x12]).is_err());
+        assert!(AacConfig::parse(&[0x17, 0x90]).is_err());
+
+        let mut muxer = TsMuxer::new(Some(config));
+        let mut out = Vec::new();
+        muxer.write_audio(&mut out, 90_000, &[0x21; 20]);
+        let pes = payload(&out);
+        assert_eq!(&pes[..6], &[0, 0, 1, 0xC0, 0, 35]);
+        assert_eq!(decode_timestamp(&pes[9..14]), 90_000);
+        assert_eq!(&pes[14..16], &[0xFF, 0xF1]);
+        assert_eq!(pes.len(), 14 + 7 + 20);
+    }
+}
@@ -0,0 +1,354 @@
+// totp.rs - Двухфакторная аутентификация (TOTP, RFC 6238)
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use crate::error::{SurveillanceError, Result};
+use crate::i18n::Detail;
+
+/// Длина секрета TOTP в байтах (160 бит, как рекомендует RFC 4226)
+const SECRET_LEN: usize = 20;
+
+/// Длина nonce AES-GCM
+const NONCE_LEN: usize = 12;
+
+/// Количество резервных кодов, выдаваемых при подключении
+const RECOVERY_CODES_COUNT: usize = 10;
+
+/// Политика двухфакторной аутентификации
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TwoFactorPolicy {
+    pub required_roles: Vec<String>, // Роли, для которых 2FA обязательна
+    pub issuer: String,              // Издатель в приложении-аутентификаторе
+    pub digits: u32,                 // Количество цифр в коде
+    pub period: u64,                 // Период смены кода в секундах
+    pub allowed_skew: u64,           // Допустимое расхождение часов в периодах
+}
+
+impl Default for TwoFactorPolicy {
+    fn default() -> Self {
+        Self {
+            required_roles: vec!["Admin".to_string()],
+            issuer: "Surveillance System".to_string(),
+            digits: 6,
+            period: 30,
+            allowed_skew: 1,
+        }
+    }
+}
+
+impl TwoFactorPolicy {
+    /// Обязательна ли 2FA для роли
+    pub fn is_required_for(&self, role_name: &str) -> bool {
+        self.required_roles.iter().any(|role| role == role_name)
+    }
+
+    /// Проверка параметров политики
+    pub fn validate(&self) -> Result<()> {
+        if !(6..=8).contains(&self.digits) {
+            return Err(SurveillanceError::config_error("two_factor_digits_invalid"));
+        }
+        if self.period == 0 {
+            return Err(SurveillanceError::config_error("two_factor_period_invalid"));
+        }
+        Ok(())
+    }
+}
+
+/// Состояние 2FA пользователя (хранится вместе с пользователем)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TwoFactorState {
+    /// Секрет, зашифрованный `SecretCipher`
+    pub encrypted_secret: String,
+    /// Подтверждено ли подключение кодом из приложения
+    pub confirmed: bool,
+    /// SHA-256 хеши неиспользованных резервных кодов
+    pub recovery_code_hashes: Vec<String>,
+    /// Последний принятый временной шаг (защита от повторного использования кода)
+    pub last_used_step: Option<u64>,
+}
+
+/// Данные для подключения 2FA, показываются пользователю один раз
+#[derive(Debug, Clone, Serialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// Шифрование секретов TOTP (AES-256-GCM)
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(key.into()),
+        }
+    }
+
+    /// Шифр со случайным ключом, живущим только в памяти процесса
+    pub fn ephemeral() -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self::new(&key)
+    }
+
+    /// Загрузка ключа из файла или создание нового
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(encoded) => return Self::decode_key(&encoded),
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            Err(_) => {}
+        }
+
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = match create_key_file(path) {
+            Ok(file) => file,
+            // Ключ успел создать другой процесс: используем его, а не перезаписываем
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Self::decode_key(&std::fs::read_to_string(path)?);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(STANDARD.encode(key).as_bytes())?;
+        file.sync_all()?;
+        log::info!("Создан новый ключ шифрования 2FA: {}", path.display());
+        Ok(Self::new(&key))
+    }
+
+    fn decode_key(encoded: &str) -> Result<Self> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| SurveillanceError::config_error(Detail::new("two_factor_key_corrupted").with("error", e.to_string())))?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| SurveillanceError::config_error("two_factor_key_length"))?;
+        Ok(Self::new(&key))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| SurveillanceError::internal_error("two_factor_encrypt_failed"))?;
+
+        let mut combined = nonce.to_vec();
+        combined.extend(ciphertext);
+        Ok(STANDARD.encode(combined))
+    }
+
+    pub fn decrypt(&self, encoded: &str) -> Result<Vec<u8>> {
+        let combined = STANDARD
+            .decode(encoded)
+            .map_err(|_| SurveillanceError::auth_error("two_factor_secret_corrupted"))?;
+        if combined.len() <= NONCE_LEN {
+            return Err(SurveillanceError::auth_error("two_factor_secret_corrupted"));
+        }
+
+        let (nonce, ciphertext) = combined.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| SurveillanceError::auth_error("two_factor_decrypt_failed"))
+    }
+}
+
+/// Генерация нового секрета TOTP
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// Кодирование секрета в Base32 без выравнивания (формат приложений-аутентификаторов)
+pub fn encode_secret(secret: &[u8]) -> String {
+    data_encoding::BASE32_NOPAD.encode(secret)
+}
+
+/// Код HOTP (RFC 4226) для счётчика
+pub fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC принимает ключ любой длины");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Динамическое усечение
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    binary % 10u32.pow(digits)
+}
+
+/// Временной шаг для момента времени
+pub fn time_step(unix_time: u64, period: u64) -> u64 {
+    unix_time / period
+}
+
+/// Проверка кода TOTP с учётом расхождения часов.
+///
+/// Возвращает принятый временной шаг; шаги не новее `last_used_step`
+/// отклоняются, чтобы один и тот же код нельзя было использовать дважды.
+pub fn verify_code(
+    secret: &[u8],
+    code: &str,
+    unix_time: u64,
+    policy: &TwoFactorPolicy,
+    last_used_step: Option<u64>,
+) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != policy.digits as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let expected: u32 = code.parse().ok()?;
+
+    let current = time_step(unix_time, policy.period);
+    let first = current.saturating_sub(policy.allowed_skew);
+    let last = current + policy.allowed_skew;
+
+    (first..=last)
+        .filter(|step| last_used_step.is_none_or(|used| *step > used))
+        .find(|step| hotp(secret, *step, policy.digits) == expected)
+}
+
+/// URI для QR-кода приложения-аутентификатора (формат Key Uri)
+pub fn provisioning_uri(secret: &[u8], login: &str, policy: &TwoFactorPolicy) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(&policy.issuer),
+        percent_encode(login),
+        encode_secret(secret),
+        percent_encode(&policy.issuer),
+        policy.digits,
+        policy.period,
+    )
+}
+
+/// Генерация резервных кодов вида `abcd-efgh` и их хешей
+pub fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+
+    let codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let chars: String = (0..8)
+                .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..4], &chars[4..])
+        })
+        .collect();
+    let hashes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+
+    (codes, hashes)
+}
+
+/// Хеш резервного кода (коды случайные, поэтому достаточно SHA-256)
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized = code.trim().to_lowercase();
+    hex_encode(&Sha256::digest(normalized.as_bytes()))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Новый файл ключа, доступный только владельцу; существующий не перезаписывается
+fn create_key_file(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Тестовый секрет из приложения B RFC 6238 (SHA-1)
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        let policy = TwoFactorPolicy { digits: 8, ..TwoFactorPolicy::default() };
+
+        assert_eq!(hotp(RFC_SECRET, time_step(59, 30), 8), 94287082);
+        assert_eq!(hotp(RFC_SECRET, time_step(1111111109, 30), 8), 7081804);
+        assert_eq!(hotp(RFC_SECRET, time_step(2000000000, 30), 8), 69279037);
+
+        assert!(verify_code(RFC_SECRET, "94287082", 59, &policy, None).is_some());
+        // Код предыдущего периода принимается в пределах расхождения часов
+        assert!(verify_code(RFC_SECRET, "94287082", 89, &policy, None).is_some());
+        assert!(verify_code(RFC_SECRET, "94287082", 200, &policy, None).is_none());
+        // Повторное использование того же шага отклоняется
+        assert!(verify_code(RFC_SECRET, "94287082", 59, &policy, Some(1)).is_none());
+    }
+
+    #[test]
+    fn test_secret_encryption_and_uri() {
+        let cipher = SecretCipher::ephemeral();
+        let secret = generate_secret();
+        let encrypted = cipher.encrypt(&secret).unwrap();
+
+        assert_ne!(encrypted.as_bytes(), secret.as_slice());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), secret);
+        assert!(SecretCipher::ephemeral().decrypt(&encrypted).is_err());
+
+        let uri = provisioning_uri(RFC_SECRET, "admin", &TwoFactorPolicy::default());
+        assert_eq!(
+            uri,
+            "otpauth://totp/Surveillance%20System:admin?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=Surveillance%20System&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_key_file_created_once_and_private() {
+        let path = std::env::temp_dir().join(format!("totp-key-{}", uuid::Uuid::new_v4()));
+        let cipher = SecretCipher::load_or_create(&path).unwrap();
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+
+        // Повторная загрузка читает тот же ключ, а не создаёт новый
+        let reloaded = SecretCipher::load_or_create(&path).unwrap();
+        assert_eq!(reloaded.decrypt(&encrypted).unwrap(), b"secret");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_file(&path).ok();
+    }
+}